@@ -1,21 +1,125 @@
 #[derive(Debug, clap::Parser)]
 #[command(author, version, about)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Assemble and link the input file into an executable
+    Build(BuildArgs),
+
+    /// Build the input file and run the resulting executable
+    Run(RunArgs),
+
+    /// Parse and typecheck the input file without generating any output
+    Check(CheckArgs),
+
+    /// Output an intermediate compilation stage
+    Emit(EmitArgs),
+
+    /// Generate API docs from the `///` comments in the input file
+    Doc(DocArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BuildArgs {
     /// Path to the input file
     pub file: String,
 
-    /// Run without compiling
-    #[arg(short, long)]
-    pub parse_only: bool,
+    /// Path to the output executable
+    #[arg(short, long, default_value_t = String::from("main"))]
+    pub out: String,
+
+    /// Keep the generated asm at this path
+    #[arg(long, value_name = "PATH")]
+    pub asm: Option<String>,
 
+    /// Assemble with an external nasm instead of the built-in encoder
     #[arg(long)]
-    pub dry_run: bool,
+    pub nasm: bool,
+
+    /// Optimization level, 0 disables the peephole optimizer
+    #[arg(short = 'O', default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=zasm::peephole::MAX_LEVEL as i64))]
+    pub opt_level: u8,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub build: BuildArgs,
+
+    /// Arguments passed to the executed program
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
 
-    /// Output generated asm
+#[derive(Debug, clap::Args)]
+pub struct CheckArgs {
+    /// Path to the input file
+    pub file: String,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct EmitArgs {
+    /// Path to the input file
+    pub file: String,
+
+    /// Which compilation stage to output
+    #[arg(long, value_enum)]
+    pub kind: EmitKind,
+
+    /// Path to the output file, defaults to stdout for text output
+    #[arg(short, long)]
+    pub out: Option<String>,
+
+    /// Assemble objects with an external nasm instead of the built-in encoder
     #[arg(long)]
-    pub asm: Option<String>,
+    pub nasm: bool,
 
-    /// Path to the output file
-    #[arg(short, long, default_value_t = String::from("main.o"))]
-    pub out: String,
+    /// Assembly syntax used for `--kind asm`
+    #[arg(long, value_enum, default_value_t = AsmSyntax::Nasm)]
+    pub syntax: AsmSyntax,
+
+    /// Optimization level, 0 disables the peephole optimizer
+    #[arg(short = 'O', default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=zasm::peephole::MAX_LEVEL as i64))]
+    pub opt_level: u8,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DocArgs {
+    /// Path to the input file
+    pub file: String,
+
+    /// Format of the generated docs
+    #[arg(long, value_enum, default_value_t = DocFormat::Markdown)]
+    pub format: DocFormat,
+
+    /// Path to the output file, defaults to stdout
+    #[arg(short, long)]
+    pub out: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum EmitKind {
+    Tokens,
+    Ast,
+    Ir,
+    Asm,
+    Obj,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum AsmSyntax {
+    /// Intel syntax as accepted by nasm
+    Nasm,
+    /// AT&T syntax as accepted by GNU as
+    Gas,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum DocFormat {
+    Markdown,
+    Html,
 }
@@ -8,51 +8,204 @@ use zasm::{
     Builder, Module,
 };
 
+use self::types::DataType;
 use crate::{
     error::CompilerError,
     grammar,
-    lexer::token::SourcePos,
+    lexer::{intern::Symbol, token::SourcePos, Lexer},
     parser::{
         ast::{
-            Assign, BinOp, Call, If, Loop, Module as Mod, Node, Primitive, Return, Scope,
+            Assign, AugAssign, BinOp, Call, Cast, Constant, FunctionDef, If, Index, Loop, Match,
+            Module as Mod, Name, Node, Primitive, Return, Scope, Store, Try, Unary, UnaryOp,
             VariableDef,
         },
-        ZResult,
+        Parser, ZResult,
     },
 };
 
+pub mod types;
+
+/// Functions every program can use, written in Ž.
+const PRELUDE: &str = include_str!("prelude.ž");
+/// Goes in front of the prelude's function names, a name with a dot can't
+/// be defined by a program.
+const PRELUDE_PREFIX: &str = "prelude.";
+
 #[derive(Debug, Clone)]
 struct InternalVar {
     inner: Variable,
+    ty: DataType,
     mutable: bool,
     scope: u32,
 }
 
 impl InternalVar {
-    pub fn new(inner: Variable, mutable: bool, scope: u32) -> Self {
+    pub fn new(inner: Variable, ty: DataType, mutable: bool, scope: u32) -> Self {
         Self {
             inner,
+            ty,
             mutable,
             scope,
         }
     }
 }
 
+#[derive(Debug, Clone)]
+struct Signature {
+    args: Vec<DataType>,
+    returns: Option<DataType>,
+}
+
+/// The variants of an enum with the types of their payload, a variant's
+/// tag is its position.
+#[derive(Debug, Clone)]
+struct EnumInfo {
+    variants: Vec<(Symbol, Vec<DataType>)>,
+}
+
+impl EnumInfo {
+    fn tag(&self, variant: &str) -> Option<usize> {
+        self.variants.iter().position(|(name, _)| name == variant)
+    }
+}
+
+fn error_at(pos: Option<&SourcePos>, len: usize, message: &str) -> CompilerError {
+    match pos {
+        Some(pos) => CompilerError::new(pos.line as usize, pos.column as usize, len, message),
+        None => CompilerError::new(1, 1, 1, message),
+    }
+}
+
+/// `ok`, `err` and the functions looking into results work for every
+/// result type, so they're checked by the compiler instead of a signature.
+fn is_result_fn(name: &str) -> bool {
+    [
+        grammar::F_OK,
+        grammar::F_ERR,
+        grammar::F_IS_OK,
+        grammar::F_ERROR,
+        grammar::F_UNWRAP,
+    ]
+    .contains(&name)
+}
+
+/// Whether the node can give a value, blocks end with one to have it.
+fn is_value(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Constant(_)
+            | Node::Name(..)
+            | Node::BinOp(_)
+            | Node::Call(_)
+            | Node::Cast(_)
+            | Node::Index(_)
+            | Node::Unary(_)
+            | Node::Try(_)
+            | Node::If(_)
+            | Node::Scope(_)
+    )
+}
+
+fn is_comparison(op: &Operator) -> bool {
+    matches!(
+        op,
+        Operator::DoubleEquals
+            | Operator::NotEquals
+            | Operator::Greater
+            | Operator::GreaterEquals
+            | Operator::Less
+            | Operator::LessEquals
+    )
+}
+
+/// Both sides of a binary operation need the same type, and the operator
+/// has to work on it.
+fn check_operator(
+    op: &Operator,
+    ty: DataType,
+    right_ty: DataType,
+    pos: Option<&SourcePos>,
+) -> ZResult<()> {
+    if ty != right_ty {
+        return Err(error_at(
+            pos,
+            1,
+            &format!("Mismatched types {} and {}.", ty, right_ty),
+        ));
+    }
+
+    let equality = matches!(op, Operator::DoubleEquals | Operator::NotEquals);
+    let bitwise = matches!(op, Operator::BitAnd | Operator::BitOr | Operator::BitXor);
+    let arithmetic = matches!(
+        op,
+        Operator::Add | Operator::Sub | Operator::Mult | Operator::Div
+    );
+    let supported = ty.is_integer()
+        || (ty.is_float() && (arithmetic || is_comparison(op)))
+        || (ty == DataType::Str && (equality || *op == Operator::Add))
+        || ((equality || bitwise) && ty == DataType::Bool)
+        || (equality && matches!(ty, DataType::Ptr(_)));
+    if !supported {
+        return Err(error_at(
+            pos,
+            1,
+            &format!("Operator isn't supported for {}.", ty),
+        ));
+    }
+    Ok(())
+}
+
+/// Floats are passed around as their bits, an `f32`'s are sign extended
+/// so they fit in a 32-bit immediate.
+fn float_bits(x: f64, ty: DataType) -> Operand {
+    match ty {
+        DataType::F64 => Operand::Int(x.to_bits() as i64),
+        _ => Operand::Int((x as f32).to_bits() as i32 as i64),
+    }
+}
+
 pub struct Compiler<'guard> {
     module: Module<'guard>,
     builder: Builder,
-    vars: HashMap<String, InternalVar>,
-    shadowed_vars: Vec<(String, InternalVar)>,
+    vars: HashMap<Symbol, InternalVar>,
+    shadowed_vars: Vec<(Symbol, InternalVar)>,
 
     scope_depth: u32,
     has_main: bool,
     current_labels: Vec<Label>,
+    functions: HashMap<Symbol, Signature>,
+    enums: HashMap<Symbol, EnumInfo>,
+    return_type: Option<DataType>,
+    /// Compiling one of the prelude's functions.
+    in_prelude: bool,
+    opt_level: u8,
 }
 
 impl<'guard> Compiler<'guard> {
-    pub fn compile(&mut self, source: Mod) -> ZResult<&Module> {
+    pub fn compile(&mut self, source: Mod) -> ZResult<&Module<'guard>> {
         self.module = Module::new();
         self.builder = Builder::new();
+        let source = with_prelude(source);
+
+        // Collect enums and signatures first so they can be used before
+        // they're defined.
+        self.declare_enums(&source)?;
+        self.functions.clear();
+        self.add_builtins();
+        for node in &source.body {
+            if let Node::FunctionDef(fun) = node {
+                let signature = self.signature(fun)?;
+                let reserved = is_result_fn(&fun.name)
+                    || [grammar::F_ASM, grammar::F_ASSERT_EQ].contains(&fun.name.as_str());
+                if reserved || self.functions.insert(fun.name.clone(), signature).is_some() {
+                    return Err(error_at(
+                        Some(&fun.pos),
+                        grammar::FUN.len(),
+                        &format!("Function '{}' is already defined.", fun.name),
+                    ));
+                }
+            }
+        }
 
         for node in source.body {
             self.handle_node(node)?;
@@ -62,27 +215,283 @@ impl<'guard> Compiler<'guard> {
             return Err(CompilerError::new(1, 1, 1, "Missing main function."));
         }
 
+        self.module.optimize(self.opt_level);
         Ok(&self.module)
     }
 
+    fn declare_enums(&mut self, source: &Mod) -> ZResult<()> {
+        self.enums.clear();
+        let defs = source
+            .body
+            .iter()
+            .filter_map(|node| match node {
+                Node::EnumDef(def) => Some(def),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Every name goes in first, so payloads can hold any enum.
+        for def in &defs {
+            let info = EnumInfo { variants: vec![] };
+            if DataType::from_name(&def.name).is_some()
+                || self.enums.insert(def.name.clone(), info).is_some()
+            {
+                return Err(error_at(
+                    Some(&def.pos),
+                    grammar::ENUM.len(),
+                    &format!("Type '{}' is already defined.", def.name),
+                ));
+            }
+        }
+
+        for def in defs {
+            let mut info = EnumInfo { variants: vec![] };
+            for variant in &def.variants {
+                if info.tag(&variant.name).is_some() {
+                    return Err(error_at(
+                        Some(&variant.pos),
+                        variant.name.len(),
+                        &format!("Variant '{}' is already defined.", variant.name),
+                    ));
+                }
+
+                let fields = variant
+                    .fields
+                    .iter()
+                    .map(|field| self.resolve_type(field))
+                    .collect::<ZResult<Vec<_>>>()?;
+                info.variants.push((variant.name.clone(), fields));
+            }
+            self.enums.insert(def.name.clone(), info);
+        }
+        Ok(())
+    }
+
+    /// `Enum.Variant` names a variant of a declared enum.
+    fn is_variant(&self, path: &str) -> bool {
+        path.split_once('.')
+            .is_some_and(|(name, _)| self.enums.contains_key(name))
+    }
+
+    /// Makes the value of a variant, `Shape.Circle(1.5)`.
+    fn build_variant(
+        &mut self,
+        path: &str,
+        args: Vec<Node>,
+        pos: &SourcePos,
+    ) -> ZResult<(Reg, DataType)> {
+        let (name, variant) = path.split_once('.').expect("Variants have a dot.");
+        let info = &self.enums[name];
+        let tag = info.tag(variant).ok_or_else(|| {
+            error_at(
+                Some(pos),
+                path.len(),
+                &format!("Enum '{}' has no variant '{}'.", name, variant),
+            )
+        })?;
+
+        let fields = info.variants[tag].1.clone();
+        if fields.len() != args.len() {
+            return Err(error_at(
+                Some(pos),
+                path.len(),
+                &format!(
+                    "Variant '{}' holds {} value(s), but {} were given.",
+                    variant,
+                    fields.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        let saved = self.builder.save_registers();
+        let count = args.len();
+        for (arg, ty) in args.into_iter().zip(fields).rev() {
+            let arg_pos = arg.pos().cloned().unwrap_or(pos.clone());
+            let (value, found) = self.make_operand(arg, Some(ty))?;
+            expect_type(ty, found, &arg_pos)?;
+            self.builder.build_push(value);
+        }
+        self.builder.build_push(Operand::Int(count as i64));
+        self.builder.build_push(Operand::Int(tag as i64));
+        self.builder.call_by_name("enum_new");
+        self.builder.drop_args(count + 2);
+
+        let ty = DataType::enum_named(name);
+        let reg = self.builder.call_result(ty.scalar().reg_size());
+        self.builder.restore_registers(saved);
+        Ok((reg, ty))
+    }
+
+    /// Compares the tag with every arm's variant in turn and binds the
+    /// payload of the one that matches.
+    fn build_match(&mut self, r#match: Match) -> ZResult<()> {
+        let pos = r#match.value.pos().cloned().unwrap_or(r#match.pos.clone());
+        let (value, ty) = self.make_operand(*r#match.value, None)?;
+        let DataType::Enum(name) = ty else {
+            return Err(error_at(
+                Some(&pos),
+                1,
+                &format!("Only enums can be matched, found {}.", ty),
+            ));
+        };
+        let info = self.enums[name].clone();
+        let prefix = format!("{}.", name);
+
+        // Every variant needs an arm, unless there's a `_` one.
+        let mut tags = vec![];
+        let mut covered = vec![false; info.variants.len()];
+        let mut wildcard = false;
+        for arm in &r#match.arms {
+            let len = arm.variant.as_ref().map_or(1, |variant| variant.len());
+            if wildcard {
+                return Err(error_at(
+                    Some(&arm.pos),
+                    len,
+                    "This arm is unreachable, '_' matches everything before it.",
+                ));
+            }
+            let Some(variant) = &arm.variant else {
+                wildcard = true;
+                tags.push(None);
+                continue;
+            };
+
+            let variant = variant.strip_prefix(&prefix).unwrap_or(variant);
+            let tag = info.tag(variant).ok_or_else(|| {
+                error_at(
+                    Some(&arm.pos),
+                    len,
+                    &format!("Enum '{}' has no variant '{}'.", name, variant),
+                )
+            })?;
+            if covered[tag] {
+                return Err(error_at(
+                    Some(&arm.pos),
+                    len,
+                    &format!("Variant '{}' is already matched.", variant),
+                ));
+            }
+            covered[tag] = true;
+
+            let fields = info.variants[tag].1.len();
+            if arm.bindings.len() != fields {
+                return Err(error_at(
+                    Some(&arm.pos),
+                    len,
+                    &format!(
+                        "Variant '{}' holds {} value(s), but {} were bound.",
+                        variant,
+                        fields,
+                        arm.bindings.len()
+                    ),
+                ));
+            }
+            tags.push(Some(tag));
+        }
+
+        let missing = info
+            .variants
+            .iter()
+            .zip(&covered)
+            .filter(|(_, covered)| !**covered)
+            .map(|((variant, _), _)| variant.as_str())
+            .collect::<Vec<_>>();
+        if !wildcard && !missing.is_empty() {
+            return Err(error_at(
+                Some(&r#match.pos),
+                grammar::MATCH.len(),
+                &format!("Missing variants in match: {}.", missing.join(", ")),
+            ));
+        }
+
+        let subject = self.builder.make_var(value, ty.scalar());
+        let end = self.builder.get_label();
+        for (arm, tag) in r#match.arms.into_iter().zip(tags) {
+            self.add_scope();
+            let next = self.builder.get_label();
+
+            if let Some(tag) = tag {
+                self.builder.build_tag_check(&subject, tag as i64, &next);
+                let fields = &info.variants[tag].1;
+                for (i, (binding, ty)) in arm.bindings.into_iter().zip(fields).enumerate() {
+                    if binding == grammar::WILDCARD {
+                        continue;
+                    }
+                    let reg = self.builder.build_field(&subject, i, ty.scalar());
+                    let inner = self.builder.make_var(Operand::Reg(reg), ty.scalar());
+                    let var = InternalVar::new(inner, *ty, false, self.scope_depth);
+                    self.declare_var(binding, var);
+                }
+            }
+
+            for node in arm.body {
+                self.handle_node(node)?;
+            }
+            self.builder.build_jump(&end, Jump::Always);
+            self.builder.insert_label(&next);
+            self.clear_scope();
+        }
+        self.builder.insert_label(&end);
+        Ok(())
+    }
+
+    /// `main` can take the program's arguments and its result becomes
+    /// the exit code.
+    fn check_main(&mut self, main: &FunctionDef, signature: &Signature) -> ZResult<()> {
+        let args = DataType::array_of(DataType::Str);
+        match signature.args.as_slice() {
+            [] => {}
+            [ty] if *ty == args => {}
+            _ => {
+                let pos = main.args.iter().find_map(|arg| match arg {
+                    Node::Arg(arg) => arg.annotation.pos(),
+                    _ => None,
+                });
+                return Err(error_at(pos, 1, &format!("main can only take {}.", args)));
+            }
+        }
+
+        match signature.returns {
+            None | Some(DataType::I32) => {}
+            Some(_) => return Err(error_at(main.returns.pos(), 1, "main can only return int.")),
+        }
+
+        self.module
+            .set_main(!signature.args.is_empty(), signature.returns.is_some());
+        Ok(())
+    }
+
     fn handle_node(&mut self, node: Node) -> ZResult<()> {
         match node {
             Node::FunctionDef(fun) => {
                 let mut f = func::Function::new(&fun.name);
+                let signature = self.functions[&fun.name].clone();
+                self.return_type = signature.returns;
+                self.in_prelude = fun.name.starts_with(PRELUDE_PREFIX);
+                if fun.name == grammar::F_MAIN {
+                    self.check_main(&fun, &signature)?;
+                }
+
+                // Variables belong to the function they're defined in.
+                self.vars.clear();
+                self.shadowed_vars.clear();
 
                 let return_label = self.builder.get_label();
                 self.current_labels.clear();
                 self.current_labels.push(return_label);
 
                 let mut offset = 16;
-                for arg in fun.args {
-                    if let Node::Arg(arg) = arg {
-                        let inner = Variable::new(offset);
-                        offset += 8;
+                let args = fun.args.into_iter().filter_map(|arg| match arg {
+                    Node::Arg(arg) => Some(arg),
+                    _ => None,
+                });
+                for (arg, ty) in args.zip(signature.args) {
+                    let inner = Variable::new(offset, ty.scalar());
+                    offset += 8;
 
-                        let var = InternalVar::new(inner, false, self.scope_depth);
-                        self.vars.insert(arg.name, var);
-                    }
+                    let var = InternalVar::new(inner, ty, false, self.scope_depth);
+                    self.vars.insert(arg.name, var);
                 }
 
                 for node in fun.body {
@@ -103,17 +512,59 @@ impl<'guard> Compiler<'guard> {
             }
             Node::Scope(scope) => self.build_scope(scope)?,
             Node::BinOp(binop) => {
-                let tmp = self.build_binop(binop)?;
+                let (tmp, _) = self.build_binop(binop, None)?;
                 self.builder.free_reg(tmp);
             }
+            Node::Cast(cast) => {
+                let (tmp, _) = self.build_cast(cast)?;
+                self.builder.free_reg(tmp);
+            }
+            Node::Index(index) => {
+                let (tmp, _) = self.build_index(index)?;
+                self.builder.free_reg(tmp);
+            }
+            Node::Call(call) if call.func.id == grammar::F_LEN => {
+                let tmp = self.build_len(call)?;
+                self.builder.free_reg(tmp);
+            }
+            Node::Call(call) if call.func.id == grammar::F_ASSERT_EQ => {
+                self.build_assert_eq(call)?
+            }
+            Node::Call(call) if is_result_fn(&call.func.id) => {
+                let (tmp, _) = self.build_result_call(call, None)?;
+                self.builder.free_reg(tmp);
+            }
+            Node::Try(try_) => {
+                let (tmp, _) = self.build_try(try_)?;
+                self.builder.free_reg(tmp);
+            }
+            Node::Unary(unary) => {
+                let (tmp, _) = self.build_unary(unary, None)?;
+                self.builder.free_reg(tmp);
+            }
+            Node::Store(store) => self.build_store(store)?,
             Node::VariableDef(var) => self.build_var(var)?,
             Node::Assign(ass) => self.build_assign(ass)?,
-            Node::Call(call) => self.build_call(call)?,
+            Node::AugAssign(assign) => self.build_aug_assign(assign)?,
+            Node::Call(call) => {
+                if let Some((tmp, _)) = self.build_call(call)? {
+                    self.builder.free_reg(tmp);
+                }
+            }
             Node::Return(ret) => self.build_return(ret)?,
+            Node::Match(r#match) => self.build_match(r#match)?,
+            // Enums are collected before compiling.
+            Node::EnumDef(_) => {}
             Node::If(case) => self.build_if(case)?,
             Node::Loop(r#loop) => self.build_loop(r#loop)?,
             Node::Break(br) => self.build_break(br)?,
-            _ => panic!("Unknown node {:?}", node),
+            node => {
+                return Err(error_at(
+                    node.pos(),
+                    1,
+                    "Expected a statement, this value isn't used.",
+                ))
+            }
         }
 
         Ok(())
@@ -132,13 +583,11 @@ impl<'guard> Compiler<'guard> {
 
     fn build_if(&mut self, case: If) -> ZResult<()> {
         self.add_scope();
-        self.handle_node(*case.test)?;
-        self.builder.write_raw("    cmp eax, 1\n");
-
+        let pos = case.test.pos().cloned().unwrap_or(case.pos.clone());
         let label1 = self.builder.get_label();
         let label2 = self.builder.get_label();
 
-        self.builder.build_jump(&label1, Jump::NotEqual);
+        self.build_condition(*case.test, &label1, false, &pos)?;
         for node in case.run.body {
             self.handle_node(node)?;
         }
@@ -158,6 +607,113 @@ impl<'guard> Compiler<'guard> {
         Ok(())
     }
 
+    /// Jumps to `target` when `test` comes out as `when`. Comparisons feed
+    /// their flags straight into the jump, anything else has to be a bool.
+    fn build_condition(
+        &mut self,
+        test: Node,
+        target: &Label,
+        when: bool,
+        pos: &SourcePos,
+    ) -> ZResult<()> {
+        let binop = match test {
+            Node::BinOp(binop) if is_comparison(&binop.op) => binop,
+            test => {
+                let (test, ty) = self.make_operand(test, Some(DataType::Bool))?;
+                expect_type(DataType::Bool, ty, pos)?;
+                self.builder.build_test(test, target, when);
+                return Ok(());
+            }
+        };
+
+        let op_pos = binop.left.pos().or(binop.right.pos()).cloned();
+        let ((left, ty), (right, right_ty)) =
+            self.binop_operands(*binop.left, *binop.right, true, None)?;
+        check_operator(&binop.op, ty, right_ty, op_pos.as_ref())?;
+
+        match ty {
+            DataType::Str => {
+                let reg = self.builder.build_str_op(left, right, binop.op);
+                self.builder.build_test(Operand::Reg(reg), target, when);
+            }
+            ty => self
+                .builder
+                .build_branch(left, right, binop.op, ty.scalar(), target, when),
+        }
+        Ok(())
+    }
+
+    /// An `if` used as a value, each branch leaves its value in the same
+    /// register.
+    fn build_if_value(&mut self, case: If, hint: Option<DataType>) -> ZResult<(Reg, DataType)> {
+        if *case.orelse == Node::None {
+            return Err(error_at(
+                Some(&case.pos),
+                grammar::IF.len(),
+                "An if used as a value needs an else.",
+            ));
+        }
+
+        let pos = case.test.pos().cloned().unwrap_or(case.pos.clone());
+        let orelse = self.builder.get_label();
+        let end = self.builder.get_label();
+        self.build_condition(*case.test, &orelse, false, &pos)?;
+
+        self.add_scope();
+        let (value, ty) = self.build_block_value(case.run.body, hint, &case.pos)?;
+        let reg = self
+            .builder
+            .store_to_reg(value, None, ty.scalar().reg_size());
+        self.clear_scope();
+        self.builder.build_jump(&end, Jump::Always);
+
+        self.builder.insert_label(&orelse);
+        let pos = match *case.orelse {
+            Node::Scope(ref scope) => scope.body.last().and_then(Node::pos),
+            ref other => other.pos(),
+        };
+        let pos = pos.cloned().unwrap_or(case.pos.clone());
+        let (value, found) = match *case.orelse {
+            Node::Scope(scope) => {
+                self.add_scope();
+                let value = self.build_block_value(scope.body, Some(ty), &case.pos)?;
+                self.clear_scope();
+                value
+            }
+            other => self.make_operand(other, Some(ty))?,
+        };
+        expect_type(ty, found, &pos)?;
+        let reg = self
+            .builder
+            .store_to_reg(value, Some(reg), ty.scalar().reg_size());
+
+        self.builder.insert_label(&end);
+        Ok((reg, ty))
+    }
+
+    /// Compiles a block used as a value, which is its last expression.
+    /// The caller opens and closes its scope.
+    fn build_block_value(
+        &mut self,
+        mut body: Vec<Node>,
+        hint: Option<DataType>,
+        pos: &SourcePos,
+    ) -> ZResult<(Operand, DataType)> {
+        let last = body.pop();
+        for node in body {
+            self.handle_node(node)?;
+        }
+
+        match last {
+            Some(node) if is_value(&node) => self.make_operand(node, hint),
+            last => Err(error_at(
+                last.as_ref().and_then(Node::pos).or(Some(pos)),
+                1,
+                "This block doesn't end with a value.",
+            )),
+        }
+    }
+
     fn add_scope(&mut self) {
         self.scope_depth += 1;
     }
@@ -237,97 +793,545 @@ impl<'guard> Compiler<'guard> {
                     .to_string();
 
                 if out.is_empty() {
-                    return Err(CompilerError::new(1, 1, 1, "Bruhhhh"));
+                    return Err(error_at(
+                        Some(&constant.pos),
+                        1,
+                        "Inline assembly can't be empty.",
+                    ));
+                }
+                if let Err(err) = self.builder.write_asm(&out) {
+                    return Err(error_at(
+                        Some(&constant.pos),
+                        1,
+                        &format!("Invalid inline assembly: {}", err),
+                    ));
                 }
-                self.builder.write_raw_fmt(&out);
             } else {
-                panic!("Only constants can be used in inline asm.")
+                return Err(error_at(
+                    arg.pos(),
+                    1,
+                    "Only constants can be used in inline asm.",
+                ));
             }
         }
         Ok(())
     }
 
-    fn build_call(&mut self, mut call: Call) -> ZResult<()> {
+    /// Builds the call and returns its result, if it has one.
+    fn build_call(&mut self, call: Call) -> ZResult<Option<(Reg, DataType)>> {
         if call.func.id == grammar::F_ASM {
-            return Ok(self.build_inline_asm(call))?;
+            self.build_inline_asm(call)?;
+            return Ok(None);
+        }
+        if self.is_variant(&call.func.id) {
+            return self
+                .build_variant(&call.func.id, call.args, &call.pos)
+                .map(Some);
         }
 
         let n_args = call.args.len();
-        call.args.reverse();
-        for arg in call.args {
-            let value = self.make_operand(arg)?;
+        let callee = self.callee(&call.func.id);
+        let (params, returns) = match self.functions.get(callee.as_str()) {
+            Some(signature) => {
+                if signature.args.len() != n_args {
+                    return Err(error_at(
+                        Some(&call.pos),
+                        call.func.id.len(),
+                        &format!(
+                            "Function '{}' takes {} argument(s), but {} were given.",
+                            call.func.id,
+                            signature.args.len(),
+                            n_args
+                        ),
+                    ));
+                }
+                (signature.args.clone(), signature.returns)
+            }
+            None => {
+                return Err(error_at(
+                    Some(&call.pos),
+                    call.func.id.len(),
+                    &format!("Unknown function '{}'.", call.func.id),
+                ))
+            }
+        };
+
+        let saved = self.builder.save_registers();
+        let args = call.args.into_iter().zip(params).rev();
+        for (arg, param) in args {
+            let pos = arg.pos().cloned().unwrap_or(call.pos.clone());
+            let (value, ty) = self.make_operand(arg, Some(param))?;
+            expect_type(param, ty, &pos)?;
             self.builder.build_push(value);
         }
-        self.builder.call_by_name(&call.func.id);
+        self.builder.call_by_name(&callee);
+        self.builder.drop_args(n_args);
 
-        // Temporarely create the rsp register that gets deleted
-        // after this function exits.
-        let reg = Operand::Reg(Reg::new("rsp"));
-        self.builder
-            .build_op(reg, Operand::Int((n_args * 8) as i32), Operator::Add);
-        Ok(())
+        let result = returns.map(|ty| (self.builder.call_result(ty.scalar().reg_size()), ty));
+        self.builder.restore_registers(saved);
+        Ok(result)
     }
 
     fn build_return(&mut self, ret: Return) -> ZResult<()> {
-        let operand = self.make_operand(*ret.value)?;
-        self.builder.build_return(
-            operand,
-            self.current_labels
-                .first()
-                .expect("Function return label was not found."),
-        );
+        let label = self
+            .current_labels
+            .first()
+            .expect("Function return label was not found.");
+
+        if *ret.value == Node::None {
+            self.builder.build_jump(label, Jump::Always);
+            return Ok(());
+        }
+
+        let pos = ret.value.pos().cloned();
+        let expected = match self.return_type {
+            Some(ty) => ty,
+            None => {
+                return Err(error_at(
+                    pos.as_ref(),
+                    1,
+                    "This function doesn't return a value.",
+                ))
+            }
+        };
+
+        let (operand, ty) = self.make_operand(*ret.value, Some(expected))?;
+        if let Some(pos) = &pos {
+            expect_type(expected, ty, pos)?;
+        }
+
+        let label = self
+            .current_labels
+            .first()
+            .expect("Function return label was not found.");
+        self.builder.build_return(operand, label, expected.scalar());
         Ok(())
     }
 
-    fn build_assign(&mut self, assign: Assign) -> ZResult<()> {
-        let value = self.make_operand(*assign.value)?;
-
-        let var = match self.vars.get(&assign.target) {
-            Some(var) => var,
+    /// The variable an assignment writes to, it has to exist and be mutable.
+    fn assign_target(&self, target: &str, pos: &SourcePos) -> ZResult<InternalVar> {
+        let var = match self.vars.get(target) {
+            Some(var) => var.clone(),
             None => {
                 return Err(CompilerError::new(
-                    assign.pos.line as usize,
-                    assign.pos.column as usize,
-                    assign.target.len(),
-                    &format!("Variable '{}', not found in scope.", assign.target),
+                    pos.line as usize,
+                    pos.column as usize,
+                    target.len(),
+                    &format!("Variable '{}', not found in scope.", target),
                 ))
             }
         };
         // checks if var is mutable
         if !var.mutable {
             return Err(CompilerError::new(
-                assign.pos.line as usize,
-                assign.pos.column as usize,
-                assign.target.len(),
+                pos.line as usize,
+                pos.column as usize,
+                target.len(),
                 "Variable is imutable.",
             ));
         }
+        Ok(var)
+    }
+
+    fn build_assign(&mut self, assign: Assign) -> ZResult<()> {
+        let var = self.assign_target(&assign.target, &assign.pos)?;
+
+        let (value, ty) = self.make_operand(*assign.value, Some(var.ty))?;
+        expect_type(var.ty, ty, &assign.pos)?;
 
         self.builder.assign_var(value, &var.inner);
         Ok(())
     }
 
-    fn build_binop(&mut self, binop: BinOp) -> ZResult<Reg> {
-        let left = self.make_operand(*binop.left)?;
-        let right = self.make_operand(*binop.right)?;
+    /// `x += 1` adds to the variable in memory when it's an integer,
+    /// everything else is compiled as `x = x + 1`.
+    fn build_aug_assign(&mut self, assign: AugAssign) -> ZResult<()> {
+        let var = self.assign_target(&assign.target, &assign.pos)?;
+
+        let in_place = matches!(
+            assign.op,
+            Operator::Add | Operator::Sub | Operator::BitAnd | Operator::BitOr | Operator::BitXor
+        );
+        if var.ty.is_integer() && in_place {
+            let pos = assign.value.pos().cloned().unwrap_or(assign.pos.clone());
+            let (value, ty) = self.make_operand(*assign.value, Some(var.ty))?;
+            expect_type(var.ty, ty, &pos)?;
+            self.builder.build_in_place(&var.inner, value, assign.op);
+            return Ok(());
+        }
+
+        let binop = BinOp {
+            left: Box::new(Node::Name(Name { id: assign.target }, assign.pos.clone())),
+            op: assign.op,
+            right: assign.value,
+        };
+        let (value, ty) = self.build_binop(binop, Some(var.ty))?;
+        expect_type(var.ty, ty, &assign.pos)?;
+
+        self.builder.assign_var(Operand::Reg(value), &var.inner);
+        Ok(())
+    }
+
+    /// Compiles both sides of a binary operation, literals take the type of
+    /// the other side.
+    fn binop_operands(
+        &mut self,
+        left: Node,
+        right: Node,
+        comparison: bool,
+        hint: Option<DataType>,
+    ) -> ZResult<((Operand, DataType), (Operand, DataType))> {
+        let hint = self
+            .natural_type(&left)
+            .or_else(|| self.natural_type(&right))
+            .or(if comparison { None } else { hint });
 
-        Ok(self.builder.build_op(left, right, binop.op))
+        let (left, ty) = self.make_operand(left, hint)?;
+        let right = self.make_operand(right, Some(ty))?;
+        Ok(((left, ty), right))
     }
 
-    fn build_var(&mut self, var: VariableDef) -> ZResult<()> {
-        let value = self.make_operand(*var.value)?;
-        let inner = self.builder.make_var(value);
-        let old = self.vars.insert(
-            var.name.clone(),
-            InternalVar::new(inner, var.mutable, self.scope_depth),
-        );
+    fn build_binop(&mut self, binop: BinOp, hint: Option<DataType>) -> ZResult<(Reg, DataType)> {
+        let comparison = is_comparison(&binop.op);
+        let pos = binop.left.pos().or(binop.right.pos()).cloned();
+        let ((left, ty), (right, right_ty)) =
+            self.binop_operands(*binop.left, *binop.right, comparison, hint)?;
+
+        // Moving a pointer counts in elements, not bytes.
+        if let (Some(pointee), Operator::Add | Operator::Sub) = (ty.pointee(), &binop.op) {
+            if right_ty.is_integer() {
+                let index = match right_ty {
+                    DataType::I64 | DataType::U64 => right,
+                    from => Operand::Reg(self.builder.build_cast(
+                        right,
+                        from.scalar(),
+                        DataType::I64.scalar(),
+                    )),
+                };
+                let stride = pointee.scalar().size;
+                let reg = self.builder.build_offset(left, index, stride, binop.op);
+                return Ok((reg, ty));
+            }
+        }
+
+        check_operator(&binop.op, ty, right_ty, pos.as_ref())?;
+
+        let reg = match ty {
+            DataType::Str => self.builder.build_str_op(left, right, binop.op),
+            ty => self.builder.build_op(left, right, binop.op, ty.scalar()),
+        };
+        Ok((reg, if comparison { DataType::Bool } else { ty }))
+    }
+
+    /// `len(s)` is compiled inline instead of calling the runtime.
+    fn build_len(&mut self, call: Call) -> ZResult<Reg> {
+        let [value]: [Node; 1] = call.args.try_into().map_err(|args: Vec<Node>| {
+            error_at(
+                Some(&call.pos),
+                grammar::F_LEN.len(),
+                &format!(
+                    "Function 'len' takes 1 argument(s), but {} were given.",
+                    args.len()
+                ),
+            )
+        })?;
+
+        let pos = value.pos().cloned().unwrap_or(call.pos);
+        let (sequence, ty) = self.make_operand(value, Some(DataType::Str))?;
+        self.element(ty, &pos)?;
+        Ok(self.builder.build_len(sequence))
+    }
+
+    /// `assert_eq(a, b)` works for every type `==` does, so it's compiled
+    /// as `assert(a == b)`.
+    fn build_assert_eq(&mut self, call: Call) -> ZResult<()> {
+        let [left, right]: [Node; 2] = call.args.try_into().map_err(|args: Vec<Node>| {
+            error_at(
+                Some(&call.pos),
+                grammar::F_ASSERT_EQ.len(),
+                &format!(
+                    "Function 'assert_eq' takes 2 argument(s), but {} were given.",
+                    args.len()
+                ),
+            )
+        })?;
+
+        let test = Node::BinOp(BinOp {
+            left: Box::new(left),
+            op: Operator::DoubleEquals,
+            right: Box::new(right),
+        });
+        self.build_call(Call {
+            func: Name {
+                id: grammar::F_ASSERT.into(),
+            },
+            args: vec![test],
+            pos: call.pos,
+        })?;
+        Ok(())
+    }
+
+    /// Indexing a string gives the byte at that position, indexing an
+    /// array gives the element.
+    fn build_index(&mut self, index: Index) -> ZResult<(Reg, DataType)> {
+        let pos = index.value.pos().cloned().unwrap_or(index.pos.clone());
+        let (sequence, ty) = self.make_operand(*index.value, None)?;
+        let element = self.element(ty, &pos)?;
+
+        let (value, ty) = self.make_operand(*index.index, Some(DataType::U64))?;
+        if !ty.is_integer() {
+            return Err(error_at(
+                Some(&index.pos),
+                1,
+                &format!("Can't index with {}.", ty),
+            ));
+        }
+
+        // Negative indices wrap around and fail the bounds check.
+        let value = match ty {
+            DataType::U64 => value,
+            ty => Operand::Reg(
+                self.builder
+                    .build_cast(value, ty.scalar(), DataType::U64.scalar()),
+            ),
+        };
+
+        let reg = self.builder.build_index(sequence, value, element.scalar());
+        Ok((reg, element))
+    }
+
+    /// Calls the runtime function behind `ok`, `err`, `is_ok`, `error` or
+    /// `unwrap`, `hint` tells `ok` and `err` which result they make.
+    fn build_result_call(
+        &mut self,
+        call: Call,
+        hint: Option<DataType>,
+    ) -> ZResult<(Reg, DataType)> {
+        let name = call.func.id.clone();
+        let [value]: [Node; 1] = call.args.try_into().map_err(|args: Vec<Node>| {
+            error_at(
+                Some(&call.pos),
+                name.len(),
+                &format!(
+                    "Function '{}' takes 1 argument(s), but {} were given.",
+                    name,
+                    args.len()
+                ),
+            )
+        })?;
+        let pos = value.pos().cloned().unwrap_or(call.pos.clone());
+        let hint = hint.filter(|ty| ty.ok_type().is_some());
+
+        let saved = self.builder.save_registers();
+        let (value, returns) = match name.as_str() {
+            grammar::F_OK => {
+                let (value, ty) = self.make_operand(value, hint.and_then(DataType::ok_type))?;
+                (value, DataType::result_of(ty))
+            }
+            grammar::F_ERR => {
+                let returns = hint.ok_or_else(|| {
+                    error_at(
+                        Some(&call.pos),
+                        name.len(),
+                        "Can't tell which result this is, annotate it.",
+                    )
+                })?;
+                let (value, ty) = self.make_operand(value, Some(DataType::I64))?;
+                expect_type(DataType::I64, ty, &pos)?;
+                (value, returns)
+            }
+            _ => {
+                let (value, ty) = self.make_operand(value, None)?;
+                let ok_type = ty.ok_type().ok_or_else(|| {
+                    error_at(
+                        Some(&pos),
+                        1,
+                        &format!("Mismatched types, expected a result but found {}.", ty),
+                    )
+                })?;
+                let returns = match name.as_str() {
+                    grammar::F_IS_OK => DataType::Bool,
+                    grammar::F_ERROR => DataType::I64,
+                    _ => ok_type,
+                };
+                (value, returns)
+            }
+        };
+
+        self.builder.build_push(value);
+        self.builder.call_by_name(&format!("result_{}", name));
+        self.builder.drop_args(1);
+        let reg = self.builder.call_result(returns.scalar().reg_size());
+        self.builder.restore_registers(saved);
+        Ok((reg, returns))
+    }
+
+    /// `value?` returns the error from the function, which has to return
+    /// a result as well.
+    fn build_try(&mut self, try_: Try) -> ZResult<(Reg, DataType)> {
+        if self.return_type.and_then(DataType::ok_type).is_none() {
+            return Err(error_at(
+                Some(&try_.pos),
+                1,
+                "'?' can only be used in a function that returns a result.",
+            ));
+        }
+
+        let pos = try_.value.pos().cloned().unwrap_or(try_.pos.clone());
+        let (result, ty) = self.make_operand(*try_.value, None)?;
+        let value = ty.ok_type().ok_or_else(|| {
+            error_at(
+                Some(&pos),
+                1,
+                &format!("Only results can be unwrapped with '?', found {}.", ty),
+            )
+        })?;
+
+        let label = self
+            .current_labels
+            .first()
+            .expect("Function return label was not found.");
+        let reg = self.builder.build_try(result, label, value.scalar());
+        Ok((reg, value))
+    }
+
+    fn build_unary(&mut self, unary: Unary, hint: Option<DataType>) -> ZResult<(Reg, DataType)> {
+        match unary.op {
+            UnaryOp::AddressOf => {
+                let var = match *unary.value {
+                    Node::Name(name, pos) => match self.vars.get(&name.id) {
+                        Some(var) => var.clone(),
+                        None => {
+                            return Err(error_at(
+                                Some(&pos),
+                                name.id.len(),
+                                &format!("Variable '{}' not found in scope.", name.id),
+                            ))
+                        }
+                    },
+                    _ => {
+                        return Err(error_at(
+                            Some(&unary.pos),
+                            1,
+                            "Only variables have an address.",
+                        ))
+                    }
+                };
+
+                let reg = self.builder.build_address(&var.inner);
+                Ok((reg, DataType::pointer_to(var.ty)))
+            }
+            UnaryOp::Deref => {
+                let (pointer, ty) = self.make_operand(*unary.value, None)?;
+                let pointee = self.pointee(ty, &unary.pos)?;
+                Ok((self.builder.build_deref(pointer, pointee.scalar()), pointee))
+            }
+            UnaryOp::BitNot => {
+                let pos = unary.value.pos().cloned().unwrap_or(unary.pos);
+                let (value, ty) = self.make_operand(*unary.value, hint)?;
+                if !ty.is_integer() {
+                    return Err(error_at(
+                        Some(&pos),
+                        1,
+                        &format!("'~' only works on integers, found {}.", ty),
+                    ));
+                }
+                Ok((self.builder.build_not(value, ty.scalar()), ty))
+            }
+        }
+    }
+
+    /// `*pointer = value`
+    fn build_store(&mut self, store: Store) -> ZResult<()> {
+        let (pointer, ty) = self.make_operand(*store.pointer, None)?;
+        let pointee = self.pointee(ty, &store.pos)?;
 
-        if let Some(old) = old {
-            self.shadowed_vars.push((var.name, old));
+        let pos = store.value.pos().cloned().unwrap_or(store.pos);
+        let (value, ty) = self.make_operand(*store.value, Some(pointee))?;
+        expect_type(pointee, ty, &pos)?;
+
+        self.builder.build_store(pointer, value, pointee.scalar());
+        Ok(())
+    }
+
+    fn pointee(&self, ty: DataType, pos: &SourcePos) -> ZResult<DataType> {
+        ty.pointee().ok_or_else(|| {
+            error_at(
+                Some(pos),
+                1,
+                &format!("Can't dereference {}, it isn't a pointer.", ty),
+            )
+        })
+    }
+
+    fn element(&self, ty: DataType, pos: &SourcePos) -> ZResult<DataType> {
+        ty.element().ok_or_else(|| {
+            error_at(
+                Some(pos),
+                1,
+                &format!("Can't index {}, it isn't a string or an array.", ty),
+            )
+        })
+    }
+
+    fn build_cast(&mut self, cast: Cast) -> ZResult<(Reg, DataType)> {
+        let to = self.resolve_name(&cast.target, &cast.pos)?;
+        let (value, from) = self.make_operand(*cast.value, None)?;
+
+        if !from.can_cast(to) {
+            return Err(error_at(
+                Some(&cast.pos),
+                grammar::AS.len(),
+                &format!("Can't cast {} to {}.", from, to),
+            ));
         }
 
+        let reg = self.builder.build_cast(value, from.scalar(), to.scalar());
+        Ok((reg, to))
+    }
+
+    fn build_var(&mut self, var: VariableDef) -> ZResult<()> {
+        let annotation = match *var.annotation {
+            Node::None => None,
+            ref node => Some(self.resolve_type(node)?),
+        };
+
+        let (value, ty) = match (*var.value, annotation) {
+            (Node::None, Some(ty)) => (Operand::Int(0), ty),
+            (Node::None, None) => {
+                return Err(CompilerError::new(
+                    1,
+                    1,
+                    1,
+                    &format!("Variable '{}' needs a type or a value.", var.name),
+                ))
+            }
+            (value, annotation) => {
+                let pos = value.pos().cloned();
+                let (operand, ty) = self.make_operand(value, annotation)?;
+                if let (Some(expected), Some(pos)) = (annotation, &pos) {
+                    expect_type(expected, ty, pos)?;
+                }
+                (operand, annotation.unwrap_or(ty))
+            }
+        };
+
+        let inner = self.builder.make_var(value, ty.scalar());
+        let inner = InternalVar::new(inner, ty, var.mutable, self.scope_depth);
+        self.declare_var(var.name, inner);
         Ok(())
     }
+
+    /// Adds a variable to the current scope, it shadows one with the same
+    /// name from an outer scope until this one ends.
+    fn declare_var(&mut self, name: Symbol, var: InternalVar) {
+        let old = self.vars.insert(name.clone(), var);
+
+        // Redefining a variable in the same scope replaces it for good.
+        if let Some(old) = old.filter(|old| old.scope < self.scope_depth) {
+            self.shadowed_vars.push((name, old));
+        }
+    }
 }
 
 impl<'guard> Compiler<'guard> {
@@ -340,23 +1344,242 @@ impl<'guard> Compiler<'guard> {
             scope_depth: 0,
             has_main: false,
             shadowed_vars: vec![],
+            functions: HashMap::default(),
+            enums: HashMap::default(),
+            return_type: None,
+            in_prelude: false,
+            opt_level: 0,
+        }
+    }
+
+    /// Functions provided by the runtime.
+    fn add_builtins(&mut self) {
+        let bytes = DataType::pointer_to(DataType::U8);
+        let builtins = [
+            (grammar::F_PRINT, vec![DataType::Str], None),
+            (grammar::F_LEN, vec![DataType::Str], Some(DataType::U64)),
+            (grammar::F_ALLOC, vec![DataType::U64], Some(bytes)),
+            (grammar::F_FREE, vec![bytes], None),
+            (grammar::F_REALLOC, vec![bytes, DataType::U64], Some(bytes)),
+            (
+                grammar::F_STR_FROM,
+                vec![bytes, DataType::U64],
+                Some(DataType::Str),
+            ),
+            (
+                grammar::F_ARGS,
+                vec![],
+                Some(DataType::array_of(DataType::Str)),
+            ),
+            (grammar::F_ARG_COUNT, vec![], Some(DataType::U64)),
+            (grammar::F_ENV, vec![DataType::Str], Some(DataType::Str)),
+        ];
+
+        for (name, args, returns) in builtins {
+            self.functions
+                .insert(name.into(), Signature { args, returns });
+        }
+    }
+
+    /// The function a call to `name` reaches. The prelude always calls its
+    /// own functions, the program only when it doesn't define the name.
+    fn callee(&self, name: &str) -> String {
+        let prelude = format!("{}{}", PRELUDE_PREFIX, name);
+        let own = !self.in_prelude && self.functions.contains_key(name);
+        if !own && self.functions.contains_key(prelude.as_str()) {
+            return prelude;
         }
+        name.to_owned()
+    }
+
+    /// Sets the peephole optimization level applied after compiling.
+    pub fn set_opt_level(&mut self, level: u8) {
+        self.opt_level = level;
     }
 
-    pub fn make_operand(&mut self, node: Node) -> ZResult<Operand> {
+    fn signature(&self, fun: &FunctionDef) -> ZResult<Signature> {
+        let mut args = vec![];
+        for arg in &fun.args {
+            if let Node::Arg(arg) = arg {
+                args.push(self.resolve_type(&arg.annotation)?);
+            }
+        }
+
+        let returns = match *fun.returns {
+            Node::None => None,
+            ref node => Some(self.resolve_type(node)?),
+        };
+
+        Ok(Signature { args, returns })
+    }
+
+    fn resolve_type(&self, node: &Node) -> ZResult<DataType> {
         match node {
+            Node::Name(name, pos) => self.resolve_name(&name.id, pos),
+            other => Err(error_at(other.pos(), 1, "Expected a type.")),
+        }
+    }
+
+    /// The type called `name`, which can be declared in the program.
+    fn type_named(&self, name: &str) -> Option<DataType> {
+        DataType::from_name_with(name, &|name| {
+            self.enums
+                .contains_key(name)
+                .then(|| DataType::enum_named(name))
+        })
+    }
+
+    fn resolve_name(&self, name: &str, pos: &SourcePos) -> ZResult<DataType> {
+        self.type_named(name)
+            .ok_or_else(|| error_at(Some(pos), name.len(), &format!("Unknown type '{}'.", name)))
+    }
+
+    /// The type of an expression on its own. Number literals without a
+    /// suffix adapt to their context, so they don't have one.
+    fn natural_type(&self, node: &Node) -> Option<DataType> {
+        match node {
+            Node::Constant(Constant {
+                suffix: Some(suffix),
+                ..
+            }) => self.type_named(suffix),
             Node::Constant(c) => match c.value {
-                Primitive::Int(i) => Ok(Operand::Int(i)),
-                Primitive::Str(str) => {
-                    let ptr = self.module.add_string(&str);
-                    Ok(Operand::StrPtr(ptr))
-                }
-                _ => todo!("Support."),
+                Primitive::Str(_) => Some(DataType::Str),
+                Primitive::Bool(_) => Some(DataType::Bool),
+                Primitive::Char(_) => Some(DataType::U8),
+                _ => None,
             },
-            Node::BinOp(binop) => Ok(Operand::Reg(self.build_binop(binop)?)),
+            Node::Name(Name { id }, _)
+            | Node::Call(Call {
+                func: Name { id }, ..
+            }) if self.is_variant(id) => {
+                let (name, _) = id.split_once('.')?;
+                Some(DataType::enum_named(name))
+            }
+            Node::Name(name, _) => self.vars.get(&name.id).map(|var| var.ty),
+            Node::Call(call) if is_result_fn(&call.func.id) => {
+                let arg = call.args.first()?;
+                match call.func.id.as_str() {
+                    grammar::F_OK => self.natural_type(arg).map(DataType::result_of),
+                    grammar::F_IS_OK => Some(DataType::Bool),
+                    grammar::F_ERROR => Some(DataType::I64),
+                    grammar::F_UNWRAP => self.natural_type(arg)?.ok_type(),
+                    _ => None,
+                }
+            }
+            Node::Try(try_) => self.natural_type(&try_.value)?.ok_type(),
+            Node::If(case) => {
+                let run = case
+                    .run
+                    .body
+                    .last()
+                    .and_then(|node| self.natural_type(node));
+                run.or_else(|| self.natural_type(&case.orelse))
+            }
+            Node::Scope(scope) => self.natural_type(scope.body.last()?),
+            Node::Call(call) => {
+                self.functions
+                    .get(self.callee(&call.func.id).as_str())?
+                    .returns
+            }
+            Node::BinOp(binop) if is_comparison(&binop.op) => Some(DataType::Bool),
+            Node::BinOp(binop) => self
+                .natural_type(&binop.left)
+                .or_else(|| self.natural_type(&binop.right)),
+            Node::Cast(cast) => self.type_named(&cast.target),
+            Node::Index(index) => self.natural_type(&index.value)?.element(),
+            Node::Unary(unary) => {
+                let ty = self.natural_type(&unary.value)?;
+                match unary.op {
+                    UnaryOp::AddressOf => Some(DataType::pointer_to(ty)),
+                    UnaryOp::Deref => ty.pointee(),
+                    UnaryOp::BitNot => Some(ty),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn build_constant(
+        &mut self,
+        constant: Constant,
+        hint: Option<DataType>,
+    ) -> ZResult<(Operand, DataType)> {
+        match constant.value {
+            // Integer literals can stand in for floats, `x * 2`.
+            Primitive::Int(i) if hint.is_some_and(DataType::is_float) => {
+                let ty = hint.expect("The hint is a float.");
+                Ok((float_bits(i as f64, ty), ty))
+            }
+            Primitive::Int(i) => {
+                let ty = match hint {
+                    Some(ty) if ty.is_integer() => ty,
+                    _ => DataType::of_literal(i),
+                };
+                let (min, max) = ty.range().expect("Integer types have a range.");
+                if !(min..=max).contains(&i) {
+                    return Err(error_at(
+                        Some(&constant.pos),
+                        i.to_string().len(),
+                        &format!("Literal {} is out of range for {}.", i, ty),
+                    ));
+                }
+                // A `u64` above `i64::MAX` keeps its bits.
+                Ok((Operand::Int(i as i64), ty))
+            }
+            Primitive::Bool(b) => Ok((Operand::Int(b as i64), DataType::Bool)),
+            Primitive::Str(str) => {
+                let ptr = self.module.add_string(&str);
+                Ok((Operand::StrPtr(ptr), DataType::Str))
+            }
+            Primitive::Float(x) => {
+                let ty = match hint {
+                    Some(DataType::F64) => DataType::F64,
+                    _ => DataType::F32,
+                };
+                if ty == DataType::F32 && (x as f32).is_infinite() {
+                    return Err(error_at(
+                        Some(&constant.pos),
+                        1,
+                        "Float literal is out of range for f32.",
+                    ));
+                }
+                Ok((float_bits(x, ty), ty))
+            }
+            Primitive::Char(c) => Ok((Operand::Int(c as i64), DataType::U8)),
+            _ => todo!("Support."),
+        }
+    }
+
+    /// Compiles an expression, `hint` is the type expected by its context.
+    pub fn make_operand(
+        &mut self,
+        node: Node,
+        hint: Option<DataType>,
+    ) -> ZResult<(Operand, DataType)> {
+        match node {
+            Node::Constant(c) => {
+                // A suffix decides the type, `10u8`.
+                let hint = match &c.suffix {
+                    Some(suffix) => Some(self.resolve_name(suffix, &c.pos)?),
+                    None => hint,
+                };
+                self.build_constant(c, hint)
+            }
+            Node::BinOp(binop) => {
+                let (reg, ty) = self.build_binop(binop, hint)?;
+                Ok((Operand::Reg(reg), ty))
+            }
+            Node::Cast(cast) => {
+                let (reg, ty) = self.build_cast(cast)?;
+                Ok((Operand::Reg(reg), ty))
+            }
+            Node::Name(name, pos) if self.is_variant(&name.id) => {
+                let (reg, ty) = self.build_variant(&name.id, vec![], &pos)?;
+                Ok((Operand::Reg(reg), ty))
+            }
             Node::Name(name, pos) => {
                 let var = match self.vars.get(&name.id) {
-                    Some(var) => var.inner.clone(),
+                    Some(var) => var.clone(),
                     None => {
                         return Err(CompilerError::new(
                             pos.line as usize,
@@ -366,13 +1589,273 @@ impl<'guard> Compiler<'guard> {
                         ))
                     }
                 };
-                Ok(Operand::Var(var))
+                Ok((Operand::Var(var.inner), var.ty))
+            }
+            Node::Call(call) if call.func.id == grammar::F_LEN => {
+                Ok((Operand::Reg(self.build_len(call)?), DataType::U64))
+            }
+            Node::Index(index) => {
+                let (reg, ty) = self.build_index(index)?;
+                Ok((Operand::Reg(reg), ty))
+            }
+            Node::Call(call) if is_result_fn(&call.func.id) => {
+                let (reg, ty) = self.build_result_call(call, hint)?;
+                Ok((Operand::Reg(reg), ty))
+            }
+            Node::Try(try_) => {
+                let (reg, ty) = self.build_try(try_)?;
+                Ok((Operand::Reg(reg), ty))
+            }
+            Node::If(case) => {
+                let (reg, ty) = self.build_if_value(case, hint)?;
+                Ok((Operand::Reg(reg), ty))
+            }
+            Node::Scope(scope) => {
+                let pos = scope.body.first().and_then(Node::pos).cloned();
+                let pos = pos.unwrap_or(SourcePos::new(1, 1));
+                self.add_scope();
+                let value = self.build_block_value(scope.body, hint, &pos)?;
+                self.clear_scope();
+                Ok(value)
+            }
+            Node::Unary(unary) => {
+                let (reg, ty) = self.build_unary(unary, hint)?;
+                Ok((Operand::Reg(reg), ty))
             }
             Node::Call(call) => {
-                self.build_call(call)?;
-                Ok(Operand::Reg(Reg::new("eax")))
+                let pos = call.pos.clone();
+                let name = call.func.id.clone();
+                match self.build_call(call)? {
+                    Some((reg, ty)) => Ok((Operand::Reg(reg), ty)),
+                    None => Err(error_at(
+                        Some(&pos),
+                        name.len(),
+                        &format!("Function '{}' doesn't return a value.", name),
+                    )),
+                }
             }
-            oops => panic!("This can't be an operand: {:?}", oops),
+            node => Err(error_at(node.pos(), 1, "Expected a value.")),
         }
     }
 }
+
+/// Puts the prelude's functions in front of the module's own, renamed so
+/// the module's functions don't replace them for the prelude itself.
+fn with_prelude(source: Mod) -> Mod {
+    let mut prelude = Parser::new()
+        .parse(Lexer::from(PRELUDE))
+        .expect("The prelude is valid.");
+
+    for node in &mut prelude.body {
+        if let Node::FunctionDef(fun) = node {
+            fun.name = format!("{}{}", PRELUDE_PREFIX, fun.name).into();
+        }
+    }
+
+    prelude.body.extend(source.body);
+    prelude
+}
+
+fn expect_type(expected: DataType, found: DataType, pos: &SourcePos) -> ZResult<()> {
+    if expected == found {
+        return Ok(());
+    }
+
+    Err(error_at(
+        Some(pos),
+        1,
+        &format!(
+            "Mismatched types, expected {} but found {}.",
+            expected, found
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use zasm::instr::{Cond, Instruction, Operand as Arg};
+
+    use super::*;
+
+    #[test]
+    fn test_prelude() {
+        // The prelude's `read_file` still calls its own `open` and `read`.
+        let source = "fun open(a: bool) -> bool { return a }\nfun read() {}\nfun main() {\n var o = open(true)\n var r = read_file(\"x\")\n var m = min(1, 2)\n}";
+        let module = with_prelude(Parser::new().parse(Lexer::from(source)).unwrap());
+        let functions = module
+            .body
+            .iter()
+            .filter_map(|node| match node {
+                Node::FunctionDef(fun) => Some(fun),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let opens = functions.iter().filter(|fun| fun.name.ends_with("open"));
+        assert_eq!(opens.count(), 2);
+        assert!(functions.iter().any(|fun| fun.name == "prelude.min"));
+        assert_eq!(functions.last().unwrap().name, "main");
+
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let mut compiler = Compiler::new();
+        let module = compiler.compile(module).unwrap();
+        let main = module
+            .functions()
+            .iter()
+            .find(|fun| fun.name() == "main")
+            .unwrap();
+        let calls = main
+            .body()
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Call(Arg::Sym(name, _)) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(calls, ["open", "prelude.read_file", "prelude.min"]);
+    }
+
+    #[test]
+    fn test_unused_value() {
+        let source = "fun main() {\n var x = 3\n var y = x-1\n x\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(4, 2, 1, "Expected a statement, this value isn't used.")
+        );
+    }
+
+    #[test]
+    fn test_redefined_function() {
+        let source = "fun main() {}\n\n  fun print(text: string) {}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(3, 3, 3, "Function 'print' is already defined.")
+        );
+
+        let source = "fun ok(x: i32) -> i32 { return x }\nfun main() {}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(1, 1, 3, "Function 'ok' is already defined.")
+        );
+    }
+
+    #[test]
+    fn test_unknown_function() {
+        let source = "fun main() {\n  greet(1)\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(2, 3, 5, "Unknown function 'greet'.")
+        );
+
+        let source = "fun main() {\n  var x = 2 + twice(1)\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(2, 15, 5, "Unknown function 'twice'.")
+        );
+    }
+
+    #[test]
+    fn test_match_exhaustive() {
+        let source = "enum S { A(i32), B, C }\nfun main() {\n var s = S.B\n match s {\n A(x) => {}\n B => {}\n }\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(4, 2, 5, "Missing variants in match: C.")
+        );
+
+        let source = source.replace("B => {}", "_ => {}");
+        let module = Parser::new().parse(Lexer::from(&source)).unwrap();
+        assert!(Compiler::new().compile(module).is_ok());
+    }
+
+    #[test]
+    fn test_fused_conditions() {
+        let source = "fun main() {\n var a: u64 = 1\n if a < 2 { println(\"x\") }\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let mut compiler = Compiler::new();
+        let module = compiler.compile(module).unwrap();
+        let main = module
+            .functions()
+            .iter()
+            .find(|fun| fun.name() == "main")
+            .unwrap();
+
+        let body = main.body();
+        assert!(body
+            .iter()
+            .any(|instr| matches!(instr, Instruction::Jcc(Cond::Ae, _))));
+        assert!(!body
+            .iter()
+            .any(|instr| matches!(instr, Instruction::Set(..) | Instruction::Movzx(..))));
+    }
+
+    #[test]
+    fn test_aug_assign() {
+        let source = "fun main() {\n var x: i32 = 1\n x += 2\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(err, CompilerError::new(3, 2, 1, "Variable is imutable."));
+
+        let source = source.replace("var x", "var mut x");
+        let module = Parser::new().parse(Lexer::from(&source)).unwrap();
+        let mut compiler = Compiler::new();
+        let module = compiler.compile(module).unwrap();
+        let main = module
+            .functions()
+            .iter()
+            .find(|fun| fun.name() == "main")
+            .unwrap();
+
+        assert!(main
+            .body()
+            .iter()
+            .any(|instr| matches!(instr, Instruction::Add(Arg::Mem(_), Arg::Imm(2)))));
+    }
+
+    #[test]
+    fn test_suffixed_literals() {
+        let source = "fun main() {\n var x: u8 = 300\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(2, 14, 3, "Literal 300 is out of range for u8.")
+        );
+
+        let source = "fun main() {\n var x = 1 + 300u8\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(2, 14, 3, "Literal 300 is out of range for u8.")
+        );
+
+        let source = "fun main() {\n var x = 0xff_u64 + 1\n var y: u64 = x\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        assert!(Compiler::new().compile(module).is_ok());
+
+        let source = "fun main() {\n var x: i64 = 9223372036854775808\n}";
+        let module = Parser::new().parse(Lexer::from(source)).unwrap();
+        let err = Compiler::new().compile(module).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(
+                2,
+                15,
+                19,
+                "Literal 9223372036854775808 is out of range for i64."
+            )
+        );
+    }
+}
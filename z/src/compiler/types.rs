@@ -0,0 +1,326 @@
+use std::{fmt, sync::Mutex};
+
+use zasm::{instr::Size, types::Scalar};
+
+/// The type of a value known to the compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bool,
+    Str,
+    /// The address of a value, written `ptr<T>`.
+    Ptr(&'static DataType),
+    /// A pointer to elements with their count before them like a
+    /// string's length, written `[T]`.
+    Array(&'static DataType),
+    /// Either a value or an `i64` error code, written `result<T>`.
+    Result(&'static DataType),
+    /// An enum declared in the program, pointing at the variant's tag
+    /// with its payload after it.
+    Enum(&'static str),
+}
+
+/// Every type pointers and arrays refer to lives here once, so
+/// `DataType` can stay `Copy`.
+static INNER_TYPES: Mutex<Vec<&'static DataType>> = Mutex::new(vec![]);
+
+fn intern(ty: DataType) -> &'static DataType {
+    let mut types = INNER_TYPES.lock().expect("Nothing panics while interning.");
+    match types.iter().find(|interned| ***interned == ty) {
+        Some(interned) => interned,
+        None => {
+            let interned: &'static DataType = Box::leak(Box::new(ty));
+            types.push(interned);
+            interned
+        }
+    }
+}
+
+static ENUM_NAMES: Mutex<Vec<&'static str>> = Mutex::new(vec![]);
+
+const NAMES: [(DataType, &str); 12] = [
+    (DataType::I8, "i8"),
+    (DataType::I16, "i16"),
+    (DataType::I32, "i32"),
+    (DataType::I64, "i64"),
+    (DataType::U8, "u8"),
+    (DataType::U16, "u16"),
+    (DataType::U32, "u32"),
+    (DataType::U64, "u64"),
+    (DataType::F32, "f32"),
+    (DataType::F64, "f64"),
+    (DataType::Bool, "bool"),
+    (DataType::Str, "string"),
+];
+
+impl DataType {
+    /// The type of a pointer to `target`.
+    pub fn pointer_to(target: Self) -> Self {
+        DataType::Ptr(intern(target))
+    }
+
+    /// The type of an array of `element`s.
+    pub fn array_of(element: Self) -> Self {
+        DataType::Array(intern(element))
+    }
+
+    /// The type of a result holding a `value` when it's ok.
+    pub fn result_of(value: Self) -> Self {
+        DataType::Result(intern(value))
+    }
+
+    /// The type of the enum declared as `name`.
+    pub fn enum_named(name: &str) -> Self {
+        let mut names = ENUM_NAMES.lock().expect("Nothing panics while interning.");
+        match names.iter().find(|interned| **interned == name) {
+            Some(interned) => DataType::Enum(interned),
+            None => {
+                let interned: &'static str = Box::leak(name.to_owned().into_boxed_str());
+                names.push(interned);
+                DataType::Enum(interned)
+            }
+        }
+    }
+
+    /// The type an ok result holds.
+    pub fn ok_type(self) -> Option<Self> {
+        match self {
+            DataType::Result(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The type indexing a string or an array gives.
+    pub fn element(self) -> Option<Self> {
+        match self {
+            DataType::Str => Some(DataType::U8),
+            DataType::Array(element) => Some(*element),
+            _ => None,
+        }
+    }
+
+    /// The type a pointer points to.
+    pub fn pointee(self) -> Option<Self> {
+        match self {
+            DataType::Ptr(target) => Some(*target),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        DataType::from_name_with(name, &|_| None)
+    }
+
+    /// Like `from_name`, with `declared` looking up the types declared
+    /// in the program.
+    pub fn from_name_with(name: &str, declared: &dyn Fn(&str) -> Option<Self>) -> Option<Self> {
+        if let Some(inner) = name.strip_prefix("ptr<").and_then(|x| x.strip_suffix('>')) {
+            return DataType::from_name_with(inner, declared).map(DataType::pointer_to);
+        }
+        if let Some(inner) = name.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            return DataType::from_name_with(inner, declared).map(DataType::array_of);
+        }
+        if let Some(inner) = name
+            .strip_prefix("result<")
+            .and_then(|x| x.strip_suffix('>'))
+        {
+            return DataType::from_name_with(inner, declared).map(DataType::result_of);
+        }
+
+        // `int` and `float` are kept as aliases of the default types,
+        // characters are single bytes.
+        match name {
+            "int" => return Some(DataType::I32),
+            "float" => return Some(DataType::F32),
+            "char" => return Some(DataType::U8),
+            _ => {}
+        }
+
+        NAMES
+            .iter()
+            .find(|(_, type_name)| *type_name == name)
+            .map(|(ty, _)| *ty)
+            .or_else(|| declared(name))
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            DataType::Ptr(target) => format!("ptr<{}>", target.name()),
+            DataType::Array(element) => format!("[{}]", element.name()),
+            DataType::Result(value) => format!("result<{}>", value.name()),
+            DataType::Enum(name) => name.to_owned(),
+            ty => NAMES
+                .iter()
+                .find(|(named, _)| *named == ty)
+                .map(|(_, name)| name.to_string())
+                .expect("Every type has a name."),
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.range().is_some()
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, DataType::F32 | DataType::F64)
+    }
+
+    /// The smallest and largest value of an integer type.
+    pub fn range(self) -> Option<(i128, i128)> {
+        let range = match self {
+            DataType::I8 => (i8::MIN as i128, i8::MAX as i128),
+            DataType::I16 => (i16::MIN as i128, i16::MAX as i128),
+            DataType::I32 => (i32::MIN as i128, i32::MAX as i128),
+            DataType::I64 => (i64::MIN as i128, i64::MAX as i128),
+            DataType::U8 => (0, u8::MAX as i128),
+            DataType::U16 => (0, u16::MAX as i128),
+            DataType::U32 => (0, u32::MAX as i128),
+            DataType::U64 => (0, u64::MAX as i128),
+            DataType::F32
+            | DataType::F64
+            | DataType::Bool
+            | DataType::Str
+            | DataType::Ptr(_)
+            | DataType::Array(_)
+            | DataType::Result(_)
+            | DataType::Enum(_) => return None,
+        };
+        Some(range)
+    }
+
+    /// The type of an integer literal without any context.
+    pub fn of_literal(value: i128) -> Self {
+        if i32::try_from(value).is_ok() {
+            DataType::I32
+        } else if i64::try_from(value).is_ok() {
+            DataType::I64
+        } else {
+            DataType::U64
+        }
+    }
+
+    /// Whether `value as target` is allowed. Integers, floats and bools
+    /// convert to numbers, nothing converts to a bool or a string.
+    /// Pointers convert to other pointers and 64-bit integers, and a
+    /// string can be viewed as a `ptr<u8>` to its bytes.
+    pub fn can_cast(self, target: Self) -> bool {
+        let number = |ty: Self| ty.is_integer() || ty.is_float();
+        let address = |ty: Self| matches!(ty, DataType::Ptr(_) | DataType::I64 | DataType::U64);
+        match (self, target) {
+            (DataType::Bool, _) => target.is_integer(),
+            (DataType::Ptr(_), _) | (_, DataType::Ptr(_)) if address(self) && address(target) => {
+                true
+            }
+            (DataType::Str, DataType::Ptr(DataType::U8)) => true,
+            (from, _) => number(from) && number(target),
+        }
+    }
+
+    /// How values of the type are stored.
+    pub fn scalar(self) -> Scalar {
+        let (size, signed) = match self {
+            DataType::F32 => return Scalar::float(Size::Dword),
+            DataType::F64 => return Scalar::float(Size::Qword),
+            DataType::I8 => (Size::Byte, true),
+            DataType::I16 => (Size::Word, true),
+            DataType::I32 => (Size::Dword, true),
+            DataType::I64 => (Size::Qword, true),
+            DataType::U8 | DataType::Bool => (Size::Byte, false),
+            DataType::U16 => (Size::Word, false),
+            DataType::U32 => (Size::Dword, false),
+            DataType::U64
+            | DataType::Str
+            | DataType::Ptr(_)
+            | DataType::Array(_)
+            | DataType::Result(_)
+            | DataType::Enum(_) => (Size::Qword, false),
+        };
+        Scalar::new(size, signed)
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(DataType::from_name("int"), Some(DataType::I32));
+        assert_eq!(DataType::from_name("u16"), Some(DataType::U16));
+        assert_eq!(DataType::from_name("float"), Some(DataType::F32));
+        assert_eq!(DataType::from_name("char"), Some(DataType::U8));
+        assert_eq!(DataType::from_name("f64"), Some(DataType::F64));
+        assert_eq!(DataType::U64.to_string(), "u64");
+
+        let ptr = DataType::from_name("ptr<ptr<char>>").unwrap();
+        assert_eq!(
+            ptr.pointee().and_then(DataType::pointee),
+            Some(DataType::U8)
+        );
+        assert_eq!(ptr.to_string(), "ptr<ptr<u8>>");
+        assert_eq!(
+            ptr,
+            DataType::pointer_to(DataType::pointer_to(DataType::U8))
+        );
+        assert_eq!(DataType::from_name("ptr<f16>"), None);
+
+        let args = DataType::from_name("[string]").unwrap();
+        assert_eq!(args, DataType::array_of(DataType::Str));
+        assert_eq!(args.element(), Some(DataType::Str));
+        assert_eq!(args.to_string(), "[string]");
+        assert_eq!(DataType::Str.element(), Some(DataType::U8));
+
+        let read = DataType::from_name("result<ptr<u8>>").unwrap();
+        assert_eq!(read.ok_type(), Some(DataType::pointer_to(DataType::U8)));
+        assert_eq!(read.to_string(), "result<ptr<u8>>");
+
+        let shape = DataType::enum_named("Shape");
+        let declared = |name: &str| (name == "Shape").then_some(shape);
+        assert_eq!(DataType::from_name("Shape"), None);
+        assert_eq!(
+            DataType::from_name_with("[Shape]", &declared),
+            Some(DataType::array_of(shape))
+        );
+        assert_eq!(shape.to_string(), "Shape");
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(DataType::of_literal(-5), DataType::I32);
+        assert_eq!(DataType::of_literal(1 << 40), DataType::I64);
+        assert_eq!(DataType::U8.range(), Some((0, 255)));
+    }
+
+    #[test]
+    fn test_casts() {
+        assert!(DataType::I32.can_cast(DataType::F32));
+        assert!(DataType::F32.can_cast(DataType::U8));
+        assert!(DataType::F64.can_cast(DataType::F32));
+        assert!(DataType::Bool.can_cast(DataType::I64));
+        assert!(!DataType::Bool.can_cast(DataType::F32));
+        assert!(!DataType::I32.can_cast(DataType::Bool));
+        assert!(!DataType::Str.can_cast(DataType::U64));
+
+        let bytes = DataType::pointer_to(DataType::U8);
+        assert!(DataType::Str.can_cast(bytes));
+        assert!(bytes.can_cast(DataType::pointer_to(DataType::I64)));
+        assert!(bytes.can_cast(DataType::U64));
+        assert!(!bytes.can_cast(DataType::I32));
+        assert!(!bytes.can_cast(DataType::Str));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::grammar::F_MAIN;
+use crate::parser::ast::{Arg, EnumDef, FunctionDef, Module, Name, Node, Variant};
+
+/// Lists the functions and enums of a module with their `///` comments as
+/// Markdown. `main` isn't part of the API so it's left out.
+pub fn markdown(module: &Module, title: &str) -> String {
+    let mut out = format!("# {}\n", title);
+
+    let functions = functions(module);
+    if !functions.is_empty() {
+        out.push_str("\n## Functions\n");
+        for fun in functions {
+            out.push_str(&format!("\n### `{}`\n", signature(fun)));
+            if let Some(doc) = &fun.doc {
+                out.push_str(&format!("\n{}\n", doc));
+            }
+        }
+    }
+
+    let enums = enums(module);
+    if !enums.is_empty() {
+        out.push_str("\n## Enums\n");
+        for def in enums {
+            out.push_str(&format!("\n### `enum {}`\n", def.name));
+            if let Some(doc) = &def.doc {
+                out.push_str(&format!("\n{}\n", doc));
+            }
+
+            out.push('\n');
+            for variant in &def.variants {
+                out.push_str(&format!("- `{}`", variant_signature(variant)));
+                if let Some(doc) = &variant.doc {
+                    out.push_str(&format!(": {}", doc.replace('\n', " ")));
+                }
+                out.push('\n');
+            }
+        }
+    }
+
+    out
+}
+
+/// The same contents as [`markdown`] as a standalone HTML page.
+pub fn html(module: &Module, title: &str) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape(title));
+
+    let functions = functions(module);
+    if !functions.is_empty() {
+        body.push_str("<h2>Functions</h2>\n");
+        for fun in functions {
+            body.push_str(&format!(
+                "<h3><code>{}</code></h3>\n",
+                escape(&signature(fun))
+            ));
+            if let Some(doc) = &fun.doc {
+                body.push_str(&paragraphs(doc));
+            }
+        }
+    }
+
+    let enums = enums(module);
+    if !enums.is_empty() {
+        body.push_str("<h2>Enums</h2>\n");
+        for def in enums {
+            body.push_str(&format!(
+                "<h3><code>enum {}</code></h3>\n",
+                escape(&def.name)
+            ));
+            if let Some(doc) = &def.doc {
+                body.push_str(&paragraphs(doc));
+            }
+
+            body.push_str("<ul>\n");
+            for variant in &def.variants {
+                body.push_str(&format!(
+                    "<li><code>{}</code>",
+                    escape(&variant_signature(variant))
+                ));
+                if let Some(doc) = &variant.doc {
+                    body.push_str(&format!(": {}", escape(doc)));
+                }
+                body.push_str("</li>\n");
+            }
+            body.push_str("</ul>\n");
+        }
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+fn functions(module: &Module) -> Vec<&FunctionDef> {
+    module
+        .body
+        .iter()
+        .filter_map(|node| match node {
+            Node::FunctionDef(fun) if fun.name != F_MAIN => Some(fun),
+            _ => None,
+        })
+        .collect()
+}
+
+fn enums(module: &Module) -> Vec<&EnumDef> {
+    module
+        .body
+        .iter()
+        .filter_map(|node| match node {
+            Node::EnumDef(def) => Some(def),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `fun name(arg: type, ...) -> type`
+fn signature(fun: &FunctionDef) -> String {
+    let args = fun
+        .args
+        .iter()
+        .filter_map(|arg| match arg {
+            Node::Arg(Arg { name, annotation }) => {
+                Some(format!("{}: {}", name, type_name(annotation)))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match type_name(&fun.returns) {
+        "" => format!("fun {}({})", fun.name, args),
+        returns => format!("fun {}({}) -> {}", fun.name, args, returns),
+    }
+}
+
+/// `Variant(type, ...)`
+fn variant_signature(variant: &Variant) -> String {
+    if variant.fields.is_empty() {
+        return variant.name.to_string();
+    }
+
+    let fields = variant
+        .fields
+        .iter()
+        .map(type_name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({})", variant.name, fields)
+}
+
+fn type_name(node: &Node) -> &str {
+    match node {
+        Node::Name(Name { id }, _) => id,
+        _ => "",
+    }
+}
+
+/// Blank lines separate paragraphs, like in Markdown.
+fn paragraphs(doc: &str) -> String {
+    doc.split("\n\n")
+        .map(|paragraph| format!("<p>{}</p>\n", escape(paragraph.trim())))
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    const SOURCE: &str = "/// Adds two numbers.\n///\n/// Wraps on overflow.\nfun add(a: i32, b: i32) -> i32 {\n return a + b\n}\n\n/// A shape.\nenum Shape {\n /// Has a radius.\n Circle(f32)\n Square\n}\n\nfun main() {}";
+
+    #[test]
+    fn test_markdown() {
+        let module = Parser::new().parse(Lexer::from(SOURCE)).unwrap();
+        let expected = "# shapes\n\n## Functions\n\n### `fun add(a: i32, b: i32) -> i32`\n\nAdds two numbers.\n\nWraps on overflow.\n\n## Enums\n\n### `enum Shape`\n\nA shape.\n\n- `Circle(f32)`: Has a radius.\n- `Square`\n";
+
+        assert_eq!(markdown(&module, "shapes"), expected);
+    }
+
+    #[test]
+    fn test_html() {
+        let module = Parser::new().parse(Lexer::from(SOURCE)).unwrap();
+        let page = html(&module, "<shapes>");
+
+        assert!(page.contains("<title>&lt;shapes&gt;</title>"));
+        assert!(page.contains("<h3><code>fun add(a: i32, b: i32) -&gt; i32</code></h3>\n<p>Adds two numbers.</p>\n<p>Wraps on overflow.</p>\n"));
+        assert!(page.contains("<li><code>Circle(f32)</code>: Has a radius.</li>"));
+        assert!(!page.contains("main"));
+    }
+}
@@ -1,6 +1,10 @@
 use std::fmt;
 
+use unicode_width::UnicodeWidthChar;
+
 const LINE_PADDING: usize = 3;
+/// Tabs are shown as this many spaces so the arrows line up with them.
+const TAB_WIDTH: usize = 4;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct CompilerError {
@@ -28,9 +32,22 @@ impl CompilerError {
         }
     }
 
-    fn print_message(&self) {
-        let spaces = " ".repeat(self.pos + 4);
-        let arrows = "^".repeat(self.arrows);
+    /// Positions count characters, but the arrows under `line` have to
+    /// line up with what's on screen, where a tab or a CJK character can
+    /// be wider than one column and a combining mark has no width.
+    fn print_message(&self, line: Option<&str>) {
+        let start = self.pos.saturating_sub(1);
+        let (offset, width) = match line {
+            Some(line) => {
+                let before = line.chars().take(start);
+                let marked = line.chars().skip(start).take(self.arrows);
+                (display_width(before), display_width(marked).max(1))
+            }
+            None => (start, self.arrows),
+        };
+
+        let spaces = " ".repeat(offset + 5);
+        let arrows = "^".repeat(width);
         eprintln!("{}{} {}", spaces, arrows, self.message);
     }
 
@@ -41,21 +58,27 @@ impl CompilerError {
             line_num = self.line - LINE_PADDING;
         }
 
-        source
+        let lines = source
             .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect::<Vec<_>>();
+        let error_line = self.line.checked_sub(1).and_then(|i| lines.get(i)).copied();
+
+        lines
+            .iter()
             .skip(line_num)
             .take(LINE_PADDING * 2)
             .for_each(|line| {
                 if line_num == self.line {
-                    self.print_message();
+                    self.print_message(error_line);
                     displayed = true;
                 }
                 line_num += 1;
-                println!("{}| {}", padding(line_num), line)
+                println!("{}| {}", padding(line_num), expand_tabs(line))
             });
 
         if !displayed {
-            self.print_message();
+            self.print_message(error_line);
         }
 
         println!();
@@ -66,6 +89,19 @@ fn padding(num: usize) -> String {
     format!("{:<3}", num)
 }
 
+fn display_width(chars: impl Iterator<Item = char>) -> usize {
+    chars
+        .map(|ch| match ch {
+            '\t' => TAB_WIDTH,
+            ch => ch.width().unwrap_or(0),
+        })
+        .sum()
+}
+
+fn expand_tabs(line: &str) -> String {
+    line.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
 pub trait MakeErr {
     fn into_err(self, message: &str) -> CompilerError;
 }
@@ -81,4 +117,13 @@ mod tests {
 
         assert_eq!(expected, got);
     }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("var x".chars()), 5);
+        assert_eq!(display_width("\tž".chars()), TAB_WIDTH + 1);
+        assert_eq!(display_width("日本".chars()), 4);
+        assert_eq!(display_width("e\u{301}".chars()), 1);
+        assert_eq!(expand_tabs("\tx"), "    x");
+    }
 }
@@ -7,7 +7,10 @@ pub const OPEN_BR: char = '[';
 pub const CLOSED_BR: char = ']';
 pub const NL: char = '\n';
 pub const DOUBLE_QUOTES: char = '"';
+pub const SINGLE_QUOTE: char = '\'';
 pub const SPACE: char = ' ';
+pub const TAB: char = '\t';
+pub const CR: char = '\r';
 pub const DOT: char = '.';
 pub const COLON: char = ':';
 pub const SEMICOLON: char = ';';
@@ -18,6 +21,11 @@ pub const MINUS: char = '-';
 pub const PLUS: char = '+';
 pub const MOD: char = '%';
 pub const STAR: char = '*';
+pub const AMPERSAND: char = '&';
+pub const PIPE: char = '|';
+pub const CARET: char = '^';
+pub const TILDE: char = '~';
+pub const QUESTION: char = '?';
 pub const GREATER_THAN: char = '>';
 pub const LESS_THAN: char = '<';
 pub const EQUALS: char = '=';
@@ -35,10 +43,36 @@ pub const ELSE: &str = "else";
 // pub const FLOAT: &str = "float";
 pub const LOOP: &str = "loop";
 pub const BREAK: &str = "break";
+pub const AS: &str = "as";
+pub const ENUM: &str = "enum";
+pub const MATCH: &str = "match";
+
+/// Types
+pub const PTR: &str = "ptr";
+pub const RESULT: &str = "result";
+
+/// Matches every variant in a `match`.
+pub const WILDCARD: &str = "_";
 
 /// Important functions
 pub const F_ASM: &str = "__asm__";
 pub const F_MAIN: &str = "main";
+pub const F_LEN: &str = "len";
+pub const F_PRINT: &str = "print";
+pub const F_ALLOC: &str = "alloc";
+pub const F_FREE: &str = "free";
+pub const F_REALLOC: &str = "realloc";
+pub const F_STR_FROM: &str = "str_from";
+pub const F_ARGS: &str = "args";
+pub const F_ARG_COUNT: &str = "arg_count";
+pub const F_ENV: &str = "env";
+pub const F_OK: &str = "ok";
+pub const F_ERR: &str = "err";
+pub const F_IS_OK: &str = "is_ok";
+pub const F_ERROR: &str = "error";
+pub const F_UNWRAP: &str = "unwrap";
+pub const F_ASSERT: &str = "assert";
+pub const F_ASSERT_EQ: &str = "assert_eq";
 
 /// Booleans
 pub const TRUE: &str = "true";
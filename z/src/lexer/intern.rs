@@ -0,0 +1,121 @@
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+/// An identifier from the symbol table. Every occurrence of a name shares
+/// one allocation, so cloning a symbol doesn't copy the name.
+#[derive(Clone, Eq)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Hashes like the name, so maps keyed by symbols can be searched with a
+/// `&str`.
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+/// A symbol outside of any table, for names that don't come from a lexer.
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol(name.into())
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol(name.into())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", &*self.0)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", &*self.0)
+    }
+}
+
+/// The symbol table of a lexer, only the first occurrence of a name is
+/// allocated.
+#[derive(Default)]
+pub struct Interner {
+    symbols: HashSet<Rc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return Symbol(symbol.clone());
+        }
+
+        let symbol: Rc<str> = name.into();
+        self.symbols.insert(symbol.clone());
+        Symbol(symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::default();
+        let a = interner.intern("žaba");
+        let b = interner.intern(&String::from("žaba"));
+
+        assert!(Rc::ptr_eq(&a.0, &b.0));
+        assert_eq!(a, Symbol::from("žaba"));
+        assert_ne!(a, interner.intern("zaba"));
+        assert_eq!(a, "žaba");
+        assert_eq!(format!("{:?} {}", a, a), "\"žaba\" žaba");
+    }
+}
@@ -1,13 +1,18 @@
-use std::{iter::Peekable, str::Chars};
+use std::io::Read;
 
-use self::token::{Keyword, SourcePos, Token, Type};
+use self::intern::Interner;
+use self::source::Source;
+use self::token::{Keyword, SourcePos, Token, Tokens, Type};
 use crate::{
     error::CompilerError,
     grammar::*,
     parser::{ast::Primitive, ZResult},
 };
+use unicode_ident::{is_xid_continue, is_xid_start};
 use zasm::types::Operator;
 
+pub mod intern;
+mod source;
 pub mod token;
 // #[cfg(test)]
 // mod tokenizer_tests;
@@ -44,17 +49,48 @@ macro_rules! token {
 }
 
 pub struct Lexer<'guard> {
-    chars: Peekable<Chars<'guard>>,
+    chars: Source<'guard>,
+    symbols: Interner,
+    /// Reused for every identifier, so names that were seen before don't
+    /// allocate.
+    word: String,
     line: u32,
     column: u32,
+    /// The last token can end an operand, so a `-` after it subtracts
+    /// instead of starting a negative number.
+    after_operand: bool,
 }
 
 impl<'guard> Iterator for Lexer<'guard> {
     type Item = ZResult<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let tok = self.lex();
+        if let Some(Ok(tok)) = &tok {
+            self.after_operand = matches!(
+                tok.value,
+                Type::Word(_)
+                    | Type::Primitive(_)
+                    | Type::Suffixed(..)
+                    | Type::RParen
+                    | Type::RBracket
+                    | Type::Question
+                    | Type::Keyword(Keyword::True | Keyword::False)
+            );
+        }
+        tok
+    }
+}
+
+impl<'guard> Lexer<'guard> {
+    fn lex(&mut self) -> Option<ZResult<Token>> {
         self.column += 1;
-        let ch = self.chars.next()?;
+        let Some(ch) = self.chars.next() else {
+            let err = self.chars.take_error()?;
+            return Some(Err(
+                self.throw(&format!("Couldn't read the source: {}.", err))
+            ));
+        };
         let tok = match ch {
             OPEN_PAREN => tok_ok!(self, Type::LParen),
             CLOSED_PAREN => tok_ok!(self, Type::RParen),
@@ -73,51 +109,114 @@ impl<'guard> Iterator for Lexer<'guard> {
 
             SEMICOLON => tok_ok!(self, Type::default()),
 
-            SPACE => return self.next(),
-            DOUBLE_QUOTES => {
-                let mut word = String::new();
+            // The `\r` of a `\r\n` line ending doesn't take up a column.
+            CR if self.chars.peek() == Some(&NL) => {
+                self.column -= 1;
+                return self.next();
+            }
+            // Tabs and other whitespace are a single column, like spaces.
+            case if case.is_whitespace() => return self.next(),
+            DOUBLE_QUOTES => self.string(self.column, false),
+            SINGLE_QUOTE => self.char(),
+            // r"..." is a raw string without escapes.
+            'r' if self.chars.peek() == Some(&DOUBLE_QUOTES) => {
                 let column = self.column;
-
-                let mut current = self.chars.next()?;
-                while current != DOUBLE_QUOTES {
-                    if current == BACKSLASH {
-                        current = match self.chars.next()? {
-                            'n' => '\n',
-                            DOUBLE_QUOTES => DOUBLE_QUOTES,
-                            BACKSLASH => BACKSLASH,
-                            _ => return Some(Err(self.throw("Unknown escape char."))),
-                        };
-                    }
-
-                    word.push(current);
-                    current = self.chars.next()?;
-                }
-
-                self.column += word.len() as u32 + 1;
-                tok_ok!(
-                    pos!(column, self.line),
-                    Type::Primitive(Primitive::Str(word))
-                )
+                self.chars.next();
+                self.column += 1;
+                self.string(column, true)
             }
             COLON => tok_ok!(self, Type::DoubleDot),
 
             // Matches on arrow
-            MINUS if *self.chars.peek()? == GREATER_THAN => {
+            MINUS if self.chars.peek() == Some(&GREATER_THAN) => {
                 self.chars.next();
                 self.column += 1;
                 tok_ok!(pos!(self.column - 1, self.line), Type::Arrow)
             }
-            FORWARD_SLASH if *self.chars.peek()? == FORWARD_SLASH => {
-                while *self.chars.peek()? != NL {
+            FORWARD_SLASH if self.chars.peek() == Some(&FORWARD_SLASH) => {
+                let column = self.column;
+                self.chars.next();
+                self.column += 1;
+
+                // `///` is a doc comment, but `////` is a plain one.
+                if self.chars.peek_nth(0) == Some(FORWARD_SLASH)
+                    && self.chars.peek_nth(1) != Some(FORWARD_SLASH)
+                {
                     self.chars.next();
+                    self.column += 1;
+                    let text = self.rest_of_line();
+                    let text = text.strip_prefix(SPACE).unwrap_or(&text);
+                    let text = text.strip_suffix(CR).unwrap_or(text);
+                    return Some(tok_ok!(
+                        pos!(column, self.line),
+                        Type::DocComment(text.to_owned())
+                    ));
+                }
+
+                self.rest_of_line();
+                return self.next();
+            }
+            FORWARD_SLASH if self.chars.peek() == Some(&STAR) => {
+                if let Err(err) = self.block_comment() {
+                    return Some(Err(err));
                 }
-                self.column -= 1;
 
                 return self.next();
             }
 
+            // matches +=, -=, *=, /=, %=, &=, |= and ^=
+            PLUS | MINUS | STAR | FORWARD_SLASH | MOD | AMPERSAND | PIPE | CARET
+                if self.chars.peek() == Some(&EQUALS) =>
+            {
+                self.chars.next();
+                self.column += 1;
+                let op = match ch {
+                    PLUS => Operator::Add,
+                    MINUS => Operator::Sub,
+                    STAR => Operator::Mult,
+                    FORWARD_SLASH => Operator::Div,
+                    MOD => Operator::Mod,
+                    AMPERSAND => Operator::BitAnd,
+                    PIPE => Operator::BitOr,
+                    _ => Operator::BitXor,
+                };
+                tok_ok!(pos!(self.column - 1, self.line), Type::AugAssign(op))
+            }
+
+            AMPERSAND | PIPE if self.chars.peek() == Some(&ch) => {
+                return Some(Err(CompilerError::new(
+                    self.line as usize,
+                    self.column as usize,
+                    2,
+                    &format!(
+                        "'{0}{0}' isn't an operator, use '{0}' to combine bools.",
+                        ch
+                    ),
+                )));
+            }
+
+            // matches <<, >>, <<= and >>=
+            LESS_THAN | GREATER_THAN if self.chars.peek() == Some(&ch) => {
+                let column = self.column;
+                self.chars.next();
+                self.column += 1;
+                let op = if ch == LESS_THAN {
+                    Operator::ShiftLeft
+                } else {
+                    Operator::ShiftRight
+                };
+
+                if self.chars.peek() == Some(&EQUALS) {
+                    self.chars.next();
+                    self.column += 1;
+                    tok_ok!(pos!(column, self.line), Type::AugAssign(op))
+                } else {
+                    tok_ok!(pos!(column, self.line), Type::Op(op))
+                }
+            }
+
             // matches ==
-            EQUALS if *self.chars.peek()? == EQUALS => {
+            EQUALS if self.chars.peek() == Some(&EQUALS) => {
                 self.chars.next();
                 self.column += 1;
                 tok_ok!(
@@ -126,8 +225,14 @@ impl<'guard> Iterator for Lexer<'guard> {
                 )
             }
 
+            EQUALS if self.chars.peek() == Some(&GREATER_THAN) => {
+                self.chars.next();
+                self.column += 1;
+                tok_ok!(pos!(self.column - 1, self.line), Type::FatArrow)
+            }
+
             // matches >=
-            GREATER_THAN if *self.chars.peek()? == EQUALS => {
+            GREATER_THAN if self.chars.peek() == Some(&EQUALS) => {
                 self.chars.next();
                 self.column += 1;
                 tok_ok!(
@@ -137,7 +242,7 @@ impl<'guard> Iterator for Lexer<'guard> {
             }
 
             // matches <=
-            LESS_THAN if *self.chars.peek()? == EQUALS => {
+            LESS_THAN if self.chars.peek() == Some(&EQUALS) => {
                 self.chars.next();
                 self.column += 1;
                 tok_ok!(
@@ -153,68 +258,56 @@ impl<'guard> Iterator for Lexer<'guard> {
             STAR => tok_ok!(self, Type::Op(Operator::Mult)),
             FORWARD_SLASH => tok_ok!(self, Type::Op(Operator::Div)),
             EQUALS => tok_ok!(self, Type::Equals),
+            AMPERSAND => tok_ok!(self, Type::Ampersand),
+            PIPE => tok_ok!(self, Type::Op(Operator::BitOr)),
+            CARET => tok_ok!(self, Type::Op(Operator::BitXor)),
+            TILDE => tok_ok!(self, Type::Tilde),
+            QUESTION => tok_ok!(self, Type::Question),
 
-            case if case.is_numeric() || (case == MINUS && self.chars.peek()?.is_numeric()) => {
-                let mut content = String::new();
-                if case == MINUS {
-                    content.push('-');
-                    content.push(self.chars.next()?);
-                } else {
-                    content.push(case);
-                }
-
-                let mut floating = false;
-                let column = self.column;
-
-                while let Some(current) = self.chars.peek() {
-                    if *current == DOT {
-                        floating = true;
-                    } else if *current == UNDERSCORE {
-                        self.chars.next();
-                        continue;
-                    } else if !current.is_numeric() {
-                        break;
-                    }
-
-                    content.push(self.chars.next()?);
-                }
-                self.column += content.len() as u32 - 1;
-
-                tok_ok!(
-                    pos!(column, self.line),
-                    if floating {
-                        Type::Primitive(Primitive::Float(content.parse().unwrap()))
-                    } else {
-                        Type::Primitive(Primitive::Int(content.parse().unwrap()))
-                    }
-                )
+            case if case.is_ascii_digit()
+                || (case == MINUS
+                    && !self.after_operand
+                    && self.chars.peek().is_some_and(char::is_ascii_digit)) =>
+            {
+                self.number(case)
             }
             MINUS => tok_ok!(self, Type::Op(Operator::Sub)),
 
-            case if case.is_alphabetic() || case == '_' => {
-                let mut word = String::from(case);
+            case if is_xid_start(case) || case == UNDERSCORE => {
                 let column = self.column;
+                self.word.clear();
+                self.word.push(case);
 
-                while let Some(current) = self.chars.peek() {
-                    if !current.is_alphanumeric() && *current != '_' {
+                while let Some(&current) = self.chars.peek() {
+                    // `Shape.Circle` is a single name.
+                    if current == DOT {
+                        if !self
+                            .chars
+                            .peek_nth(1)
+                            .is_some_and(|ch| is_xid_start(ch) || ch == UNDERSCORE)
+                        {
+                            break;
+                        }
+                    } else if !is_xid_continue(current) {
                         break;
                     }
 
-                    word.push(self.chars.next().expect("We already peeked this value."));
+                    self.word.push(current);
+                    self.chars.next();
+                    self.column += 1;
                 }
-                self.column += word.len() as u32 - 1;
 
                 tok_ok!(
                     pos!(column, self.line),
-                    if is_keyword(&word) {
-                        Type::Keyword(match_keyword(&word))
+                    if is_keyword(&self.word) {
+                        Type::Keyword(match_keyword(&self.word))
                     } else {
-                        Type::Word(word)
+                        Type::Word(self.symbols.intern(&self.word))
                     }
                 )
             }
 
-            EXCLAMATION if *self.chars.peek()? == EQUALS => {
+            EXCLAMATION if self.chars.peek() == Some(&EQUALS) => {
                 self.chars.next().expect("Shouldn't fail");
                 tok_ok!(self, Type::Op(Operator::NotEquals))
             }
@@ -226,30 +319,375 @@ impl<'guard> Iterator for Lexer<'guard> {
 
         Some(tok)
     }
-}
 
-impl<'guard> Lexer<'guard> {
     pub fn from(source: &'guard str) -> Self {
+        Self::new(Source::from(source))
+    }
+
+    /// Lexes the source as it's read, only a small buffer of it is kept in
+    /// memory.
+    pub fn from_reader(reader: impl Read + 'guard) -> Self {
+        Self::new(Source::from_reader(reader))
+    }
+
+    fn new(chars: Source<'guard>) -> Self {
         Lexer {
-            chars: source.chars().peekable(),
+            chars,
+            symbols: Interner::default(),
+            word: String::new(),
             line: 1,
             column: 0,
+            after_operand: false,
         }
     }
 
+    /// Lexes everything up front, so the tokens can be looked at in any
+    /// order. Stops at the first error.
+    pub fn tokenize(self) -> ZResult<Tokens> {
+        Ok(Tokens::new(self.collect::<ZResult<_>>()?))
+    }
+
     fn throw(&self, message: &str) -> CompilerError {
         CompilerError::new(self.line as usize, self.column as usize, 1, message)
     }
+
+    /// Lexes the rest of a string literal after its opening quote, which
+    /// is at `column`. Strings can span multiple lines.
+    fn string(&mut self, column: u32, raw: bool) -> ZResult<Token> {
+        let line = self.line;
+        let mut word = vec![];
+
+        loop {
+            let current = self.chars.next().ok_or_else(|| {
+                CompilerError::new(line as usize, column as usize, 1, "Unterminated string.")
+            })?;
+            self.column += 1;
+
+            match current {
+                DOUBLE_QUOTES => break,
+                // Line endings in strings are always `\n`.
+                CR if self.chars.peek() == Some(&NL) => self.column -= 1,
+                NL => {
+                    self.line += 1;
+                    self.column = 0;
+                    word.push(NL as u8);
+                }
+                BACKSLASH if !raw => word.extend(self.escape()?),
+                current => word.extend_from_slice(current.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+
+        tok_ok!(pos!(column, line), Type::Primitive(Primitive::Str(word)))
+    }
+
+    /// Lexes a number starting with `first`, which can be a minus sign.
+    /// Integers can be written in hex (`0xff`), octal (`0o17`) or binary
+    /// (`0b101`), floats can have an exponent (`1e-9`), and both can end in
+    /// their type (`10u8`, `2.5f32`).
+    fn number(&mut self, first: char) -> ZResult<Token> {
+        let (line, column) = (self.line, self.column);
+        let err = |offset: usize, len: usize, message: &str| {
+            CompilerError::new(line as usize, column as usize + offset, len, message)
+        };
+        let is_hex = |text: &str| {
+            let body = text.trim_start_matches(MINUS);
+            body.starts_with("0x") || body.starts_with("0X")
+        };
+
+        let mut text = String::from(first);
+        while let Some(&current) = self.chars.peek() {
+            // The sign of an exponent is part of the number, `1e-9`.
+            let exponent_sign =
+                matches!(current, PLUS | MINUS) && text.ends_with(['e', 'E']) && !is_hex(&text);
+            if !(current.is_ascii_alphanumeric()
+                || current == UNDERSCORE
+                || current == DOT
+                || exponent_sign)
+            {
+                break;
+            }
+
+            text.push(current);
+            self.chars.next();
+        }
+        self.column += text.len() as u32 - 1;
+
+        let sign = if first == MINUS { 1 } else { 0 };
+        let (radix, kind) = match text.get(sign..sign + 2) {
+            Some("0x" | "0X") => (16, "a hex literal"),
+            Some("0o" | "0O") => (8, "an octal literal"),
+            Some("0b" | "0B") => (2, "a binary literal"),
+            _ => (10, "a number"),
+        };
+        let start = if radix == 10 { sign } else { sign + 2 };
+
+        // Hex digits include `f`, so hex numbers can't be floats anyway.
+        let suffix_letters: &[char] = if radix == 16 {
+            &['i', 'u']
+        } else {
+            &['i', 'u', 'f']
+        };
+        let end = text[start..]
+            .find(suffix_letters)
+            .map_or(text.len(), |index| start + index);
+        let (digits, suffix) = (&text[start..end], &text[end..]);
+
+        if digits.replace(UNDERSCORE, "").is_empty() {
+            return Err(err(
+                0,
+                text.len(),
+                &format!("Expected digits after '{}'.", &text[sign..start]),
+            ));
+        }
+
+        let float = radix == 10 && digits.contains([DOT, 'e', 'E']);
+        for (index, digit) in digits.char_indices() {
+            let valid = digit == UNDERSCORE
+                || digit.is_digit(radix)
+                || (float && matches!(digit, DOT | 'e' | 'E' | PLUS | MINUS));
+            if !valid {
+                return Err(err(
+                    start + index,
+                    1,
+                    &format!("Invalid digit '{}' in {}.", digit, kind),
+                ));
+            }
+        }
+
+        let suffix = match suffix {
+            "" => None,
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "f32" | "f64" => {
+                Some(suffix)
+            }
+            _ => {
+                return Err(err(
+                    end,
+                    suffix.len(),
+                    &format!("Unknown suffix '{}', expected a number type.", suffix),
+                ))
+            }
+        };
+        let float_suffix = matches!(suffix, Some("f32" | "f64"));
+        if float && suffix.is_some() && !float_suffix {
+            return Err(err(
+                end,
+                text.len() - end,
+                "Floats can't have an integer suffix.",
+            ));
+        }
+        if radix != 10 && float_suffix {
+            return Err(err(0, text.len(), "Only decimal numbers can be floats."));
+        }
+
+        let cleaned = format!("{}{}", &text[..sign], digits.replace(UNDERSCORE, ""));
+        // An `f32` is checked against its range when it's compiled.
+        let value = if float || float_suffix {
+            match cleaned.parse::<f64>() {
+                Ok(x) if x.is_finite() => Primitive::Float(x),
+                Ok(_) => return Err(err(0, text.len(), "Float literal is out of range.")),
+                Err(_) => return Err(err(0, text.len(), "Invalid float literal.")),
+            }
+        } else {
+            // Checked against its type when it's compiled.
+            match i128::from_str_radix(&cleaned, radix) {
+                Ok(i) if (i64::MIN as i128..=u64::MAX as i128).contains(&i) => Primitive::Int(i),
+                _ => return Err(err(0, text.len(), "Integer literal is out of range.")),
+            }
+        };
+
+        tok_ok!(
+            pos!(column, line),
+            match suffix {
+                Some(suffix) => Type::Suffixed(value, suffix.to_owned()),
+                None => Type::Primitive(value),
+            }
+        )
+    }
+
+    /// Consumes everything up to the end of the line, but leaves the newline
+    /// itself for the next token.
+    fn rest_of_line(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(&current) = self.chars.peek() {
+            if current == NL {
+                break;
+            }
+
+            text.push(current);
+            self.chars.next();
+            self.column += 1;
+        }
+
+        text
+    }
+
+    /// Skips a `/* ... */` comment after its opening slash. Block comments
+    /// nest, so a commented out block can contain other block comments.
+    fn block_comment(&mut self) -> ZResult<()> {
+        let (line, column) = (self.line, self.column);
+        self.chars.next();
+        self.column += 1;
+
+        let mut depth = 1;
+        while depth > 0 {
+            let Some(current) = self.chars.next() else {
+                return Err(CompilerError::new(
+                    line as usize,
+                    column as usize,
+                    2,
+                    "Unterminated block comment.",
+                ));
+            };
+            self.column += 1;
+
+            match (current, self.chars.peek()) {
+                (NL, _) => {
+                    self.line += 1;
+                    self.column = 0;
+                }
+                (FORWARD_SLASH, Some(&STAR)) => {
+                    self.chars.next();
+                    self.column += 1;
+                    depth += 1;
+                }
+                (STAR, Some(&FORWARD_SLASH)) => {
+                    self.chars.next();
+                    self.column += 1;
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lexes a character literal after its opening quote. Characters are
+    /// single ASCII bytes.
+    fn char(&mut self) -> ZResult<Token> {
+        let (line, column) = (self.line, self.column);
+        let err = |len: u32, message: &str| {
+            CompilerError::new(line as usize, column as usize, len as usize, message)
+        };
+
+        let mut bytes = vec![];
+        loop {
+            let current = match self.chars.next() {
+                Some(NL) | None => return Err(err(1, "Unterminated character literal.")),
+                Some(current) => current,
+            };
+            self.column += 1;
+
+            match current {
+                SINGLE_QUOTE => break,
+                BACKSLASH => bytes.extend(self.escape()?),
+                current => bytes.extend_from_slice(current.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+
+        let len = self.column - column + 1;
+        let one_char = std::str::from_utf8(&bytes).is_ok_and(|s| s.chars().count() == 1);
+        match bytes[..] {
+            [byte] if byte.is_ascii() => {
+                tok_ok!(
+                    pos!(column, line),
+                    Type::Primitive(Primitive::Char(byte as char))
+                )
+            }
+            [] => Err(err(len, "Empty character literal.")),
+            [_] => Err(err(len, "Characters must be ASCII, use a string instead.")),
+            _ if one_char => Err(err(len, "Characters must be ASCII, use a string instead.")),
+            _ => Err(err(len, "Character literals can only hold one character.")),
+        }
+    }
+
+    /// Takes up to `max` hex digits.
+    fn hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
+        while digits.len() < max {
+            match self.chars.next_if(char::is_ascii_hexdigit) {
+                Some(digit) => digits.push(digit),
+                None => break,
+            }
+        }
+
+        self.column += digits.len() as u32;
+        digits
+    }
+
+    /// The bytes of the character after a backslash, `\xNN` is a single
+    /// byte even above `\x7F`. Nothing for a backslash at the end of a line,
+    /// which skips the line break and the next line's indentation.
+    fn escape(&mut self) -> ZResult<Vec<u8>> {
+        let (line, column) = (self.line as usize, self.column as usize);
+        let err = |len: usize, message: &str| CompilerError::new(line, column, len, message);
+
+        let mut current = self
+            .chars
+            .next()
+            .ok_or_else(|| err(1, "Unterminated string."))?;
+        self.column += 1;
+        if current == CR && self.chars.peek() == Some(&NL) {
+            current = NL;
+            self.chars.next();
+        }
+
+        let escaped = match current {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            DOUBLE_QUOTES | SINGLE_QUOTE | BACKSLASH => current,
+            'x' => {
+                let digits = self.hex_digits(2);
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 => return Ok(vec![byte]),
+                    _ => return Err(err(2, "Expected two hex digits after \\x.")),
+                }
+            }
+            'u' => {
+                let opened = self.chars.next_if_eq(&OPEN_CBR).is_some();
+                let digits = self.hex_digits(6);
+                let closed = self.chars.next_if_eq(&CLOSED_CBR).is_some();
+                let len = 2 + opened as usize + digits.len() + closed as usize;
+                self.column += (opened as u32) + (closed as u32);
+
+                let value = u32::from_str_radix(&digits, 16).ok();
+                match value.and_then(char::from_u32) {
+                    Some(ch) if opened && closed => ch,
+                    _ => return Err(err(len, "Invalid unicode escape, expected \\u{..}.")),
+                }
+            }
+            NL => {
+                self.line += 1;
+                self.column = 0;
+                while self
+                    .chars
+                    .next_if(|ch| *ch == SPACE || *ch == TAB)
+                    .is_some()
+                {
+                    self.column += 1;
+                }
+                return Ok(vec![]);
+            }
+            _ => return Err(err(2, "Unknown escape char.")),
+        };
+
+        Ok(escaped.encode_utf8(&mut [0; 4]).as_bytes().to_vec())
+    }
 }
 
 fn is_keyword(word: &str) -> bool {
-    [FUN, VAR, MUT, RETURN, IF, ELSE, TRUE, FALSE, LOOP, BREAK].contains(&word)
+    [
+        FUN, VAR, MUT, RETURN, IF, ELSE, TRUE, FALSE, LOOP, BREAK, AS, ENUM, MATCH,
+    ]
+    .contains(&word)
 }
 
 fn match_keyword(word: &str) -> Keyword {
     match word {
         TRUE => Keyword::True,
-        FALSE => Keyword::True,
+        FALSE => Keyword::False,
         FUN => Keyword::Fun,
         VAR => Keyword::Var,
         MUT => Keyword::Mut,
@@ -258,23 +696,26 @@ fn match_keyword(word: &str) -> Keyword {
         BREAK => Keyword::Break,
         LOOP => Keyword::Loop,
         RETURN => Keyword::Return,
+        AS => Keyword::As,
+        ENUM => Keyword::Enum,
+        MATCH => Keyword::Match,
         _ => panic!("Keyword: '{}' isn't implemented yet.", word),
     }
 }
 
 impl<'guard> Default for Lexer<'guard> {
     fn default() -> Self {
-        Self {
-            chars: "".chars().peekable(),
-            line: 0,
-            column: 0,
-        }
+        Self::from("")
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::ast::Primitive;
+    use crate::{
+        error::CompilerError,
+        parser::{ast::Primitive, ZResult},
+    };
+    use zasm::types::Operator;
 
     use super::{
         token::SourcePos,
@@ -285,10 +726,10 @@ mod tests {
 
     #[test]
     fn test_symbols() {
-        let test_case = "(){},\n:";
+        let test_case = "(){},\n:&?";
         let mut lexer = Lexer::from(test_case);
 
-        let expected: [Token; 7] = [
+        let expected: [Token; 9] = [
             token!(pos!(1, 1), Type::LParen),
             token!(pos!(2, 1), Type::RParen),
             token!(pos!(3, 1), Type::LBrace),
@@ -296,6 +737,8 @@ mod tests {
             token!(pos!(5, 1), Type::Comma),
             token!(pos!(6, 1), Type::Nl),
             token!(pos!(1, 2), Type::DoubleDot),
+            token!(pos!(2, 2), Type::Ampersand),
+            token!(pos!(3, 2), Type::Question),
         ];
 
         for token in expected {
@@ -303,6 +746,136 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_match() {
+        let test_case = "Shape.Circle(r) => x.";
+        let lexer = Lexer::from(test_case);
+
+        let expected = [
+            token!(pos!(1, 1), Type::Word("Shape.Circle".into())),
+            token!(pos!(13, 1), Type::LParen),
+            token!(pos!(14, 1), Type::Word("r".into())),
+            token!(pos!(15, 1), Type::RParen),
+            token!(pos!(17, 1), Type::FatArrow),
+            token!(pos!(20, 1), Type::Word("x".into())),
+        ];
+
+        let tokens = lexer.collect::<Vec<_>>();
+        for (token, expected) in tokens.iter().zip(expected) {
+            assert_eq!(*token, Ok(expected));
+        }
+        assert!(tokens[6].is_err());
+    }
+
+    #[test]
+    fn test_aug_assign() {
+        let test_case = "i += 1 -= *=/= %= -2";
+        let lexer = Lexer::from(test_case);
+
+        let expected = [
+            token!(pos!(1, 1), Type::Word("i".into())),
+            token!(pos!(3, 1), Type::AugAssign(Operator::Add)),
+            token!(pos!(6, 1), Type::Primitive(Primitive::Int(1))),
+            token!(pos!(8, 1), Type::AugAssign(Operator::Sub)),
+            token!(pos!(11, 1), Type::AugAssign(Operator::Mult)),
+            token!(pos!(13, 1), Type::AugAssign(Operator::Div)),
+            token!(pos!(16, 1), Type::AugAssign(Operator::Mod)),
+            token!(pos!(19, 1), Type::Primitive(Primitive::Int(-2))),
+        ];
+
+        let tokens = lexer.collect::<ZResult<Vec<_>>>().unwrap();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_minus() {
+        let tokens = Lexer::from("x-1 f()-2 a[0]-3 = -4 - -5\n-6")
+            .map(|token| token.unwrap().value)
+            .filter(|value| !matches!(value, Type::Word(_)))
+            .collect::<Vec<_>>();
+
+        let sub = Type::Op(Operator::Sub);
+        let int = |i| Type::Primitive(Primitive::Int(i));
+        let expected = [
+            sub.clone(),
+            int(1),
+            Type::LParen,
+            Type::RParen,
+            sub.clone(),
+            int(2),
+            Type::LBracket,
+            int(0),
+            Type::RBracket,
+            sub.clone(),
+            int(3),
+            Type::Equals,
+            int(-4),
+            sub,
+            int(-5),
+            Type::Nl,
+            int(-6),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_trailing_operators() {
+        let cases = [
+            ("x =", Type::Equals),
+            ("x -", Type::Op(Operator::Sub)),
+            ("x /", Type::Op(Operator::Div)),
+            ("x <", Type::Op(Operator::Less)),
+            ("x >", Type::Op(Operator::Greater)),
+            ("x !", Type::Not),
+        ];
+
+        for (source, last) in cases {
+            let tokens = Lexer::from(source)
+                .map(|token| token.unwrap().value)
+                .collect::<Vec<_>>();
+            assert_eq!(tokens, [Type::Word("x".into()), last], "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_bitwise() {
+        let test_case = "a&b|c^~d<<2>>1<=e<<=f>>=g&=h|=i^=j";
+        let tokens = Lexer::from(test_case)
+            .map(|token| token.unwrap().value)
+            .filter(|value| !matches!(value, Type::Word(_)))
+            .collect::<Vec<_>>();
+
+        let expected = [
+            Type::Ampersand,
+            Type::Op(Operator::BitOr),
+            Type::Op(Operator::BitXor),
+            Type::Tilde,
+            Type::Op(Operator::ShiftLeft),
+            Type::Primitive(Primitive::Int(2)),
+            Type::Op(Operator::ShiftRight),
+            Type::Primitive(Primitive::Int(1)),
+            Type::Op(Operator::LessEquals),
+            Type::AugAssign(Operator::ShiftLeft),
+            Type::AugAssign(Operator::ShiftRight),
+            Type::AugAssign(Operator::BitAnd),
+            Type::AugAssign(Operator::BitOr),
+            Type::AugAssign(Operator::BitXor),
+        ];
+        assert_eq!(tokens, expected);
+
+        let mut lexer = Lexer::from("a && b");
+        lexer.next();
+        assert_eq!(
+            lexer.next(),
+            Some(Err(CompilerError::new(
+                1,
+                3,
+                2,
+                "'&&' isn't an operator, use '&' to combine bools."
+            )))
+        );
+    }
+
     #[test]
     fn test_numbers() {
         let test_case = "23 2.5 1_349__2_";
@@ -319,6 +892,297 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_number_overflow() {
+        let mut lexer =
+            Lexer::from("18446744073709551615 18446744073709551616 0xFFFF_FFFF_FFFF_FFFFu64 1.2.3");
+
+        assert_eq!(
+            lexer.next().unwrap().unwrap().value,
+            Type::Primitive(Primitive::Int(u64::MAX as i128))
+        );
+        assert_eq!(
+            lexer.next().unwrap().unwrap_err(),
+            CompilerError::new(1, 22, 20, "Integer literal is out of range.")
+        );
+        assert_eq!(
+            lexer.next().unwrap().unwrap().value,
+            Type::Suffixed(Primitive::Int(u64::MAX as i128), "u64".to_owned())
+        );
+        assert_eq!(
+            lexer.next().unwrap().unwrap_err(),
+            CompilerError::new(1, 68, 5, "Invalid float literal.")
+        );
+    }
+
+    #[test]
+    fn test_number_forms() {
+        let test_case = "0xff 0o17 0b1010_1010 1e-3 2.5E2 10u8 3f32,-0x10 1_000i64 3.0f64";
+        let mut lexer = Lexer::from(test_case);
+
+        let expected = [
+            token!(pos!(1, 1), Type::Primitive(Primitive::Int(255))),
+            token!(pos!(6, 1), Type::Primitive(Primitive::Int(15))),
+            token!(pos!(11, 1), Type::Primitive(Primitive::Int(170))),
+            token!(pos!(23, 1), Type::Primitive(Primitive::Float(0.001))),
+            token!(pos!(28, 1), Type::Primitive(Primitive::Float(250.0))),
+            token!(
+                pos!(34, 1),
+                Type::Suffixed(Primitive::Int(10), "u8".to_owned())
+            ),
+            token!(
+                pos!(39, 1),
+                Type::Suffixed(Primitive::Float(3.0), "f32".to_owned())
+            ),
+            token!(pos!(43, 1), Type::Comma),
+            token!(pos!(44, 1), Type::Primitive(Primitive::Int(-16))),
+            token!(
+                pos!(50, 1),
+                Type::Suffixed(Primitive::Int(1000), "i64".to_owned())
+            ),
+            token!(
+                pos!(59, 1),
+                Type::Suffixed(Primitive::Float(3.0), "f64".to_owned())
+            ),
+        ];
+
+        for token in expected {
+            assert_eq!(token, lexer.next().unwrap().unwrap());
+        }
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn test_number_errors() {
+        let cases = [
+            (
+                "0b102",
+                CompilerError::new(1, 5, 1, "Invalid digit '2' in a binary literal."),
+            ),
+            (
+                "0o8",
+                CompilerError::new(1, 3, 1, "Invalid digit '8' in an octal literal."),
+            ),
+            (
+                "10abc",
+                CompilerError::new(1, 3, 1, "Invalid digit 'a' in a number."),
+            ),
+            (
+                "0x",
+                CompilerError::new(1, 1, 2, "Expected digits after '0x'."),
+            ),
+            (
+                "7u7",
+                CompilerError::new(1, 2, 2, "Unknown suffix 'u7', expected a number type."),
+            ),
+            (
+                "1.5u8",
+                CompilerError::new(1, 4, 2, "Floats can't have an integer suffix."),
+            ),
+            (
+                "0b1f32",
+                CompilerError::new(1, 1, 6, "Only decimal numbers can be floats."),
+            ),
+            (
+                "1e999",
+                CompilerError::new(1, 1, 5, "Float literal is out of range."),
+            ),
+            (
+                "0x1_0000_0000_0000_0000",
+                CompilerError::new(1, 1, 23, "Integer literal is out of range."),
+            ),
+        ];
+
+        for (source, error) in cases {
+            let mut lexer = Lexer::from(source);
+            assert_eq!(lexer.next().unwrap().unwrap_err(), error, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_strings() {
+        let test_case = "\"\\t\\x41\\u{e9}\\0\\xFF\" r\"C:\\n\" \"one\ntwo\\\n    three\" end";
+        let mut lexer = Lexer::from(test_case);
+
+        let expected = [
+            token!(
+                pos!(1, 1),
+                Type::Primitive(Primitive::Str(b"\tA\xC3\xA9\0\xFF".to_vec()))
+            ),
+            token!(
+                pos!(22, 1),
+                Type::Primitive(Primitive::Str(b"C:\\n".to_vec()))
+            ),
+            token!(
+                pos!(30, 1),
+                Type::Primitive(Primitive::Str(b"one\ntwothree".to_vec()))
+            ),
+            token!(pos!(12, 3), Type::Word("end".into())),
+        ];
+
+        for token in expected {
+            assert_eq!(token, lexer.next().unwrap().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let cases = [
+            (
+                "\"\\q\"",
+                CompilerError::new(1, 2, 2, "Unknown escape char."),
+            ),
+            (
+                "\"\\xF\"",
+                CompilerError::new(1, 2, 2, "Expected two hex digits after \\x."),
+            ),
+            (
+                "\"\\u{110000}\"",
+                CompilerError::new(1, 2, 10, "Invalid unicode escape, expected \\u{..}."),
+            ),
+            ("\"abc", CompilerError::new(1, 1, 1, "Unterminated string.")),
+        ];
+
+        for (source, expected) in cases {
+            let mut lexer = Lexer::from(source);
+            assert_eq!(lexer.next().unwrap().unwrap_err(), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_chars() {
+        let test_case = "'a' '\\n' '\\'' '\\x41' x";
+        let mut lexer = Lexer::from(test_case);
+
+        let expected = [
+            token!(pos!(1, 1), Type::Primitive(Primitive::Char('a'))),
+            token!(pos!(5, 1), Type::Primitive(Primitive::Char('\n'))),
+            token!(pos!(10, 1), Type::Primitive(Primitive::Char('\''))),
+            token!(pos!(15, 1), Type::Primitive(Primitive::Char('A'))),
+            token!(pos!(22, 1), Type::Word("x".into())),
+        ];
+
+        for token in expected {
+            assert_eq!(token, lexer.next().unwrap().unwrap());
+        }
+
+        let cases = [
+            (
+                "''",
+                CompilerError::new(1, 1, 2, "Empty character literal."),
+            ),
+            (
+                "'ab'",
+                CompilerError::new(1, 1, 4, "Character literals can only hold one character."),
+            ),
+            (
+                "'\u{e9}'",
+                CompilerError::new(1, 1, 3, "Characters must be ASCII, use a string instead."),
+            ),
+            (
+                "'\\xFF'",
+                CompilerError::new(1, 1, 6, "Characters must be ASCII, use a string instead."),
+            ),
+            (
+                "'a",
+                CompilerError::new(1, 1, 1, "Unterminated character literal."),
+            ),
+        ];
+
+        for (source, expected) in cases {
+            let mut lexer = Lexer::from(source);
+            assert_eq!(lexer.next().unwrap().unwrap_err(), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let test_case = "a // b\n/* c /* d */\n e */ f\n/// Doc\n//// g\n///x\nh // end";
+        let mut lexer = Lexer::from(test_case);
+
+        let expected = [
+            token!(pos!(1, 1), Type::Word("a".into())),
+            token!(pos!(7, 1), Type::Nl),
+            token!(pos!(7, 3), Type::Word("f".into())),
+            token!(pos!(8, 3), Type::Nl),
+            token!(pos!(1, 4), Type::DocComment("Doc".to_owned())),
+            token!(pos!(8, 4), Type::Nl),
+            token!(pos!(7, 5), Type::Nl),
+            token!(pos!(1, 6), Type::DocComment("x".to_owned())),
+            token!(pos!(5, 6), Type::Nl),
+            token!(pos!(1, 7), Type::Word("h".into())),
+        ];
+
+        for token in expected {
+            assert_eq!(token, lexer.next().unwrap().unwrap());
+        }
+        assert!(lexer.next().is_none());
+
+        let mut lexer = Lexer::from("x /* a /* b */");
+        lexer.next();
+        assert_eq!(
+            lexer.next().unwrap().unwrap_err(),
+            CompilerError::new(1, 3, 2, "Unterminated block comment.")
+        );
+    }
+
+    #[test]
+    fn test_unicode() {
+        let test_case = "žaba\tΔx = 日本 + x\u{301}\r\n\tend\r\n€";
+        let mut lexer = Lexer::from(test_case);
+
+        let expected = [
+            token!(pos!(1, 1), Type::Word("žaba".into())),
+            token!(pos!(6, 1), Type::Word("Δx".into())),
+            token!(pos!(9, 1), Type::Equals),
+            token!(pos!(11, 1), Type::Word("日本".into())),
+            token!(pos!(14, 1), Type::Op(Operator::Add)),
+            token!(pos!(16, 1), Type::Word("x\u{301}".into())),
+            token!(pos!(18, 1), Type::Nl),
+            token!(pos!(2, 2), Type::Word("end".into())),
+            token!(pos!(5, 2), Type::Nl),
+        ];
+
+        for token in expected {
+            assert_eq!(token, lexer.next().unwrap().unwrap());
+        }
+        assert_eq!(
+            lexer.next().unwrap().unwrap_err(),
+            CompilerError::new(3, 1, 1, "Unexpected char.")
+        );
+
+        let mut lexer = Lexer::from("\"a\r\nb\\\r\n  c\" x");
+        assert_eq!(
+            lexer.next().unwrap().unwrap().value,
+            Type::Primitive(Primitive::Str(b"a\nbc".to_vec()))
+        );
+        assert_eq!(lexer.next().unwrap().unwrap().pos, pos!(6, 3));
+    }
+
+    #[test]
+    fn test_reader() {
+        struct Failing;
+
+        impl std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+        }
+
+        let source = "fun žaba(x: i32) -> i32 {\n    return x << 2 // four times\n}\n";
+        let from_str = Lexer::from(source).tokenize().unwrap();
+        let from_reader = Lexer::from_reader(source.as_bytes()).tokenize().unwrap();
+        assert!(from_str.eq(from_reader));
+
+        let mut lexer = Lexer::from_reader(std::io::Read::chain("a".as_bytes(), Failing));
+        assert_eq!(lexer.next().unwrap().unwrap().value, Type::Word("a".into()));
+        assert_eq!(
+            lexer.next().unwrap().unwrap_err(),
+            CompilerError::new(1, 2, 1, "Couldn't read the source: disk on fire.")
+        );
+        assert!(lexer.next().is_none());
+    }
+
     #[test]
     fn test_main() {
         let test_case = "fun main() -> int { return 0; }\n";
@@ -326,11 +1190,11 @@ mod tests {
 
         let expected = [
             token!(pos!(1, 1), Type::Keyword(Keyword::Fun)),
-            token!(pos!(5, 1), Type::Word("main".to_owned())),
+            token!(pos!(5, 1), Type::Word("main".into())),
             token!(pos!(9, 1), Type::LParen),
             token!(pos!(10, 1), Type::RParen),
             token!(pos!(12, 1), Type::Arrow),
-            token!(pos!(15, 1), Type::Word("int".to_owned())),
+            token!(pos!(15, 1), Type::Word("int".into())),
             token!(pos!(19, 1), Type::LBrace),
             token!(pos!(21, 1), Type::Keyword(Keyword::Return)),
             token!(pos!(28, 1), Type::Primitive(Primitive::Int(0))),
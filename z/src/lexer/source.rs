@@ -0,0 +1,197 @@
+use std::{collections::VecDeque, io, io::Read, str::Chars};
+
+/// How many bytes are read from a reader at once.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// The characters the lexer reads, either from a string or decoded from a
+/// reader through a fixed size buffer. Any number of characters can be
+/// looked at before they're consumed.
+pub struct Source<'guard> {
+    input: Input<'guard>,
+    lookahead: VecDeque<char>,
+    error: Option<io::Error>,
+}
+
+enum Input<'guard> {
+    Str(Chars<'guard>),
+    Reader {
+        reader: Box<dyn Read + 'guard>,
+        buffer: Box<[u8]>,
+        /// The bytes of `buffer` that haven't been decoded yet.
+        start: usize,
+        end: usize,
+    },
+}
+
+impl<'guard> From<&'guard str> for Source<'guard> {
+    fn from(source: &'guard str) -> Self {
+        Self::new(Input::Str(source.chars()))
+    }
+}
+
+impl<'guard> Source<'guard> {
+    pub fn from_reader(reader: impl Read + 'guard) -> Self {
+        Self::new(Input::Reader {
+            reader: Box::new(reader),
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            start: 0,
+            end: 0,
+        })
+    }
+
+    fn new(input: Input<'guard>) -> Self {
+        Source {
+            input,
+            lookahead: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<&char> {
+        self.fill(1);
+        self.lookahead.front()
+    }
+
+    /// The character `n` places after the next one, `peek_nth(0)` is the
+    /// same as `peek`.
+    pub fn peek_nth(&mut self, n: usize) -> Option<char> {
+        self.fill(n + 1);
+        self.lookahead.get(n).copied()
+    }
+
+    pub fn next_if(&mut self, func: impl FnOnce(&char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(ch) if func(ch) => self.next(),
+            _ => None,
+        }
+    }
+
+    pub fn next_if_eq(&mut self, expected: &char) -> Option<char> {
+        self.next_if(|ch| ch == expected)
+    }
+
+    /// The error that stopped reading early, the source looks like it
+    /// ended there.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    fn fill(&mut self, len: usize) {
+        while self.lookahead.len() < len {
+            match self.decode() {
+                Some(ch) => self.lookahead.push_back(ch),
+                None => break,
+            }
+        }
+    }
+
+    fn decode(&mut self) -> Option<char> {
+        let (reader, buffer, start, end) = match &mut self.input {
+            Input::Str(chars) => return chars.next(),
+            Input::Reader {
+                reader,
+                buffer,
+                start,
+                end,
+            } => (reader, buffer, start, end),
+        };
+
+        // A character can be split between two reads, so read until all of
+        // its bytes are there.
+        let len = loop {
+            let len = if *start < *end {
+                utf8_len(buffer[*start])
+            } else {
+                1
+            };
+            if *end - *start >= len {
+                break len;
+            }
+
+            buffer.copy_within(*start..*end, 0);
+            *end -= *start;
+            *start = 0;
+            match reader.read(&mut buffer[*end..]) {
+                Ok(0) => break len,
+                Ok(read) => *end += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                // Nothing is read after an error.
+                Err(err) => {
+                    self.error = Some(err);
+                    self.input = Input::Str("".chars());
+                    return None;
+                }
+            }
+        };
+
+        if *start == *end {
+            return None;
+        }
+
+        let available = len.min(*end - *start);
+        match std::str::from_utf8(&buffer[*start..*start + available]) {
+            Ok(text) => {
+                *start += available;
+                text.chars().next()
+            }
+            // Invalid bytes are skipped one by one, like `String::from_utf8_lossy`.
+            Err(_) => {
+                *start += 1;
+                Some(char::REPLACEMENT_CHARACTER)
+            }
+        }
+    }
+}
+
+impl Iterator for Source<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        self.lookahead.pop_front().or_else(|| self.decode())
+    }
+}
+
+/// The length of a UTF-8 sequence from its first byte.
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out at most one byte per read to split every character.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((byte, rest)) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader() {
+        let text = "fun žaba() { 日本 }\n".repeat(1000);
+        let mut source = Source::from_reader(Trickle(text.as_bytes()));
+
+        assert_eq!(source.peek_nth(4), Some('ž'));
+        assert_eq!(source.peek(), Some(&'f'));
+        assert_eq!(source.by_ref().collect::<String>(), text);
+        assert!(source.take_error().is_none());
+
+        let source = Source::from_reader(&b"a\xffb\xe6"[..]);
+        assert_eq!(source.collect::<String>(), "a\u{fffd}b\u{fffd}");
+    }
+}
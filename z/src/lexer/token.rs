@@ -1,3 +1,4 @@
+use super::intern::Symbol;
 use crate::{
     error::{CompilerError, MakeErr},
     parser::ast::Primitive,
@@ -7,7 +8,7 @@ use zasm::types::Operator;
 #[derive(Debug, PartialEq, Clone, Default)]
 pub enum Type {
     Keyword(Keyword),
-    Word(String),
+    Word(Symbol),
     LParen,
     RParen,
     #[default]
@@ -18,12 +19,23 @@ pub enum Type {
     RBracket,
     Op(Operator),
     Arrow,
+    FatArrow,
     DoubleDot,
     Comma,
     Equals,
+    /// `+=`, `-=`, `*=`, `/=`, `%=`, `&=`, `|=`, `^=`, `<<=` and `>>=`.
+    AugAssign(Operator),
     Not,
+    /// `&` is a prefix for addresses and the bitwise and between values.
+    Ampersand,
+    Tilde,
+    Question,
+    /// The text of a `///` comment, without the slashes.
+    DocComment(String),
 
     Primitive(Primitive),
+    /// A number with its type written after it, `10u8`.
+    Suffixed(Primitive, String),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -38,6 +50,9 @@ pub enum Keyword {
     Break,
     Loop,
     Return,
+    As,
+    Enum,
+    Match,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -67,6 +82,39 @@ impl Default for Token {
     }
 }
 
+/// All the tokens of a source, any of the ones that weren't consumed yet
+/// can be looked at.
+#[derive(Debug, Default)]
+pub struct Tokens {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Tokens {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Tokens { tokens, next: 0 }
+    }
+
+    pub fn peek(&self) -> Option<&Token> {
+        self.peek_nth(0)
+    }
+
+    /// The token `n` places after the next one.
+    pub fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.next + n)
+    }
+}
+
+impl Iterator for Tokens {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get_mut(self.next)?;
+        self.next += 1;
+        Some(std::mem::take(token))
+    }
+}
+
 impl MakeErr for Token {
     fn into_err(self, message: &str) -> CompilerError {
         CompilerError::new(self.pos.line as usize, self.pos.column as usize, 1, message)
@@ -101,4 +149,24 @@ mod tests {
 
         assert_eq!(test_case, expected)
     }
+
+    #[test]
+    fn test_tokens() {
+        let token = |column, value| Token {
+            pos: pos!(column, 1),
+            value,
+        };
+        let mut tokens = Tokens::new(vec![
+            token(1, Type::Word("a".into())),
+            token(3, Type::Equals),
+            token(5, Type::Word("b".into())),
+        ]);
+
+        assert_eq!(tokens.peek_nth(2), Some(&token(5, Type::Word("b".into()))));
+        assert_eq!(tokens.next(), Some(token(1, Type::Word("a".into()))));
+        assert_eq!(tokens.peek(), Some(&token(3, Type::Equals)));
+        assert_eq!(tokens.peek_nth(2), None);
+        assert_eq!(tokens.by_ref().count(), 2);
+        assert_eq!(tokens.next(), None);
+    }
 }
@@ -1,8 +1,10 @@
 #[allow(clippy::new_without_default)]
 pub mod compiler;
+pub mod doc;
 pub mod error;
 mod grammar;
 #[allow(clippy::new_without_default)]
 pub mod lexer;
 #[allow(clippy::new_without_default)]
 pub mod parser;
+pub mod toolchain;
@@ -1,67 +1,217 @@
 mod args;
 
-use args::Args;
+use args::{
+    Args, AsmSyntax, BuildArgs, CheckArgs, Command, DocArgs, DocFormat, EmitArgs, EmitKind, RunArgs,
+};
 use clap::Parser;
+use std::env;
 use std::fs;
-use std::process::Command;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{self, ExitCode};
 use z::compiler::Compiler as zCompiler;
+use z::doc;
 use z::lexer::Lexer as zLexer;
-use z::parser::Parser as zParser;
+use z::parser::{ast::Module as zModule, Parser as zParser};
+use z::toolchain;
+use zasm::{printer::Syntax, Module};
 
-const TEMPFILE: &str = "/tmp/.zcompiled";
+const DEFAULT_OBJECT: &str = "main.o";
 
-fn main() {
-    let mut parser = zParser::new();
-    let mut compiler = zCompiler::new();
+type CmdResult<T> = Result<T, ExitCode>;
+
+fn main() -> ExitCode {
     let args = Args::parse();
 
-    let source = match fs::read_to_string(&args.file) {
-        Ok(source) => source,
-        Err(_) => {
-            println!("File: {} doesn't exist.", args.file);
-            return;
-        }
+    let result = match args.command {
+        Command::Build(args) => build(&args).map(|_| ExitCode::SUCCESS),
+        Command::Run(args) => run(&args),
+        Command::Check(args) => check(&args).map(|_| ExitCode::SUCCESS),
+        Command::Emit(args) => emit(&args).map(|_| ExitCode::SUCCESS),
+        Command::Doc(args) => doc(&args).map(|_| ExitCode::SUCCESS),
     };
-    let lexer = zLexer::from(&source);
 
-    let module = match parser.parse(lexer) {
-        Ok(module) => module,
-        Err(err) => return err.display(&source),
-    };
+    result.unwrap_or_else(|code| code)
+}
+
+fn build(args: &BuildArgs) -> CmdResult<()> {
+    let ast = parse(&args.file)?;
 
-    if args.parse_only {
-        println!("{:#?}", module);
-        return;
+    let mut compiler = zCompiler::new();
+    compiler.set_opt_level(args.opt_level);
+    let module = compile(&mut compiler, ast, &args.file)?;
+
+    if let Some(asm_file) = &args.asm {
+        module.write_to_file(asm_file).map_err(io_failure)?;
     }
 
-    let module = match compiler.compile(module) {
-        Ok(module) => module,
-        Err(err) => return err.display(&source),
+    let object_file = temp_file("o");
+    let linked = write_object(module, &object_file, args.nasm)
+        .and_then(|_| toolchain::link(&object_file, &args.out).map_err(io_failure));
+    let _ = fs::remove_file(&object_file);
+
+    linked
+}
+
+fn run(args: &RunArgs) -> CmdResult<ExitCode> {
+    build(&args.build)?;
+
+    let exe = if args.build.out.contains('/') {
+        args.build.out.clone()
+    } else {
+        format!("./{}", args.build.out)
+    };
+
+    let status = process::Command::new(&exe)
+        .args(&args.args)
+        .status()
+        .map_err(io_failure)?;
+
+    // Mirror the shell's convention for processes killed by a signal.
+    let code = match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
     };
 
-    if args.dry_run {
-        return;
-    }
+    Ok(ExitCode::from(code as u8))
+}
+
+fn check(args: &CheckArgs) -> CmdResult<()> {
+    let ast = parse(&args.file)?;
+
+    let mut compiler = zCompiler::new();
+    compile(&mut compiler, ast, &args.file)?;
+
+    Ok(())
+}
 
-    let tmp_file = if let Some(file) = args.asm {
-        if file.is_empty() {
-            "out.asm".to_owned()
-        } else {
-            file
+fn emit(args: &EmitArgs) -> CmdResult<()> {
+    let text = match args.kind {
+        EmitKind::Tokens => {
+            let mut out = String::new();
+            for token in zLexer::from_reader(open_source(&args.file)?) {
+                match token {
+                    Ok(token) => out.push_str(&format!("{:?}\n", token)),
+                    Err(err) => return Err(compile_failure(&err, &args.file)),
+                }
+            }
+            out
         }
-    } else {
-        TEMPFILE.to_owned()
+        EmitKind::Ast => format!("{:#?}\n", parse(&args.file)?),
+        EmitKind::Ir | EmitKind::Asm => {
+            let ast = parse(&args.file)?;
+            let mut compiler = zCompiler::new();
+            compiler.set_opt_level(args.opt_level);
+            let module = compile(&mut compiler, ast, &args.file)?;
+
+            if args.kind == EmitKind::Ir {
+                format!("{:#?}\n", module)
+            } else {
+                module.to_asm(match args.syntax {
+                    AsmSyntax::Nasm => Syntax::Nasm,
+                    AsmSyntax::Gas => Syntax::Gas,
+                })
+            }
+        }
+        EmitKind::Obj => {
+            let ast = parse(&args.file)?;
+            let mut compiler = zCompiler::new();
+            compiler.set_opt_level(args.opt_level);
+            let module = compile(&mut compiler, ast, &args.file)?;
+
+            let object_file = args.out.as_deref().unwrap_or(DEFAULT_OBJECT);
+            return write_object(module, object_file, args.nasm);
+        }
+    };
+
+    match &args.out {
+        Some(file) => fs::write(file, text).map_err(io_failure),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+fn doc(args: &DocArgs) -> CmdResult<()> {
+    let ast = parse(&args.file)?;
+
+    let title = Path::new(&args.file)
+        .file_stem()
+        .map_or(args.file.clone(), |stem| {
+            stem.to_string_lossy().into_owned()
+        });
+    let text = match args.format {
+        DocFormat::Markdown => doc::markdown(&ast, &title),
+        DocFormat::Html => doc::html(&ast, &title),
     };
 
-    module
-        .write_to_file(&tmp_file)
-        .expect("Failed to write to tempfile.");
-    Command::new("nasm")
-        .arg("-felf64")
-        .arg("-g")
-        .arg("-o")
-        .arg(args.out)
-        .arg(tmp_file)
-        .spawn()
-        .expect("Failed to run nasm.");
+    match &args.out {
+        Some(file) => fs::write(file, text).map_err(io_failure),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+fn write_object(module: &Module, object_file: &str, nasm: bool) -> CmdResult<()> {
+    if !nasm {
+        return module.write_object(object_file).map_err(io_failure);
+    }
+
+    let asm_file = temp_file("asm");
+    let assembled = module
+        .write_to_file(&asm_file)
+        .and_then(|_| toolchain::assemble(&asm_file, object_file))
+        .map_err(io_failure);
+    let _ = fs::remove_file(&asm_file);
+
+    assembled
+}
+
+/// A path in the temp dir with the process id in it, so builds running at
+/// the same time don't share files.
+fn temp_file(extension: &str) -> String {
+    let name = format!(".zcompiled-{}.{}", process::id(), extension);
+    env::temp_dir().join(name).to_string_lossy().into_owned()
+}
+
+fn open_source(file: &str) -> CmdResult<fs::File> {
+    fs::File::open(file).map_err(|_| {
+        eprintln!("File: {} doesn't exist.", file);
+        ExitCode::FAILURE
+    })
+}
+
+/// The file is lexed as it's read, it's only read as a whole to show an
+/// error.
+fn parse(file: &str) -> CmdResult<zModule> {
+    let mut parser = zParser::new();
+    parser
+        .parse(zLexer::from_reader(open_source(file)?))
+        .map_err(|err| compile_failure(&err, file))
+}
+
+fn compile<'a>(
+    compiler: &'a mut zCompiler<'static>,
+    ast: zModule,
+    file: &str,
+) -> CmdResult<&'a Module<'static>> {
+    compiler
+        .compile(ast)
+        .map_err(|err| compile_failure(&err, file))
+}
+
+fn compile_failure(err: &z::error::CompilerError, file: &str) -> ExitCode {
+    let source = fs::read_to_string(file).unwrap_or_default();
+    err.display(&source);
+    ExitCode::FAILURE
+}
+
+fn io_failure(err: io::Error) -> ExitCode {
+    eprintln!("{}", err);
+    ExitCode::FAILURE
 }
@@ -1,6 +1,8 @@
+use std::fmt;
+
 use zasm::types;
 
-use crate::lexer::token::SourcePos;
+use crate::lexer::{intern::Symbol, token::SourcePos};
 
 #[derive(Debug, PartialEq)]
 pub struct Module {
@@ -15,10 +17,13 @@ impl Module {
 
 #[derive(Debug, PartialEq)]
 pub struct FunctionDef {
-    pub name: String,
+    pub name: Symbol,
     pub args: Vec<Node>,
     pub body: Vec<Node>,
     pub returns: Box<Node>,
+    pub pos: SourcePos,
+    /// The `///` comments right before the function.
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -26,11 +31,13 @@ pub struct Return {
     pub value: Box<Node>,
 }
 
+/// `if test { run } else orelse`, as a value both branches end with one.
 #[derive(Debug, PartialEq)]
 pub struct If {
     pub test: Box<Node>,
     pub run: Scope,
     pub orelse: Box<Node>,
+    pub pos: SourcePos,
 }
 
 #[derive(Debug, PartialEq)]
@@ -45,26 +52,39 @@ pub struct Loop {
 
 #[derive(Debug, PartialEq)]
 pub struct Arg {
-    pub name: String,
+    pub name: Symbol,
     pub annotation: Box<Node>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Assign {
-    pub target: String,
+    pub target: Symbol,
+    pub value: Box<Node>,
+    pub pos: SourcePos,
+}
+
+/// `x += value`, the target is checked like an `Assign`'s.
+#[derive(Debug, PartialEq)]
+pub struct AugAssign {
+    pub target: Symbol,
+    pub op: types::Operator,
     pub value: Box<Node>,
     pub pos: SourcePos,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct VariableDef {
-    pub name: String,
+    pub name: Symbol,
     pub mutable: bool,
+    pub annotation: Box<Node>,
     pub value: Box<Node>,
 }
 #[derive(Debug, PartialEq)]
 pub struct Constant {
     pub value: Primitive,
+    /// The type written after a number, `10u8`.
+    pub suffix: Option<String>,
+    pub pos: SourcePos,
 }
 
 #[derive(Debug, PartialEq)]
@@ -75,7 +95,7 @@ pub struct BinOp {
 }
 #[derive(Debug, PartialEq)]
 pub struct Name {
-    pub id: String,
+    pub id: Symbol,
 }
 
 #[derive(Debug, PartialEq)]
@@ -87,6 +107,91 @@ pub struct List {
 pub struct Call {
     pub func: Name,
     pub args: Vec<Node>,
+    pub pos: SourcePos,
+}
+
+/// `value as target`
+#[derive(Debug, PartialEq)]
+pub struct Cast {
+    pub value: Box<Node>,
+    pub target: Symbol,
+    pub pos: SourcePos,
+}
+
+/// `value[index]`
+#[derive(Debug, PartialEq)]
+pub struct Index {
+    pub value: Box<Node>,
+    pub index: Box<Node>,
+    pub pos: SourcePos,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOp {
+    /// `&x`
+    AddressOf,
+    /// `*p`
+    Deref,
+    /// `~x`
+    BitNot,
+}
+
+/// A prefix operator applied to a value.
+#[derive(Debug, PartialEq)]
+pub struct Unary {
+    pub op: UnaryOp,
+    pub value: Box<Node>,
+    pub pos: SourcePos,
+}
+
+/// `enum Name { Variant(fields), ... }`
+#[derive(Debug, PartialEq)]
+pub struct EnumDef {
+    pub name: Symbol,
+    pub variants: Vec<Variant>,
+    pub pos: SourcePos,
+    pub doc: Option<String>,
+}
+
+/// A variant and the types of its payload, which are names.
+#[derive(Debug, PartialEq)]
+pub struct Variant {
+    pub name: Symbol,
+    pub fields: Vec<Node>,
+    pub pos: SourcePos,
+    pub doc: Option<String>,
+}
+
+/// `match value { Variant(bindings) => body, ... }`
+#[derive(Debug, PartialEq)]
+pub struct Match {
+    pub value: Box<Node>,
+    pub arms: Vec<MatchArm>,
+    pub pos: SourcePos,
+}
+
+/// An arm of a `match`, without a variant it's the `_` arm.
+#[derive(Debug, PartialEq)]
+pub struct MatchArm {
+    pub variant: Option<Symbol>,
+    pub bindings: Vec<Symbol>,
+    pub body: Vec<Node>,
+    pub pos: SourcePos,
+}
+
+/// `value?`, unwraps an ok result or returns the error.
+#[derive(Debug, PartialEq)]
+pub struct Try {
+    pub value: Box<Node>,
+    pub pos: SourcePos,
+}
+
+/// `*pointer = value`
+#[derive(Debug, PartialEq)]
+pub struct Store {
+    pub pointer: Box<Node>,
+    pub value: Box<Node>,
+    pub pos: SourcePos,
 }
 
 #[derive(Debug, PartialEq)]
@@ -94,6 +199,7 @@ pub enum Node {
     FunctionDef(FunctionDef),
     VariableDef(VariableDef),
     Assign(Assign),
+    AugAssign(AugAssign),
     Arg(Arg),
     Constant(Constant),
     BinOp(BinOp),
@@ -104,30 +210,63 @@ pub enum Node {
     Loop(Loop),
     Return(Return),
     List(List),
+    Cast(Cast),
+    Index(Index),
+    Unary(Unary),
+    Store(Store),
+    Try(Try),
+    EnumDef(EnumDef),
+    Match(Match),
 
     Break(SourcePos),
     None,
 }
 
+impl Node {
+    /// Where the node starts in the source, if it's known.
+    pub fn pos(&self) -> Option<&SourcePos> {
+        match self {
+            Node::Name(_, pos) | Node::Break(pos) => Some(pos),
+            Node::Constant(constant) => Some(&constant.pos),
+            Node::Call(call) => Some(&call.pos),
+            Node::If(case) => Some(&case.pos),
+            Node::Assign(assign) => Some(&assign.pos),
+            Node::AugAssign(assign) => Some(&assign.pos),
+            Node::Unary(unary) => Some(&unary.pos),
+            Node::Store(store) => Some(&store.pos),
+            Node::Cast(cast) => cast.value.pos().or(Some(&cast.pos)),
+            Node::Index(index) => index.value.pos().or(Some(&index.pos)),
+            Node::Try(try_) => try_.value.pos().or(Some(&try_.pos)),
+            Node::BinOp(binop) => binop.left.pos().or(binop.right.pos()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Default)]
 pub enum Primitive {
-    Int(i32),
-    Float(f32),
-    Str(String),
+    /// Wide enough for every `i64` and `u64`.
+    Int(i128),
+    Float(f64),
+    /// The bytes of a string, `\xFF` makes them invalid UTF-8.
+    Str(Vec<u8>),
     Bool(bool),
+    /// An ASCII character, `'a'`.
+    Char(char),
 
     #[default]
     None,
 }
 
-impl ToString for Primitive {
-    fn to_string(&self) -> String {
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Primitive::Int(i) => i.to_string(),
-            Primitive::Float(f) => f.to_string(),
-            Primitive::Str(s) => s.clone(),
-            Primitive::Bool(b) => b.to_string(),
-            Primitive::None => "".to_owned(),
+            Primitive::Int(i) => write!(f, "{}", i),
+            Primitive::Float(x) => write!(f, "{}", x),
+            Primitive::Str(s) => write!(f, "{}", String::from_utf8_lossy(s)),
+            Primitive::Bool(b) => write!(f, "{}", b),
+            Primitive::Char(c) => write!(f, "{:?}", c),
+            Primitive::None => Ok(()),
         }
     }
 }
@@ -140,6 +279,7 @@ mod tests {
     fn test_primitive() {
         let t = Primitive::Float(5.2);
 
-        assert_eq!(&t.to_string(), "5.2")
+        assert_eq!(&t.to_string(), "5.2");
+        assert_eq!(Primitive::Char('\n').to_string(), "'\\n'");
     }
 }
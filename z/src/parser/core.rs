@@ -1,12 +1,15 @@
 use std::borrow::BorrowMut;
 
 use super::ast::{
-    Arg, Assign, BinOp, Call, Constant, FunctionDef, If, List, Loop, Module, Name, Node, Primitive,
-    Return, Scope, VariableDef,
+    Arg, Assign, AugAssign, BinOp, Call, Cast, Constant, EnumDef, FunctionDef, If, Index, List,
+    Loop, Match, MatchArm, Module, Name, Node, Primitive, Return, Scope, Store, Try, Unary,
+    UnaryOp, VariableDef, Variant,
 };
 use super::{Parser, ZResult};
 use crate::error::MakeErr;
-use crate::lexer::token::{Keyword, Token, Type};
+use crate::grammar;
+use crate::lexer::intern::Symbol;
+use crate::lexer::token::{Keyword, SourcePos, Token, Tokens, Type};
 use crate::lexer::Lexer;
 use crate::parser::rpn::shutting_yard;
 use zasm::types::Operator;
@@ -14,7 +17,7 @@ use zasm::types::Operator;
 macro_rules! next {
     ($self:ident) => {
         match $self.tokens.next() {
-            Some(val) => val?,
+            Some(val) => val,
             None => return Err($self.prev.clone().into_err("invalid syntax")),
         }
     };
@@ -22,10 +25,7 @@ macro_rules! next {
 
 macro_rules! peek {
     ($self:ident) => {
-        match $self.tokens.peek() {
-            Some(val) => val.clone()?,
-            None => Token::default(),
-        }
+        $self.tokens.peek().unwrap_or(&$self.end)
     };
 }
 
@@ -33,26 +33,55 @@ macro_rules! peek {
 pub enum ExprPart {
     Operator(Operator),
     Operand(Node),
+    /// `as <type>`, applies to the operand right before it.
+    Cast(Symbol, SourcePos),
+    /// `[index]`, also applies to the operand right before it.
+    Index(Node, SourcePos),
+    /// `?` after an operand.
+    Try(SourcePos),
+    /// `&` or `*` in front of an operand.
+    Prefix(UnaryOp, SourcePos),
     Lpar,
     Rpar,
 }
 
-impl<'guard> Parser<'guard> {
+/// A `*` or `&` here comes before an operand instead of after one.
+fn is_prefix_position(parts: &[ExprPart]) -> bool {
+    matches!(
+        parts.last(),
+        None | Some(ExprPart::Operator(_) | ExprPart::Lpar | ExprPart::Prefix(..))
+    )
+}
+
+/// The token can continue an expression after an operand.
+fn continues_expr(value: &Type) -> bool {
+    matches!(
+        value,
+        Type::Op(_)
+            | Type::Keyword(Keyword::As)
+            | Type::LBracket
+            | Type::Ampersand
+            | Type::Question
+    )
+}
+
+impl Parser {
     pub fn new() -> Self {
         Parser {
-            tokens: Lexer::default().peekable(),
+            tokens: Tokens::default(),
             prev: Token::default(),
+            end: Token::default(),
+            closed_generic: false,
         }
     }
 
-    pub fn parse(&mut self, lexer: Lexer<'guard>) -> ZResult<Module> {
+    pub fn parse(&mut self, lexer: Lexer) -> ZResult<Module> {
         let mut module = Module::new();
 
-        self.tokens = lexer.peekable();
+        self.tokens = lexer.tokenize()?;
         self.prev = Token::default();
 
         while let Some(current) = self.tokens.next() {
-            let current = current?;
             if current.value == Type::Nl {
                 continue;
             }
@@ -64,27 +93,45 @@ impl<'guard> Parser<'guard> {
     }
 }
 
-impl<'guard> Parser<'guard> {
+impl Parser {
     fn parse_node(&mut self, tok: Token) -> ZResult<Node> {
-        let prev_value = self.prev.value.clone();
         self.prev = tok.clone();
 
         match tok.value {
-            Type::Primitive(_) if matches!(peek!(self).value, Type::Op(_)) => {
-                Ok(Node::BinOp(self.build_binop(tok, None)?))
+            Type::Primitive(_) | Type::Suffixed(..) if continues_expr(&peek!(self).value) => {
+                self.build_expr(tok, None)
+            }
+            Type::Primitive(_) | Type::Suffixed(..) => {
+                Ok(Node::Constant(self.build_constant(tok)?))
             }
-            Type::Primitive(_) => Ok(Node::Constant(self.build_constant(tok)?)),
-            Type::Keyword(kw) => match kw {
-                Keyword::True => todo!(),
-                Keyword::False => todo!(),
-                Keyword::Mut => todo!(),
-                Keyword::Fun => Ok(Node::FunctionDef(self.build_fun()?)),
+            Type::Keyword(ref kw) => match kw {
+                Keyword::True | Keyword::False if continues_expr(&peek!(self).value) => {
+                    self.build_expr(tok, None)
+                }
+                Keyword::True | Keyword::False => Ok(Node::Constant(self.build_constant(tok)?)),
+                Keyword::Mut => Err(tok.into_err("Expected 'var' before 'mut'.")),
+                Keyword::Fun => Ok(Node::FunctionDef(self.build_fun(tok.pos)?)),
                 Keyword::Var => Ok(Node::VariableDef(self.build_var()?)),
-                Keyword::If => Ok(Node::If(self.build_if()?)),
-                Keyword::Else => todo!(),
+                Keyword::If => {
+                    let case = Node::If(self.build_if(tok.pos)?);
+
+                    // `if a { 1 } else { 2 } + 3` uses the if as a value.
+                    let has_else =
+                        matches!(&case, Node::If(If { orelse, .. }) if **orelse != Node::None);
+                    if has_else && continues_expr(&peek!(self).value) {
+                        let next = peek!(self).clone();
+                        self.build_expr(next, Some(ExprPart::Operand(case)))
+                    } else {
+                        Ok(case)
+                    }
+                }
+                Keyword::Else => Err(tok.into_err("Expected an 'if' before 'else'.")),
                 Keyword::Break => Ok(Node::Break(tok.pos)),
                 Keyword::Loop => Ok(Node::Loop(self.build_loop()?)),
                 Keyword::Return => Ok(Node::Return(self.build_return()?)),
+                Keyword::As => Err(tok.into_err("Expected a value before 'as'.")),
+                Keyword::Enum => Ok(Node::EnumDef(self.build_enum(tok.pos)?)),
+                Keyword::Match => Ok(Node::Match(self.build_match(tok.pos)?)),
             },
 
             Type::Word(ref word) => match peek!(self).value {
@@ -92,10 +139,9 @@ impl<'guard> Parser<'guard> {
                     next!(self);
                     let call = Node::Call(self.build_fcall(tok)?);
 
-                    if matches!(peek!(self).value, Type::Op(_)) {
-                        let next = peek!(self);
-                        let binop = self.build_binop(next, Some(ExprPart::Operand(call)))?;
-                        Ok(Node::BinOp(binop))
+                    if continues_expr(&peek!(self).value) {
+                        let next = peek!(self).clone();
+                        self.build_expr(next, Some(ExprPart::Operand(call)))
                     } else {
                         Ok(call)
                     }
@@ -104,14 +150,25 @@ impl<'guard> Parser<'guard> {
                     next!(self);
                     Ok(Node::Assign(self.build_assign(tok)?))
                 }
-                Type::Op(_) if !matches!(prev_value, Type::Op(_)) => {
-                    self.prev.value = Type::Op(Operator::Add);
-                    Ok(Node::BinOp(self.build_binop(tok, None)?))
+                Type::AugAssign(ref op) => {
+                    let op = op.clone();
+                    next!(self);
+                    let Assign { target, value, pos } = self.build_assign(tok)?;
+                    Ok(Node::AugAssign(AugAssign {
+                        target,
+                        op,
+                        value,
+                        pos,
+                    }))
                 }
+                ref next if continues_expr(next) => self.build_expr(tok, None),
                 _ => Ok(Node::Name(Name { id: word.clone() }, tok.pos)),
             },
 
+            Type::LParen => self.build_expr(tok, None),
+            Type::Ampersand | Type::Tilde | Type::Op(Operator::Mult) => self.build_prefixed(tok),
             Type::LBrace => Ok(Node::Scope(self.build_scope()?)),
+            Type::DocComment(_) => self.build_documented(tok),
             Type::LBracket => Ok(Node::List(self.build_list()?)),
 
             Type::Nl => {
@@ -140,7 +197,7 @@ impl<'guard> Parser<'guard> {
         Ok(List { elements })
     }
 
-    fn build_fun(&mut self) -> ZResult<FunctionDef> {
+    fn build_fun(&mut self, pos: SourcePos) -> ZResult<FunctionDef> {
         let mut current = next!(self);
         let name = if let Type::Word(word) = current.value {
             word
@@ -172,15 +229,11 @@ impl<'guard> Parser<'guard> {
             }
 
             current = next!(self);
-
-            if !matches!(current.value, Type::Word(_)) {
-                return Err(current.into_err("bruh"));
-            }
-
-            let annotation = self.parse_node(current)?;
+            let (annotation, pos) = self.parse_type(current)?;
+            let annotation = Node::Name(Name { id: annotation }, pos);
 
             let arg = Arg {
-                name,
+                name: name.clone(),
                 annotation: Box::new(annotation),
             };
             args.push(Node::Arg(arg));
@@ -193,11 +246,13 @@ impl<'guard> Parser<'guard> {
         let mut returns = Node::None;
         if current.value == Type::Arrow {
             current = next!(self);
-            if !matches!(current.value, Type::Word(_)) {
-                return Err(current.into_err("Function return type should be a word."));
+            let (id, pos) = self.parse_type(current)?;
+            returns = Node::Name(Name { id }, pos);
+
+            current = next!(self);
+            if current.value != Type::LBrace {
+                return Err(current.into_err("Expected a code block."));
             }
-            next!(self);
-            returns = self.parse_node(current)?;
         } else if current.value != Type::LBrace {
             return Err(current.into_err("Expected a code block."));
         }
@@ -214,16 +269,59 @@ impl<'guard> Parser<'guard> {
         }
 
         Ok(FunctionDef {
-            name,
+            name: name.clone(),
             args,
             body,
             returns: Box::new(returns),
+            pos,
+            doc: None,
         })
     }
 
+    /// Attaches the `///` comments starting at `tok` to the function or enum
+    /// that follows them.
+    fn build_documented(&mut self, tok: Token) -> ZResult<Node> {
+        let (doc, next) = self.collect_doc(tok.clone())?;
+        let next = match next {
+            Some(next) if matches!(next.value, Type::Keyword(Keyword::Fun | Keyword::Enum)) => next,
+            _ => {
+                return Err(tok.into_err("Doc comments have to come before a function or an enum."))
+            }
+        };
+
+        let mut node = self.parse_node(next)?;
+        match node {
+            Node::FunctionDef(ref mut fun) => fun.doc = Some(doc),
+            Node::EnumDef(ref mut def) => def.doc = Some(doc),
+            _ => unreachable!(),
+        }
+
+        Ok(node)
+    }
+
+    /// Joins consecutive `///` lines and returns them with the token after
+    /// them, if there is one.
+    fn collect_doc(&mut self, first: Token) -> ZResult<(String, Option<Token>)> {
+        let mut lines = vec![];
+        let mut current = first;
+        loop {
+            match current.value {
+                Type::DocComment(line) => lines.push(line),
+                Type::Nl => {}
+                _ => return Ok((lines.join("\n"), Some(current))),
+            }
+
+            current = match self.tokens.next() {
+                Some(token) => token,
+                None => return Ok((lines.join("\n"), None)),
+            };
+        }
+    }
+
     fn build_fcall(&mut self, name: Token) -> ZResult<Call> {
+        let pos = name.pos;
         let func = if let Type::Word(name) = name.value {
-            Name { id: name }
+            Name { id: name.clone() }
         } else {
             panic!()
         };
@@ -244,12 +342,17 @@ impl<'guard> Parser<'guard> {
             current = next!(self);
         }
 
-        Ok(Call { func, args })
+        Ok(Call { func, args, pos })
     }
 
     fn build_constant(&self, mut tok: Token) -> ZResult<Constant> {
+        let mut suffix = None;
         let value: Primitive = match tok.value.borrow_mut() {
             Type::Primitive(val) => std::mem::take(val),
+            Type::Suffixed(val, name) => {
+                suffix = Some(std::mem::take(name));
+                std::mem::take(val)
+            }
             Type::Keyword(val) => match val {
                 Keyword::True => Primitive::Bool(true),
                 Keyword::False => Primitive::Bool(false),
@@ -259,7 +362,11 @@ impl<'guard> Parser<'guard> {
             _ => return Err(tok.into_err("Not yet implemented!")),
         };
 
-        Ok(Constant { value })
+        Ok(Constant {
+            value,
+            suffix,
+            pos: tok.pos,
+        })
     }
 
     fn build_var(&mut self) -> ZResult<VariableDef> {
@@ -280,6 +387,14 @@ impl<'guard> Parser<'guard> {
         };
         current = next!(self);
 
+        let mut annotation = Node::None;
+        if current.value == Type::DoubleDot {
+            current = next!(self);
+            let (id, pos) = self.parse_type(current)?;
+            annotation = Node::Name(Name { id }, pos);
+            current = next!(self);
+        }
+
         let mut assigning = false;
 
         if current.value == Type::Equals {
@@ -300,8 +415,9 @@ impl<'guard> Parser<'guard> {
         };
 
         Ok(VariableDef {
-            name,
+            name: name.clone(),
             mutable,
+            annotation: Box::new(annotation),
             value: Box::new(value),
         })
     }
@@ -319,13 +435,13 @@ impl<'guard> Parser<'guard> {
         };
 
         Ok(Assign {
-            target,
+            target: target.clone(),
             value: Box::new(self.parse_node(current)?),
             pos: name.pos,
         })
     }
 
-    fn build_if(&mut self) -> ZResult<If> {
+    fn build_if(&mut self, pos: SourcePos) -> ZResult<If> {
         let mut current = next!(self);
 
         let test = self.parse_node(current)?;
@@ -338,27 +454,172 @@ impl<'guard> Parser<'guard> {
 
         let run = self.build_scope()?;
 
-        loop {
-            current = peek!(self);
-            if current.value != Type::Nl {
-                break;
-            }
-            next!(self);
+        // `else` can be on a later line, the newlines before it are only
+        // consumed if it's there.
+        let mut ahead = 0;
+        while self
+            .tokens
+            .peek_nth(ahead)
+            .is_some_and(|tok| tok.value == Type::Nl)
+        {
+            ahead += 1;
         }
 
         let mut orelse = Node::None;
-        if let Type::Keyword(kw) = current.value {
-            if kw == Keyword::Else {
+        if self
+            .tokens
+            .peek_nth(ahead)
+            .is_some_and(|tok| tok.value == Type::Keyword(Keyword::Else))
+        {
+            for _ in 0..=ahead {
                 next!(self);
-                current = next!(self);
-                orelse = self.parse_node(current)?;
             }
+            current = next!(self);
+            orelse = self.parse_node(current)?;
         }
 
         Ok(If {
             test: Box::new(test),
             run,
             orelse: Box::new(orelse),
+            pos,
+        })
+    }
+
+    fn build_enum(&mut self, pos: SourcePos) -> ZResult<EnumDef> {
+        let name = next!(self);
+        let Type::Word(ref id) = name.value else {
+            return Err(name.into_err("Expected the enum's name."));
+        };
+        let id = id.clone();
+
+        let open = next!(self);
+        if open.value != Type::LBrace {
+            return Err(open.into_err("Expected '{' after the enum's name."));
+        }
+
+        let mut variants = vec![];
+        let mut current = next!(self);
+        while current.value != Type::RBrace {
+            let mut doc = None;
+            if let Type::DocComment(_) = current.value {
+                let start = current.clone();
+                let (text, next) = self.collect_doc(current)?;
+                current = match next {
+                    Some(next) if matches!(next.value, Type::Word(_)) => next,
+                    _ => return Err(start.into_err("Doc comments have to come before a variant.")),
+                };
+                doc = Some(text);
+            }
+
+            let Type::Word(ref variant) = current.value else {
+                if matches!(current.value, Type::Nl | Type::Comma) {
+                    current = next!(self);
+                    continue;
+                }
+                return Err(current.into_err("Expected a variant."));
+            };
+
+            let mut fields = vec![];
+            if peek!(self).value == Type::LParen {
+                next!(self);
+                loop {
+                    let field = next!(self);
+                    let (id, pos) = self.parse_type(field)?;
+                    fields.push(Node::Name(Name { id }, pos));
+
+                    let separator = next!(self);
+                    match separator.value {
+                        Type::Comma => {}
+                        Type::RParen => break,
+                        _ => return Err(separator.into_err("Expected ',' or ')'.")),
+                    }
+                }
+            }
+
+            variants.push(Variant {
+                name: variant.clone(),
+                fields,
+                pos: current.pos,
+                doc,
+            });
+            current = next!(self);
+        }
+
+        Ok(EnumDef {
+            name: id,
+            variants,
+            pos,
+            doc: None,
+        })
+    }
+
+    fn build_match(&mut self, pos: SourcePos) -> ZResult<Match> {
+        let start = next!(self);
+        let value = self.build_expr(start, None)?;
+
+        let open = next!(self);
+        if open.value != Type::LBrace {
+            return Err(open.into_err("Expected '{' after the matched value."));
+        }
+
+        let mut arms = vec![];
+        let mut current = next!(self);
+        while current.value != Type::RBrace {
+            if matches!(current.value, Type::Nl | Type::Comma) {
+                current = next!(self);
+                continue;
+            }
+
+            let variant = match current.value {
+                Type::Word(ref word) if word == grammar::WILDCARD => None,
+                Type::Word(ref word) => Some(word.clone()),
+                _ => return Err(current.into_err("Expected a variant or '_'.")),
+            };
+
+            let mut bindings = vec![];
+            if variant.is_some() && peek!(self).value == Type::LParen {
+                next!(self);
+                loop {
+                    let binding = next!(self);
+                    match binding.value {
+                        Type::Word(name) => bindings.push(name.clone()),
+                        _ => return Err(binding.into_err("Expected a name to bind.")),
+                    }
+
+                    let separator = next!(self);
+                    match separator.value {
+                        Type::Comma => {}
+                        Type::RParen => break,
+                        _ => return Err(separator.into_err("Expected ',' or ')'.")),
+                    }
+                }
+            }
+
+            let arrow = next!(self);
+            if arrow.value != Type::FatArrow {
+                return Err(arrow.into_err("Expected '=>' after the pattern."));
+            }
+
+            let start = next!(self);
+            let body = match start.value {
+                Type::LBrace => self.build_scope()?.body,
+                _ => vec![self.parse_node(start)?],
+            };
+
+            arms.push(MatchArm {
+                variant,
+                bindings,
+                body,
+                pos: current.pos,
+            });
+            current = next!(self);
+        }
+
+        Ok(Match {
+            value: Box::new(value),
+            arms,
+            pos,
         })
     }
 
@@ -399,7 +660,100 @@ impl<'guard> Parser<'guard> {
         })
     }
 
-    fn build_binop(&mut self, start: Token, extra_expr_part: Option<ExprPart>) -> ZResult<BinOp> {
+    /// Parses a type name, pointers spell out what they point to as in
+    /// `ptr<u8>`.
+    fn parse_type(&mut self, tok: Token) -> ZResult<(Symbol, SourcePos)> {
+        self.parse_nested_type(tok, false)
+    }
+
+    /// Types inside `ptr<...>` and `result<...>` are `nested`, their `>`
+    /// can be the first half of a `>>`.
+    fn parse_nested_type(&mut self, tok: Token, nested: bool) -> ZResult<(Symbol, SourcePos)> {
+        let name = match tok.value {
+            Type::Word(ref word) => word.clone(),
+            Type::LBracket => {
+                let inner = next!(self);
+                let (inner, _) = self.parse_type(inner)?;
+                let close = next!(self);
+                if close.value != Type::RBracket {
+                    return Err(close.into_err("Expected ']' after the array's type."));
+                }
+                return Ok((format!("[{}]", inner).into(), tok.pos));
+            }
+            _ => return Err(tok.into_err("Expected a type.")),
+        };
+
+        if name != grammar::PTR && name != grammar::RESULT {
+            return Ok((name, tok.pos));
+        }
+
+        let open = next!(self);
+        if open.value != Type::Op(Operator::Less) {
+            return Err(open.into_err(&format!("Expected '<' after '{}'.", name)));
+        }
+        let inner = next!(self);
+        let (inner, _) = self.parse_nested_type(inner, true)?;
+        if self.closed_generic {
+            self.closed_generic = false;
+        } else {
+            let close = next!(self);
+            match close.value {
+                Type::Op(Operator::Greater) => {}
+                // `ptr<ptr<u8>>` ends in a shift, which closes both types.
+                Type::Op(Operator::ShiftRight) if nested => self.closed_generic = true,
+                _ => {
+                    return Err(close.into_err(&format!("Expected '>' after the {}'s type.", name)))
+                }
+            }
+        }
+
+        Ok((format!("{}<{}>", name, inner).into(), tok.pos))
+    }
+
+    /// Parses an expression starting with `&` or `*`, `*p = value` writes
+    /// through the pointer.
+    fn build_prefixed(&mut self, tok: Token) -> ZResult<Node> {
+        let target = self.build_expr(tok, None)?;
+        if peek!(self).value != Type::Equals {
+            return Ok(target);
+        }
+
+        let equals = next!(self);
+        let pointer = match target {
+            Node::Unary(Unary {
+                op: UnaryOp::Deref,
+                value,
+                ..
+            }) => value,
+            _ => return Err(equals.into_err("Only dereferenced pointers can be assigned to.")),
+        };
+
+        let current = next!(self);
+        if current.value == Type::Nl {
+            return Err(current.into_err("Expected a value."));
+        }
+
+        Ok(Node::Store(Store {
+            pointer,
+            value: Box::new(self.parse_node(current)?),
+            pos: equals.pos,
+        }))
+    }
+
+    /// Parses the operand of an expression without treating the
+    /// operators after it as part of it.
+    fn parse_operand(&mut self, tok: Token) -> ZResult<Node> {
+        match tok.value {
+            Type::Word(_) if peek!(self).value == Type::LParen => {
+                next!(self);
+                Ok(Node::Call(self.build_fcall(tok)?))
+            }
+            Type::Word(id) => Ok(Node::Name(Name { id: id.clone() }, tok.pos)),
+            _ => Ok(Node::Constant(self.build_constant(tok)?)),
+        }
+    }
+
+    fn build_expr(&mut self, start: Token, extra_expr_part: Option<ExprPart>) -> ZResult<Node> {
         let mut expr_unordered: Vec<ExprPart> = vec![];
         if let Some(node) = extra_expr_part {
             expr_unordered.push(node);
@@ -409,6 +763,26 @@ impl<'guard> Parser<'guard> {
 
         loop {
             let part = match current.value {
+                Type::Op(Operator::Mult) | Type::Ampersand | Type::Tilde
+                    if is_prefix_position(&expr_unordered) =>
+                {
+                    if !expr_unordered.is_empty() {
+                        next!(self);
+                    }
+                    let op = match current.value {
+                        Type::Ampersand => UnaryOp::AddressOf,
+                        Type::Tilde => UnaryOp::BitNot,
+                        _ => UnaryOp::Deref,
+                    };
+                    ExprPart::Prefix(op, current.pos)
+                }
+                Type::Ampersand => {
+                    next!(self);
+                    ExprPart::Operator(Operator::BitAnd)
+                }
+                Type::Tilde => {
+                    return Err(current.into_err("'~' can only be used in front of a value."))
+                }
                 Type::Op(op) => {
                     if !expr_unordered.is_empty() {
                         next!(self);
@@ -416,18 +790,53 @@ impl<'guard> Parser<'guard> {
                     ExprPart::Operator(op)
                 }
 
-                Type::Primitive(_) => {
+                Type::Primitive(_)
+                | Type::Suffixed(..)
+                | Type::Word(_)
+                | Type::Keyword(Keyword::True | Keyword::False) => {
                     if !expr_unordered.is_empty() {
                         next!(self);
                     }
-                    ExprPart::Operand(Node::Constant(self.build_constant(current)?))
+                    ExprPart::Operand(self.parse_operand(current)?)
                 }
 
-                Type::Word(_) => {
+                Type::Keyword(Keyword::If) => {
                     if !expr_unordered.is_empty() {
                         next!(self);
                     }
-                    ExprPart::Operand(self.parse_node(current)?)
+                    ExprPart::Operand(Node::If(self.build_if(current.pos)?))
+                }
+
+                Type::Keyword(Keyword::As) => {
+                    if !expr_unordered.is_empty() {
+                        next!(self);
+                    }
+                    let target = next!(self);
+                    if !matches!(target.value, Type::Word(_)) {
+                        return Err(target.into_err("Expected a type after 'as'."));
+                    }
+                    let (target, _) = self.parse_type(target)?;
+                    ExprPart::Cast(target, current.pos)
+                }
+
+                Type::LBracket => {
+                    if !expr_unordered.is_empty() {
+                        next!(self);
+                    }
+                    let start = next!(self);
+                    let index = self.build_expr(start, None)?;
+                    let end = next!(self);
+                    if end.value != Type::RBracket {
+                        return Err(end.into_err("Expected ']' after the index."));
+                    }
+                    ExprPart::Index(index, current.pos)
+                }
+
+                Type::Question => {
+                    if !expr_unordered.is_empty() {
+                        next!(self);
+                    }
+                    ExprPart::Try(current.pos)
                 }
 
                 Type::LParen => {
@@ -451,7 +860,7 @@ impl<'guard> Parser<'guard> {
                     next!(self);
                     break;
                 }
-                Type::Comma | Type::RBrace | Type::LBrace => break,
+                Type::Comma | Type::RBrace | Type::LBrace | Type::RBracket | Type::Equals => break,
                 ref tok => {
                     println!("{:?}", tok);
                     return Err(current.into_err("Unexpected token in binop."));
@@ -460,7 +869,7 @@ impl<'guard> Parser<'guard> {
 
             expr_unordered.push(part);
             current = match self.tokens.peek() {
-                Some(tok) => tok.clone()?,
+                Some(tok) => tok.clone(),
                 None => break,
             };
         }
@@ -479,27 +888,53 @@ impl<'guard> Parser<'guard> {
                         right: Box::new(right),
                     }))
                 }
+                ExprPart::Cast(target, pos) => {
+                    let value = stack.pop().unwrap();
+                    stack.push(Node::Cast(Cast {
+                        value: Box::new(value),
+                        target,
+                        pos,
+                    }))
+                }
+                ExprPart::Prefix(op, pos) => {
+                    let value = stack.pop().unwrap();
+                    stack.push(Node::Unary(Unary {
+                        op,
+                        value: Box::new(value),
+                        pos,
+                    }))
+                }
+                ExprPart::Index(index, pos) => {
+                    let value = stack.pop().unwrap();
+                    stack.push(Node::Index(Index {
+                        value: Box::new(value),
+                        index: Box::new(index),
+                        pos,
+                    }))
+                }
+                ExprPart::Try(pos) => {
+                    let value = stack.pop().unwrap();
+                    stack.push(Node::Try(Try {
+                        value: Box::new(value),
+                        pos,
+                    }))
+                }
                 ExprPart::Operand(operand) => stack.push(operand),
 
                 other => panic!("Unexpected token {:?}", other),
             }
         }
 
-        Ok(
-            match stack
-                .pop()
-                .expect("There should be at least something on the stack.")
-            {
-                Node::BinOp(binop) => binop,
-                _ => panic!(),
-            },
-        )
+        Ok(stack
+            .pop()
+            .expect("There should be at least something on the stack."))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::CompilerError;
 
     fn wrap_in_main(case: &str) -> String {
         format!("fun main() {{ {} }}", case)
@@ -508,10 +943,12 @@ mod tests {
     macro_rules! fun_def {
         ($name:tt, $args:expr, $body:expr, $returns:expr) => {
             Node::FunctionDef(FunctionDef {
-                name: $name.to_owned(),
+                name: $name.into(),
                 args: $args,
                 body: $body,
                 returns: Box::new($returns),
+                pos: SourcePos::new(1, 1),
+                doc: None,
             })
         };
         ($name:tt, $args:expr, $body:expr) => {
@@ -530,9 +967,11 @@ mod tests {
     }
 
     macro_rules! constant {
-        ($value:expr, i32) => {
+        ($value:expr, $column:expr) => {
             Node::Constant(Constant {
                 value: Primitive::Int($value),
+                suffix: None,
+                pos: SourcePos::new($column, 1),
             })
         };
     }
@@ -548,13 +987,420 @@ mod tests {
                 "main",
                 vec![],
                 vec![binop!(
-                    constant!(3, i32),
+                    constant!(3, 14),
                     Operator::Add,
-                    binop!(constant!(2, i32), Operator::Mult, constant!(4, i32))
+                    binop!(constant!(2, 18), Operator::Mult, constant!(4, 22))
                 ),]
             )],
         };
 
         assert_eq!(expected, ast);
     }
+
+    #[test]
+    fn test_cast() {
+        let test_case = "a * b + c as i64";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let name = |id: &str, column| Node::Name(Name { id: id.into() }, SourcePos::new(column, 1));
+        let expected = Module {
+            body: vec![fun_def!(
+                "main",
+                vec![],
+                vec![binop!(
+                    binop!(name("a", 14), Operator::Mult, name("b", 18)),
+                    Operator::Add,
+                    Node::Cast(Cast {
+                        value: Box::new(name("c", 22)),
+                        target: "i64".into(),
+                        pos: SourcePos::new(24, 1),
+                    })
+                )]
+            )],
+        };
+
+        assert_eq!(expected, ast);
+    }
+
+    #[test]
+    fn test_bool_cast() {
+        let test_case = "false as u8";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let expected = Module {
+            body: vec![fun_def!(
+                "main",
+                vec![],
+                vec![Node::Cast(Cast {
+                    value: Box::new(Node::Constant(Constant {
+                        value: Primitive::Bool(false),
+                        suffix: None,
+                        pos: SourcePos::new(14, 1),
+                    })),
+                    target: "u8".into(),
+                    pos: SourcePos::new(20, 1),
+                })]
+            )],
+        };
+
+        assert_eq!(expected, ast);
+    }
+
+    #[test]
+    fn test_index() {
+        let test_case = "s[i + 1] * 2";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let name = |id: &str, column| Node::Name(Name { id: id.into() }, SourcePos::new(column, 1));
+        let expected = Module {
+            body: vec![fun_def!(
+                "main",
+                vec![],
+                vec![binop!(
+                    Node::Index(Index {
+                        value: Box::new(name("s", 14)),
+                        index: Box::new(binop!(name("i", 16), Operator::Add, constant!(1, 20))),
+                        pos: SourcePos::new(15, 1),
+                    }),
+                    Operator::Mult,
+                    constant!(2, 25)
+                )]
+            )],
+        };
+
+        assert_eq!(expected, ast);
+    }
+
+    #[test]
+    fn test_pointers() {
+        let test_case = "*p = *(q + 1) * 2";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let name = |id: &str, column| Node::Name(Name { id: id.into() }, SourcePos::new(column, 1));
+        let expected = Module {
+            body: vec![fun_def!(
+                "main",
+                vec![],
+                vec![Node::Store(Store {
+                    pointer: Box::new(name("p", 15)),
+                    value: Box::new(binop!(
+                        Node::Unary(Unary {
+                            op: UnaryOp::Deref,
+                            value: Box::new(binop!(name("q", 21), Operator::Add, constant!(1, 25))),
+                            pos: SourcePos::new(19, 1),
+                        }),
+                        Operator::Mult,
+                        constant!(2, 30)
+                    )),
+                    pos: SourcePos::new(17, 1),
+                })]
+            )],
+        };
+
+        assert_eq!(expected, ast);
+    }
+
+    #[test]
+    fn test_deref_cast() {
+        let test_case = "var x = *p as ptr<u8>";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let Node::VariableDef(var) = &main.body[0] else {
+            panic!("Expected a variable.");
+        };
+        let Node::Cast(cast) = var.value.as_ref() else {
+            panic!("Expected a cast.");
+        };
+
+        assert_eq!(cast.target, "ptr<u8>");
+        assert!(matches!(
+            cast.value.as_ref(),
+            Node::Unary(Unary {
+                op: UnaryOp::Deref,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_array_type() {
+        let test_case = "fun main(args: [string]) -> int {}";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(test_case)).unwrap();
+        let expected = Module {
+            body: vec![fun_def!(
+                "main",
+                vec![Node::Arg(Arg {
+                    name: "args".into(),
+                    annotation: Box::new(Node::Name(
+                        Name {
+                            id: "[string]".into()
+                        },
+                        SourcePos::new(16, 1)
+                    )),
+                })],
+                vec![],
+                Node::Name(Name { id: "int".into() }, SourcePos::new(29, 1))
+            )],
+        };
+
+        assert_eq!(ast, expected);
+    }
+
+    #[test]
+    fn test_try() {
+        let test_case = "var x = f()? + 1";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let Node::VariableDef(var) = &main.body[0] else {
+            panic!("Expected a variable.");
+        };
+        let Node::BinOp(binop) = var.value.as_ref() else {
+            panic!("Expected a binary operation.");
+        };
+
+        assert_eq!(binop.op, Operator::Add);
+        assert!(matches!(
+            binop.left.as_ref(),
+            Node::Try(Try { value, pos }) if matches!(value.as_ref(), Node::Call(_))
+                && *pos == SourcePos::new(25, 1)
+        ));
+    }
+
+    #[test]
+    fn test_enum() {
+        let test_case = "enum Shape {\n    Circle(float), Rect(float, float)\n    Empty\n}";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(test_case)).unwrap();
+        let Node::EnumDef(def) = &ast.body[0] else {
+            panic!("Expected an enum.");
+        };
+
+        assert_eq!(def.name, "Shape");
+        let variants = def
+            .variants
+            .iter()
+            .map(|variant| (variant.name.as_str(), variant.fields.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(variants, [("Circle", 1), ("Rect", 2), ("Empty", 0)]);
+    }
+
+    #[test]
+    fn test_match() {
+        let test_case = "match shape {\n Shape.Rect(w, _) => { print(w) }\n _ => x = 1\n }";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let Node::Match(r#match) = &main.body[0] else {
+            panic!("Expected a match.");
+        };
+
+        assert_eq!(r#match.arms.len(), 2);
+        assert_eq!(r#match.arms[0].variant.as_deref(), Some("Shape.Rect"));
+        assert_eq!(r#match.arms[0].bindings, ["w", "_"]);
+        assert_eq!(r#match.arms[0].body.len(), 1);
+        assert_eq!(r#match.arms[1].variant, None);
+        assert!(matches!(r#match.arms[1].body[..], [Node::Assign(_)]));
+    }
+
+    #[test]
+    fn test_if_value() {
+        let test_case = "var x = 1 + if c { 2 } else { 3 }";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let Node::VariableDef(var) = &main.body[0] else {
+            panic!("Expected a variable.");
+        };
+        let Node::BinOp(binop) = var.value.as_ref() else {
+            panic!("Expected a binary operation.");
+        };
+        let Node::If(case) = binop.right.as_ref() else {
+            panic!("Expected an if.");
+        };
+
+        assert_eq!(case.pos, SourcePos::new(26, 1));
+        assert_eq!(case.run.body, [constant!(2, 33)]);
+        assert!(
+            matches!(case.orelse.as_ref(), Node::Scope(scope) if scope.body == [constant!(3, 44)])
+        );
+    }
+
+    #[test]
+    fn test_if_value_left() {
+        let test_case = "var y = if x > 2 { 10 } else { 20 } + 1";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let Node::VariableDef(var) = &main.body[0] else {
+            panic!("Expected a variable.");
+        };
+        let Node::BinOp(binop) = var.value.as_ref() else {
+            panic!("Expected a binary operation.");
+        };
+
+        assert_eq!(binop.op, Operator::Add);
+        assert!(matches!(binop.left.as_ref(), Node::If(case) if case.pos == SourcePos::new(22, 1)));
+        assert_eq!(binop.right.as_ref(), &constant!(1, 52));
+    }
+
+    #[test]
+    fn test_aug_assign() {
+        let test_case = "i *= j + 2";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let Node::AugAssign(assign) = &main.body[0] else {
+            panic!("Expected a compound assignment.");
+        };
+
+        assert_eq!(assign.target, "i");
+        assert_eq!(assign.op, Operator::Mult);
+        assert_eq!(assign.pos, SourcePos::new(14, 1));
+        assert!(matches!(
+            assign.value.as_ref(),
+            Node::BinOp(binop) if binop.op == Operator::Add
+        ));
+    }
+
+    #[test]
+    fn test_bitwise() {
+        let test_case = "var x = a | ~b & c << 1 == d";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let Node::VariableDef(var) = &main.body[0] else {
+            panic!("Expected a variable.");
+        };
+        let name = |id: &str, column| Node::Name(Name { id: id.into() }, SourcePos::new(column, 1));
+
+        let shifted = binop!(name("c", 31), Operator::ShiftLeft, constant!(1, 36));
+        let inverted = Node::Unary(Unary {
+            op: UnaryOp::BitNot,
+            value: Box::new(name("b", 27)),
+            pos: SourcePos::new(26, 1),
+        });
+        let expected = binop!(
+            name("a", 22),
+            Operator::BitOr,
+            binop!(
+                inverted,
+                Operator::BitAnd,
+                binop!(shifted, Operator::DoubleEquals, name("d", 41))
+            )
+        );
+        assert_eq!(*var.value, expected);
+    }
+
+    #[test]
+    fn test_nested_types() {
+        let test_case = "var p: ptr<ptr<u8>> = q >> 2\n var r: result<ptr<u8>> = s";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let [Node::VariableDef(p), Node::VariableDef(r)] = &main.body[..] else {
+            panic!("Expected two variables.");
+        };
+
+        assert!(matches!(
+            p.annotation.as_ref(),
+            Node::Name(Name { id }, _) if id == "ptr<ptr<u8>>"
+        ));
+        assert!(matches!(
+            p.value.as_ref(),
+            Node::BinOp(binop) if binop.op == Operator::ShiftRight
+        ));
+        assert!(matches!(
+            r.annotation.as_ref(),
+            Node::Name(Name { id }, _) if id == "result<ptr<u8>>"
+        ));
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let test_case = "/// Squares.\n///\n/// Twice.\nfun square(x: i32) -> i32 { return x * x }\n/// Colors.\nenum Color {\n /// Like a rose.\n Red\n Blue\n}";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(test_case)).unwrap();
+        let [Node::FunctionDef(fun), Node::EnumDef(def)] = &ast.body[..] else {
+            panic!("Expected a function and an enum.");
+        };
+        assert_eq!(fun.doc.as_deref(), Some("Squares.\n\nTwice."));
+        assert_eq!(def.doc.as_deref(), Some("Colors."));
+        assert_eq!(def.variants[0].doc.as_deref(), Some("Like a rose."));
+        assert_eq!(def.variants[1].doc, None);
+
+        let err = parser
+            .parse(Lexer::from("fun main() {\n /// x\n var x = 1\n}"))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(
+                2,
+                2,
+                1,
+                "Doc comments have to come before a function or an enum."
+            )
+        );
+
+        let err = parser.parse(Lexer::from("/// x")).unwrap_err();
+        assert_eq!(
+            err,
+            CompilerError::new(
+                1,
+                1,
+                1,
+                "Doc comments have to come before a function or an enum."
+            )
+        );
+    }
+
+    #[test]
+    fn test_else_lookahead() {
+        let test_case = "if a { x = 1 }\n\n else { x = 2 }\n if b { x = 3 }\n\n y = 4";
+        let mut parser = Parser::new();
+
+        let ast = parser.parse(Lexer::from(&wrap_in_main(test_case))).unwrap();
+        let Node::FunctionDef(main) = &ast.body[0] else {
+            panic!("Expected a function.");
+        };
+        let [Node::If(first), Node::If(second), Node::Assign(_)] = &main.body[..] else {
+            panic!("Expected two ifs and an assignment.");
+        };
+
+        assert!(matches!(first.orelse.as_ref(), Node::Scope(_)));
+        assert_eq!(second.orelse.as_ref(), &Node::None);
+    }
 }
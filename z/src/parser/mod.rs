@@ -1,8 +1,6 @@
-use std::iter::Peekable;
-
 use crate::{
     error::CompilerError,
-    lexer::{token::Token, Lexer},
+    lexer::token::{Token, Tokens},
 };
 
 pub mod ast;
@@ -11,7 +9,12 @@ mod rpn;
 
 pub type ZResult<T> = Result<T, CompilerError>;
 
-pub struct Parser<'guard> {
-    tokens: Peekable<Lexer<'guard>>,
+pub struct Parser {
+    tokens: Tokens,
     prev: Token,
+    /// What `peek!` sees after the last token.
+    end: Token,
+    /// A `>>` closed two generic types at once, so the outer one is closed
+    /// already.
+    closed_generic: bool,
 }
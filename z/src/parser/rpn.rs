@@ -17,6 +17,8 @@ pub fn shutting_yard(toks: Vec<ExprPart>) -> Result<Vec<ExprPart>, CompilerError
                                 break;
                             }
                         }
+                        // Prefix operators bind tighter than any binary one.
+                        ExprPart::Prefix(..) => {}
                         ExprPart::Lpar => break,
 
                         _ => panic!(),
@@ -27,6 +29,16 @@ pub fn shutting_yard(toks: Vec<ExprPart>) -> Result<Vec<ExprPart>, CompilerError
                 operator_stack.push(ExprPart::Operator(operator));
             }
             ExprPart::Operand(operand) => output.push(ExprPart::Operand(operand)),
+            // Casts, indexing and `?` are postfix and bind tighter than any operator,
+            // except that `*p as T` casts the dereferenced value.
+            part @ ExprPart::Cast(..) => {
+                while let Some(ExprPart::Prefix(..)) = operator_stack.last() {
+                    output.push(operator_stack.pop().unwrap());
+                }
+                output.push(part);
+            }
+            part @ (ExprPart::Index(..) | ExprPart::Try(_)) => output.push(part),
+            part @ ExprPart::Prefix(..) => operator_stack.push(part),
             ExprPart::Lpar => operator_stack.push(ExprPart::Lpar),
             ExprPart::Rpar => {
                 loop {
@@ -54,19 +66,25 @@ pub fn shutting_yard(toks: Vec<ExprPart>) -> Result<Vec<ExprPart>, CompilerError
     Ok(output)
 }
 
+/// Binding strength of the binary operators, the same order as in C.
 fn precedence(op: &Operator) -> u32 {
     match op {
-        Operator::Add => 2,
-        Operator::Sub => 2,
-        Operator::Mult => 3,
-        Operator::Div => 3,
-        Operator::DoubleEquals => 1,
-        Operator::Mod => 3,
-        Operator::Greater => 1,
-        Operator::GreaterEquals => 1,
-        Operator::Less => 1,
-        Operator::LessEquals => 1,
-        Operator::NotEquals => 1,
+        Operator::BitOr => 1,
+        Operator::BitXor => 2,
+        Operator::BitAnd => 3,
+        Operator::DoubleEquals => 4,
+        Operator::NotEquals => 4,
+        Operator::Greater => 5,
+        Operator::GreaterEquals => 5,
+        Operator::Less => 5,
+        Operator::LessEquals => 5,
+        Operator::ShiftLeft => 6,
+        Operator::ShiftRight => 6,
+        Operator::Add => 7,
+        Operator::Sub => 7,
+        Operator::Mult => 8,
+        Operator::Div => 8,
+        Operator::Mod => 8,
     }
 }
 
@@ -79,33 +97,30 @@ mod tests {
     use zasm::types::Operator;
 
     use super::*;
+    use crate::lexer::token::SourcePos;
+
+    fn constant(value: i128) -> ExprPart {
+        ExprPart::Operand(Node::Constant(Constant {
+            value: Primitive::Int(value),
+            suffix: None,
+            pos: SourcePos::new(1, 1),
+        }))
+    }
 
     #[test]
     fn test_rpn() {
         let test_case = vec![
-            ExprPart::Operand(Node::Constant(Constant {
-                value: Primitive::Int(5),
-            })),
+            constant(5),
             ExprPart::Operator(Operator::Add),
-            ExprPart::Operand(Node::Constant(Constant {
-                value: Primitive::Int(3),
-            })),
+            constant(3),
             ExprPart::Operator(Operator::Mult),
-            ExprPart::Operand(Node::Constant(Constant {
-                value: Primitive::Int(4),
-            })),
+            constant(4),
         ];
 
         let expected = vec![
-            ExprPart::Operand(Node::Constant(Constant {
-                value: Primitive::Int(5),
-            })),
-            ExprPart::Operand(Node::Constant(Constant {
-                value: Primitive::Int(3),
-            })),
-            ExprPart::Operand(Node::Constant(Constant {
-                value: Primitive::Int(4),
-            })),
+            constant(5),
+            constant(3),
+            constant(4),
             ExprPart::Operator(Operator::Mult),
             ExprPart::Operator(Operator::Add),
         ];
@@ -0,0 +1,48 @@
+use std::{
+    io,
+    process::{Command, ExitStatus},
+};
+
+pub const ASSEMBLER: &str = "nasm";
+pub const LINKER: &str = "ld";
+
+/// Assembles a NASM source file into an ELF64 object file.
+pub fn assemble(asm_file: &str, object_file: &str) -> io::Result<()> {
+    let status = Command::new(ASSEMBLER)
+        .arg("-felf64")
+        .arg("-g")
+        .arg("-o")
+        .arg(object_file)
+        .arg(asm_file)
+        .status()
+        .map_err(|err| spawn_error(ASSEMBLER, err))?;
+
+    check_status(ASSEMBLER, status)
+}
+
+/// Links an object file into a static executable.
+pub fn link(object_file: &str, exe_file: &str) -> io::Result<()> {
+    let status = Command::new(LINKER)
+        .arg("-o")
+        .arg(exe_file)
+        .arg(object_file)
+        .status()
+        .map_err(|err| spawn_error(LINKER, err))?;
+
+    check_status(LINKER, status)
+}
+
+fn spawn_error(program: &str, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("Failed to run {}: {}", program, err))
+}
+
+fn check_status(program: &str, status: ExitStatus) -> io::Result<()> {
+    if status.success() {
+        return Ok(());
+    }
+
+    Err(io::Error::other(format!(
+        "{} failed with {}.",
+        program, status
+    )))
+}
@@ -1,20 +1,37 @@
-use std::{fs, process::Command, time::Duration};
+use std::{
+    fs,
+    process::{Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
 
-use z::{compiler::Compiler, error::CompilerError, lexer::Lexer, parser::Parser};
+use z::{compiler::Compiler, error::CompilerError, lexer::Lexer, parser::Parser, toolchain};
 
 const EXAMPLES_PATH: &str = "./examples";
-const ASM_FILE: &str = "../build/out.asm";
 const OBJECT_FILE: &str = "../build/out.o";
 const EXE_FILE: &str = "../build/a.out";
+const BUILD_DIR: &str = "../build";
+
+/// How long an example can run before it counts as stuck.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Examples that loop forever on purpose, they only have to start.
+const ENDLESS: &[&str] = &["loop.ž"];
 
 #[test]
 fn test_examples() -> Result<(), CompilerError> {
-    let files = fs::read_dir(EXAMPLES_PATH).expect("Failed to list examples dir.");
+    let mut files = fs::read_dir(EXAMPLES_PATH)
+        .expect("Failed to list examples dir.")
+        .map(|file| file.unwrap().path())
+        .collect::<Vec<_>>();
+    files.sort();
+
     let mut compiler = Compiler::new();
+    fs::create_dir_all(BUILD_DIR).expect("Failed to create build dir.");
 
-    for file in files {
-        let path = file.unwrap().path();
-        let source = fs::read_to_string(path).expect("Failed to read file.");
+    let mut failures = vec![];
+    for path in files {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let source = fs::read_to_string(&path).expect("Failed to read file.");
 
         let lexer = Lexer::from(&source);
         let mut parser = Parser::new();
@@ -23,36 +40,81 @@ fn test_examples() -> Result<(), CompilerError> {
         let module = compiler.compile(ast)?;
 
         module
-            .write_to_file(ASM_FILE)
-            .expect("Failed to write to file.");
-
-        Command::new("nasm")
-            .arg("-felf64")
-            .arg("-g")
-            .arg("-o")
-            .arg(OBJECT_FILE)
-            .arg(ASM_FILE)
-            .spawn()
-            .unwrap()
-            .wait()
-            .expect("Failed to run nasm.");
-
-        Command::new("ld")
-            .arg("-o")
-            .arg(EXE_FILE)
-            .arg(OBJECT_FILE)
-            .spawn()
-            .unwrap()
-            .wait()
-            .expect("Failed to link executable");
+            .write_object(OBJECT_FILE)
+            .expect("Failed to write object file.");
+        toolchain::link(OBJECT_FILE, EXE_FILE).expect("Failed to link executable");
 
         let mut handle = Command::new(EXE_FILE)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
             .spawn()
             .expect("Failed to run compiled executable.");
 
-        std::thread::sleep(Duration::from_millis(10));
-        handle.kill().expect("Failed to kill subprocess.");
+        let endless = ENDLESS.contains(&name.as_str());
+        let timeout = if endless {
+            Duration::from_millis(10)
+        } else {
+            TIMEOUT
+        };
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = handle.try_wait().expect("Failed to wait on subprocess.") {
+                break Some(status);
+            }
+            if start.elapsed() > timeout {
+                handle.kill().expect("Failed to kill subprocess.");
+                handle.wait().expect("Failed to wait on subprocess.");
+                break None;
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+
+        match (status, endless) {
+            (None, true) => {}
+            (Some(status), true) => failures.push(format!("{} exited with {}", name, status)),
+            (None, false) => failures.push(format!("{} didn't exit in {:?}", name, TIMEOUT)),
+            (Some(status), false) if !status.success() => {
+                failures.push(format!("{} exited with {}", name, status))
+            }
+            _ => {}
+        }
     }
 
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
     Ok(())
 }
+
+/// Compiles and runs `source` with its own files, so it can run next to
+/// the other tests.
+fn run(source: &str, name: &str) -> ExitStatus {
+    let object_file = format!("{}/{}.o", BUILD_DIR, name);
+    let exe_file = format!("{}/{}", BUILD_DIR, name);
+    fs::create_dir_all(BUILD_DIR).expect("Failed to create build dir.");
+
+    let ast = Parser::new().parse(Lexer::from(source)).unwrap();
+    let mut compiler = Compiler::new();
+    let module = compiler.compile(ast).unwrap();
+    module
+        .write_object(&object_file)
+        .expect("Failed to write object file.");
+    toolchain::link(&object_file, &exe_file).expect("Failed to link executable");
+
+    Command::new(&exe_file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .expect("Failed to run compiled executable.")
+}
+
+#[test]
+fn test_read_file_errors() {
+    // ENOENT from open, and EISDIR from reading a directory.
+    let source = "fun main() {
+    assert_eq(error(read_file(\"/nonexistent/file\")), 2)
+    assert_eq(error(read_file(\"/\")), 21)
+    assert(is_ok(read_file(\"/proc/self/stat\")))
+}";
+
+    assert!(run(source, "read_file_errors").success());
+}
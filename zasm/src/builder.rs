@@ -1,22 +1,21 @@
-use std::{fs, io};
+use std::{fmt, fs, io};
 
 use crate::{
-    constants::PUTS_SOURCE,
+    constants::{
+        ALLOC_SOURCE, ENUM_SOURCE, PUTS_SOURCE, RESULT_SOURCE, RUNTIME_QWORDS, RUNTIME_STRINGS,
+        STRING_SOURCE,
+    },
+    elf::{ObjectFile, Section, Symbol, SymbolKind},
+    encoder::{self, EncodeError},
     func::Function,
-    types::{Jump, Label, Operator, StrPtr},
+    instr::{Cond, Instruction, Memory, Operand as Arg, Register, Size},
+    parse::{self, ParseError},
+    peephole,
+    printer::{self, Syntax},
+    types::{Jump, Label, Operator, Scalar, StrPtr},
     Builder, Module,
 };
 
-macro_rules! insert_offset {
-    ($offset:expr) => {
-        format!(
-            "rbp{}{}",
-            if $offset < 0 { '-' } else { '+' },
-            $offset.abs().to_string()
-        )
-    };
-}
-
 impl<'guard> Default for Module<'guard> {
     fn default() -> Self {
         Self::new()
@@ -34,9 +33,18 @@ impl<'guard> Module<'guard> {
             globals: vec!["_start"],
             strings: vec![],
             functions: vec![],
+            main_args: false,
+            main_returns: false,
         }
     }
 
+    /// Tells `_start` whether `main` takes the command line arguments and
+    /// whether its return value is the exit code.
+    pub fn set_main(&mut self, takes_args: bool, returns: bool) {
+        self.main_args = takes_args;
+        self.main_returns = returns;
+    }
+
     pub fn add_func(&mut self, func: Function) {
         self.functions.push(func);
     }
@@ -45,349 +53,1062 @@ impl<'guard> Module<'guard> {
         self.globals.push(global);
     }
 
-    pub fn add_string(&mut self, string: &str) -> StrPtr {
+    /// Strings are bytes, they don't have to be valid UTF-8.
+    pub fn add_string(&mut self, string: &[u8]) -> StrPtr {
         let pos = self.strings.iter().position(|x| x == string);
 
         if let Some(i) = pos {
             return StrPtr::new(i);
         }
 
-        self.strings.push(string.to_owned());
+        self.strings.push(string.to_vec());
         StrPtr::new(self.strings.len() - 1)
     }
 
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    pub fn functions_mut(&mut self) -> &mut [Function] {
+        &mut self.functions
+    }
+
+    /// Runs the peephole optimizer over every function at the given level.
+    pub fn optimize(&mut self, level: u8) {
+        for func in &mut self.functions {
+            peephole::optimize(func.body_mut(), level);
+        }
+    }
+
     pub fn write_to_file(&self, file_name: &str) -> Result<(), io::Error> {
-        // globals
-        let mut out = format!("global {}\n", self.globals.join(", "));
+        fs::write(file_name, self.to_string())
+    }
+
+    /// Encodes the module into a relocatable ELF64 object file
+    /// without going through an external assembler.
+    pub fn to_object(&self) -> Result<Vec<u8>, EncodeError> {
+        let assembled = encoder::assemble(&self.text_section())?;
+        let mut object = ObjectFile::new();
+
+        for (name, value) in assembled.labels {
+            let kind = if name.contains('.') {
+                SymbolKind::NoType
+            } else {
+                SymbolKind::Func
+            };
+            object.symbols.push(Symbol {
+                global: self.globals.contains(&name.as_str()),
+                name,
+                section: Section::Text,
+                value,
+                kind,
+            });
+        }
+
+        for (name, string) in self.data() {
+            object
+                .data
+                .extend_from_slice(&(string.len() as u64).to_le_bytes());
+            object.symbols.push(Symbol {
+                name,
+                section: Section::Data,
+                value: object.data.len() as u64,
+                kind: SymbolKind::Object,
+                global: false,
+            });
+            object.data.extend(nul_terminated(string));
+        }
+
+        for name in RUNTIME_QWORDS {
+            object.symbols.push(Symbol {
+                name: name.to_owned(),
+                section: Section::Bss,
+                value: object.bss_size,
+                kind: SymbolKind::Object,
+                global: false,
+            });
+            object.bss_size += 8;
+        }
+
+        object.text = assembled.code;
+        object.relocations = assembled.relocations;
+        Ok(object.to_bytes())
+    }
 
-        // section .text
-        out.push_str("section .text\n");
-        out.push_str("_start:\n");
-        out.push_str("    call main\n");
+    pub fn write_object(&self, file_name: &str) -> Result<(), io::Error> {
+        let bytes = self
+            .to_object()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(file_name, bytes)
+    }
 
-        out.push_str("    ; -- exit --\n");
-        out.push_str("    mov rax, 60\n");
-        out.push_str("    xor rdi, rdi\n");
-        out.push_str("    syscall\n");
+    fn text_section(&self) -> Vec<Instruction> {
+        // The kernel leaves argc on top of the stack with argv after it,
+        // envp follows argv's terminating null.
+        let rax = Arg::Reg(Register::RAX);
+        let rcx = Arg::Reg(Register::RCX);
+        let envp = Memory {
+            index: Some(Register::RAX),
+            scale: 8,
+            ..Memory::new(Register::RSP, 16)
+        };
+        let mut out = vec![
+            Instruction::Label("_start".to_owned()),
+            Instruction::Mov(rax.clone(), Arg::Mem(Memory::new(Register::RSP, 0))),
+            Instruction::Mov(Arg::Mem(Memory::label("argc")), rax.clone()),
+            Instruction::Lea(rcx.clone(), Arg::Mem(envp)),
+            Instruction::Mov(Arg::Mem(Memory::label("envp")), rcx.clone()),
+            Instruction::Lea(rax.clone(), Arg::Mem(Memory::new(Register::RSP, 8))),
+            Instruction::Mov(Arg::Mem(Memory::label("argv")), rax.clone()),
+        ];
+
+        if self.main_args {
+            out.push(Instruction::Call(Arg::label("args")));
+            out.push(Instruction::Push(rax.clone()));
+        }
+        out.push(Instruction::Call(Arg::label("main")));
+
+        out.push(Instruction::Comment("-- exit --".to_owned()));
+        out.push(if self.main_returns {
+            Instruction::Mov(
+                Arg::Reg(Register::RDI.resized(Size::Dword)),
+                Arg::Reg(Register::EAX),
+            )
+        } else {
+            Instruction::Xor(Arg::Reg(Register::RDI), Arg::Reg(Register::RDI))
+        });
+        out.push(Instruction::Mov(rax, Arg::Imm(60)));
+        out.push(Instruction::Syscall);
 
         for func in &self.functions {
-            out.push_str(&func.to_string());
+            out.extend(func.instructions());
         }
 
-        out.push_str(PUTS_SOURCE);
-
-        // section .data
-        out.push_str("section .data\n");
-        for (i, string) in self.strings.iter().enumerate() {
-            let strn = format!(
-                "str_{}: db \"{}\",0\n",
-                i,
-                string.replace('\n', "\", 0xA, \"")
-            );
-            out.push_str(&strn);
+        let runtime = [
+            PUTS_SOURCE,
+            STRING_SOURCE,
+            ALLOC_SOURCE,
+            RESULT_SOURCE,
+            ENUM_SOURCE,
+        ];
+        for source in runtime {
+            out.extend(parse::parse(source).expect("The runtime source is valid."));
         }
+        out
+    }
 
-        // section .bss
-        out.push_str("section .bss\n");
+    /// Every string in the data section with its label, the module's
+    /// own strings first and then the runtime's.
+    fn data(&self) -> Vec<(String, &[u8])> {
+        let strings = self
+            .strings
+            .iter()
+            .enumerate()
+            .map(|(i, string)| (StrPtr::new(i).to_string(), string.as_slice()));
+        let runtime = RUNTIME_STRINGS
+            .iter()
+            .map(|(name, string)| (name.to_string(), string.as_bytes()));
+
+        strings.chain(runtime).collect()
+    }
 
-        fs::write(file_name, out)
+    /// Prints the whole module as assembly in the given syntax.
+    pub fn to_asm(&self, syntax: Syntax) -> String {
+        let mut out = match syntax {
+            Syntax::Nasm => format!("global {}\nsection .text\n", self.globals.join(", ")),
+            Syntax::Gas => format!(".globl {}\n.text\n", self.globals.join(", ")),
+        };
+
+        for instr in self.text_section() {
+            out.push_str(&printer::print(&instr, syntax));
+            out.push('\n');
+        }
+
+        match syntax {
+            Syntax::Nasm => {
+                out.push_str("section .data\n");
+                for (name, string) in self.data() {
+                    let bytes = printer::print_bytes(&nul_terminated(string), syntax);
+                    out.push_str(&format!("dq {}\n{}: {}\n", string.len(), name, bytes));
+                }
+                out.push_str("section .bss\nalignb 8\n");
+                for name in RUNTIME_QWORDS {
+                    out.push_str(&format!("{}: resq 1\n", name));
+                }
+            }
+            Syntax::Gas => {
+                out.push_str(".data\n");
+                for (name, string) in self.data() {
+                    let bytes = printer::print_bytes(&nul_terminated(string), syntax);
+                    out.push_str(&format!(".quad {}\n{}: {}\n", string.len(), name, bytes));
+                }
+                out.push_str(".bss\n.balign 8\n");
+                for name in RUNTIME_QWORDS {
+                    out.push_str(&format!("{}: .zero 8\n", name));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// `movd` or `movq`, for a float of `size`.
+fn move_xmm(dst: Arg, src: Arg, size: Size) -> Instruction {
+    match size {
+        Size::Qword => Instruction::Movq(dst, src),
+        _ => Instruction::Movd(dst, src),
+    }
+}
+
+fn compare_xmm(x: Arg, y: Arg, size: Size) -> Instruction {
+    match size {
+        Size::Qword => Instruction::Ucomisd(x, y),
+        _ => Instruction::Ucomiss(x, y),
+    }
+}
+
+/// The condition a comparison sets, see `Jump::comparison`.
+fn comparison_cond(operation: &Operator, signed: bool) -> Cond {
+    Jump::comparison(operation, signed)
+        .and_then(Jump::cond)
+        .expect("Only comparisons set a condition.")
+}
+
+fn nul_terminated(string: &[u8]) -> Vec<u8> {
+    string.iter().copied().chain([0]).collect()
+}
+
+impl<'guard> fmt::Display for Module<'guard> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_asm(Syntax::Nasm))
     }
 }
 
 #[derive(Debug)]
 pub enum Operand {
     Reg(Reg),
-    Int(i32),
+    Int(i64),
     StrPtr(StrPtr),
     Var(Variable),
 }
 
-#[derive(Debug)]
-pub struct Variable(i32);
+#[derive(Debug, Clone)]
+pub struct Variable {
+    offset: i32,
+    scalar: Scalar,
+}
 
 impl Variable {
-    pub fn new(offset: i32) -> Self {
-        Self(offset)
+    pub fn new(offset: i32, scalar: Scalar) -> Self {
+        Self { offset, scalar }
     }
 
-    pub fn get_mem_location(&self) -> String {
-        let offset_display = insert_offset!(self.0);
-        format!("[{}]", offset_display)
+    pub fn scalar(&self) -> Scalar {
+        self.scalar
     }
-}
 
-#[derive(Debug)]
-pub struct Reg(String);
+    pub fn memory(&self) -> Memory {
+        Memory::new(Register::RBP, self.offset as i64).sized(self.scalar.size)
+    }
 
-impl Clone for Variable {
-    fn clone(&self) -> Self {
-        Self(self.0)
+    pub fn get_mem_location(&self) -> String {
+        self.memory().to_string()
     }
 }
 
+#[derive(Debug)]
+pub struct Reg(Register);
+
 impl Reg {
     pub fn new(name: &str) -> Self {
-        Self(name.to_owned())
-    }
-    pub fn to_x64(&self) -> String {
-        let mut out = String::new();
-        if self.0.starts_with('e') {
-            if let Some(first_char) = self.0.chars().next() {
-                // Create a new string with the first character replaced
-                out.push('r');
-                out.push_str(&self.0[first_char.len_utf8()..]);
-            }
-        } else {
-            out = self.0.clone();
-            out.pop();
-        }
+        Self(Register::from_name(name).expect("Unknown register name."))
+    }
 
-        out
+    pub fn register(&self) -> Register {
+        self.0
+    }
+
+    /// The same register accessed with a different width.
+    pub fn resized(self, size: Size) -> Self {
+        Self(self.0.resized(size))
+    }
+
+    pub fn to_x64(&self) -> Register {
+        self.0.resized(Size::Qword)
     }
 }
 
+/// The registers values are kept in, `eax` is handed out first.
+const REGISTERS: [&str; 14] = [
+    "r15d", "r14d", "r13d", "r12d", "r11d", "r10d", "r9d", "r8d", "edi", "esi", "ebx", "edx",
+    "ecx", "eax",
+];
+
 impl Builder {
     pub fn new() -> Self {
         Self {
-            buffer: String::new(),
-            registers: vec![
-                Reg::new("r15d"),
-                Reg::new("r14d"),
-                Reg::new("r13d"),
-                Reg::new("r12d"),
-                Reg::new("r11d"),
-                Reg::new("r10d"),
-                Reg::new("r9d"),
-                Reg::new("r8d"),
-                Reg::new("edi"),
-                Reg::new("esi"),
-                Reg::new("ebx"),
-                Reg::new("edx"),
-                Reg::new("ecx"),
-                Reg::new("eax"),
-            ],
+            buffer: vec![],
+            registers: REGISTERS.iter().map(|name| Reg::new(name)).collect(),
             offset: 0,
             reserved: 0,
             label_count: 0,
         }
     }
 
-    fn get_value(&mut self, value: Operand) -> String {
+    fn alloc_reg(&mut self, size: Size) -> Reg {
+        self.registers.pop().unwrap().resized(size)
+    }
+
+    /// Turns the value into an instruction operand of the given size.
+    fn get_value(&mut self, value: Operand, size: Size) -> Arg {
         match value {
             Operand::Reg(reg) => {
-                let out = reg.0.clone();
+                let out = Arg::Reg(reg.0.resized(size));
                 self.free_reg(reg);
                 out
             }
-            Operand::Int(i) => i.to_string(),
-            Operand::Var(var) => {
-                let reg = self.get_var(&var);
-                let out = reg.0.clone();
+            // Only 32-bit immediates get sign extended to 64 bits.
+            Operand::Int(i) if size == Size::Qword && i32::try_from(i).is_err() => {
+                let reg = self.alloc_reg(size);
+                self.emit(Instruction::Mov(Arg::Reg(reg.0), Arg::Imm(i)));
 
+                let out = Arg::Reg(reg.0);
                 self.free_reg(reg);
                 out
             }
-            Operand::StrPtr(str) => str.to_string(),
-        }
-    }
-
-    pub fn build_push(&mut self, value: Operand) {
-        let value = match value {
-            Operand::Reg(reg) => reg.to_x64(),
+            Operand::Int(i) => Arg::Imm(i),
             Operand::Var(var) => {
                 let reg = self.get_var(&var);
-                let out = reg.to_x64();
+                let out = Arg::Reg(reg.0.resized(size));
 
                 self.free_reg(reg);
                 out
             }
-            any => self.get_value(any),
-        };
-        let mut out = String::from("push ");
-        out.push_str(&value);
-        self.buffer.push_str(&self.format(&out));
+            Operand::StrPtr(str) => Arg::Sym(str.to_string(), 0),
+        }
+    }
+
+    pub fn emit(&mut self, instr: Instruction) {
+        self.buffer.push(instr);
+    }
+
+    pub fn build_push(&mut self, value: Operand) {
+        let value = self.get_value(value, Size::Qword);
+        self.emit(Instruction::Push(value));
     }
 
     pub fn assign_var(&mut self, value: Operand, var: &Variable) {
-        let value = self.get_value(value);
-        let out = format!("mov dword [rbp{offset}], {value}", offset = var.0);
-        self.buffer.push_str(&self.format(&out));
+        let value = self.get_value(value, var.scalar.size);
+        self.emit(Instruction::Mov(Arg::Mem(var.memory()), value));
     }
 
-    pub fn store_to_reg(&mut self, value: Operand, reg: Option<Reg>) -> Reg {
+    /// Updates a variable where it lives, `x += 1` becomes
+    /// `add dword [rbp-4], 1`.
+    pub fn build_in_place(&mut self, var: &Variable, value: Operand, operation: Operator) {
+        let value = self.get_value(value, var.scalar.size);
+        let target = Arg::Mem(var.memory());
+        self.emit(match operation {
+            Operator::Add => Instruction::Add(target, value),
+            Operator::Sub => Instruction::Sub(target, value),
+            Operator::BitAnd => Instruction::And(target, value),
+            Operator::BitOr => Instruction::Or(target, value),
+            Operator::BitXor => Instruction::Xor(target, value),
+            operation => unreachable!("{:?} can't be done in place.", operation),
+        });
+    }
+
+    pub fn store_to_reg(&mut self, value: Operand, reg: Option<Reg>, size: Size) -> Reg {
         let reg = match reg {
-            Some(reg) => reg,
-            None => self.registers.pop().unwrap(),
+            Some(reg) => reg.resized(size),
+            None => self.alloc_reg(size),
         };
-        let out = format!("mov {}, {}", reg.0, self.get_value(value));
-        self.buffer.push_str(&self.format(&out));
+        let value = self.get_value(value, size);
+        self.emit(Instruction::Mov(Arg::Reg(reg.0), value));
 
         reg
     }
 
-    pub fn build_return(&mut self, value: Operand, return_label: &Label) {
-        self.store_to_reg(value, Some(Reg::new("eax")));
+    pub fn build_return(&mut self, value: Operand, return_label: &Label, scalar: Scalar) {
+        self.store_to_reg(value, Some(Reg::new("eax")), scalar.reg_size());
+        self.build_jump(return_label, Jump::Always);
+    }
 
-        let out = format!("jmp {}", return_label.to_string());
-        self.buffer.push_str(&self.format(&out))
+    /// Unwraps an ok result or returns the error from the function, the
+    /// caller returns a result too.
+    pub fn build_try(&mut self, result: Operand, return_label: &Label, scalar: Scalar) -> Reg {
+        let reg = self.store_to_reg(result, None, Size::Qword);
+        let tag = Memory::new(reg.0, 0).sized(Size::Qword);
+        self.emit(Instruction::Cmp(Arg::Mem(tag), Arg::Imm(0)));
+
+        let ok = self.get_label();
+        self.emit(Instruction::Jcc(Cond::E, Arg::label(&ok.to_string())));
+        self.emit(Instruction::Mov(Arg::Reg(Register::RAX), Arg::Reg(reg.0)));
+        self.build_jump(return_label, Jump::Always);
+
+        self.insert_label(&ok);
+        self.load(&reg, Memory::new(reg.0, 8), scalar);
+        reg.resized(scalar.reg_size())
     }
 
-    pub fn make_var(&mut self, value: Operand) -> Variable {
-        self.offset -= 4;
-        self.reserved += 1;
-        let size = match value {
-            Operand::Int(_) => "dword ",
-            Operand::StrPtr(_) => "dword ",
-            _ => "",
-        };
+    /// Jumps to `skip` unless the enum in `value` is the variant `tag`.
+    pub fn build_tag_check(&mut self, value: &Variable, tag: i64, skip: &Label) {
+        let reg = self.get_var(value);
+        let tag_memory = Memory::new(reg.0, 0).sized(Size::Qword);
+        self.emit(Instruction::Cmp(Arg::Mem(tag_memory), Arg::Imm(tag)));
+        self.free_reg(reg);
+        self.build_jump(skip, Jump::NotEqual);
+    }
+
+    /// Loads the payload field at `index` of the enum in `value`.
+    pub fn build_field(&mut self, value: &Variable, index: usize, scalar: Scalar) -> Reg {
+        let reg = self.get_var(value);
+        let field = Memory::new(reg.0, 8 + 8 * index as i64);
+        self.load(&reg, field, scalar);
+        reg.resized(scalar.reg_size())
+    }
 
-        let value = self.get_value(value);
+    pub fn make_var(&mut self, value: Operand, scalar: Scalar) -> Variable {
+        let bytes = scalar.size.bytes() as i32;
+        self.offset = (self.offset - bytes).div_euclid(bytes) * bytes;
+        self.reserved = self.reserved.max(-self.offset as u32);
 
-        let out = format!(
-            "mov {size}[{offset}], {value}",
-            offset = insert_offset!(self.offset)
-        );
-        self.buffer.push_str(&self.format(&out));
-        Variable::new(self.offset)
+        let var = Variable::new(self.offset, scalar);
+        self.assign_var(value, &var);
+        var
     }
 
     fn get_var(&mut self, var: &Variable) -> Reg {
-        let reg = self.registers.pop().unwrap();
+        let reg = self.alloc_reg(var.scalar.reg_size());
+        self.load(&reg, var.memory(), var.scalar);
+        reg
+    }
 
-        let out = format!("mov {}, {}", reg.0, var.get_mem_location());
-        self.buffer.push_str(&self.format(&out));
+    /// Loads a `scalar` from memory, extending values narrower than 32 bits.
+    fn load(&mut self, reg: &Reg, memory: Memory, scalar: Scalar) {
+        let target = Arg::Reg(reg.0.resized(scalar.reg_size()));
+        let source = Arg::Mem(memory.sized(scalar.size));
+
+        self.emit(match scalar {
+            Scalar {
+                size: Size::Byte | Size::Word,
+                signed: true,
+                ..
+            } => Instruction::Movsx(target, source),
+            Scalar {
+                size: Size::Byte | Size::Word,
+                signed: false,
+                ..
+            } => Instruction::Movzx(target, source),
+            _ => Instruction::Mov(target, source),
+        });
+    }
 
+    /// Loads the address of a variable.
+    pub fn build_address(&mut self, var: &Variable) -> Reg {
+        let reg = self.alloc_reg(Size::Qword);
+        let address = Memory::new(Register::RBP, var.offset as i64);
+        self.emit(Instruction::Lea(Arg::Reg(reg.0), Arg::Mem(address)));
         reg
     }
 
-    pub fn build_op(&mut self, x: Operand, y: Operand, operation: Operator) -> Reg {
-        let reg = match x {
-            Operand::Reg(reg) => reg,
-            Operand::Int(i) => {
-                let reg = self.registers.pop().unwrap();
+    /// Loads the `scalar` a pointer points to.
+    pub fn build_deref(&mut self, pointer: Operand, scalar: Scalar) -> Reg {
+        let reg = self.store_to_reg(pointer, None, Size::Qword);
+        self.load(&reg, Memory::new(reg.0, 0), scalar);
+        reg.resized(scalar.reg_size())
+    }
+
+    /// Stores a value to where a pointer points to.
+    pub fn build_store(&mut self, pointer: Operand, value: Operand, scalar: Scalar) {
+        let reg = self.store_to_reg(pointer, None, Size::Qword);
+        let value = self.get_value(value, scalar.size);
+        let target = Memory::new(reg.0, 0).sized(scalar.size);
+        self.emit(Instruction::Mov(Arg::Mem(target), value));
+        self.free_reg(reg);
+    }
+
+    /// Moves a pointer by `index` elements of `stride` bytes, the index
+    /// is a 64-bit integer.
+    pub fn build_offset(
+        &mut self,
+        pointer: Operand,
+        index: Operand,
+        stride: Size,
+        operation: Operator,
+    ) -> Reg {
+        let reg = self.store_to_reg(pointer, None, Size::Qword);
+        let index = self.store_to_reg(index, None, Size::Qword);
+
+        let mut address = Memory::new(reg.0, 0);
+        address.index = Some(index.0);
+        if operation == Operator::Sub {
+            let bytes = -(stride.bytes() as i64);
+            self.emit(Instruction::Imul3(
+                Arg::Reg(index.0),
+                Arg::Reg(index.0),
+                bytes,
+            ));
+        } else {
+            address.scale = stride.bytes() as u8;
+        }
+
+        self.emit(Instruction::Lea(Arg::Reg(reg.0), Arg::Mem(address)));
+        self.free_reg(index);
+        reg
+    }
+
+    /// Brings a register holding a `scalar` back into its range after an
+    /// operation that may have overflowed the narrow type.
+    fn truncate(&mut self, reg: &Reg, scalar: Scalar) {
+        if scalar.size.bytes() >= 4 {
+            return;
+        }
+
+        let target = Arg::Reg(reg.0.resized(Size::Dword));
+        let source = Arg::Reg(reg.0.resized(scalar.size));
+        self.emit(if scalar.signed {
+            Instruction::Movsx(target, source)
+        } else {
+            Instruction::Movzx(target, source)
+        });
+    }
 
-                let out = format!("mov {}, {}", reg.0, i);
-                self.buffer.push_str(&self.format(&out));
+    /// Puts the left side of an operation in a register it can write to.
+    fn operand_reg(&mut self, x: Operand, size: Size) -> Reg {
+        match x {
+            Operand::Reg(reg) => reg.resized(size),
+            Operand::Var(var) => self.get_var(&var).resized(size),
+            value => {
+                let reg = self.alloc_reg(size);
+                let value = self.get_value(value, size);
+                self.emit(Instruction::Mov(Arg::Reg(reg.0), value));
 
                 reg
             }
-            Operand::Var(var) => self.get_var(&var),
-            Operand::StrPtr(str) => {
-                let reg = self.registers.pop().unwrap();
+        }
+    }
 
-                let out = format!("mov {}, {}", reg.0, str.to_string());
-                self.buffer.push_str(&self.format(&out));
+    pub fn build_op(&mut self, x: Operand, y: Operand, operation: Operator, scalar: Scalar) -> Reg {
+        let size = scalar.reg_size();
+        let reg = self.operand_reg(x, size);
 
-                reg
+        let source = self.get_value(y, size);
+        let target = Arg::Reg(reg.0);
+
+        if scalar.float {
+            return self.build_float_op(reg, source, operation);
+        }
+
+        let cond = match (operation, scalar.signed) {
+            (Operator::Add, _) => {
+                self.emit(Instruction::Add(target, source));
+                self.truncate(&reg, scalar);
+                return reg;
+            }
+            (Operator::Sub, _) => {
+                self.emit(Instruction::Sub(target, source));
+                self.truncate(&reg, scalar);
+                return reg;
+            }
+            (Operator::Mult, _) => {
+                self.emit(match source {
+                    Arg::Imm(imm) => Instruction::Imul3(target.clone(), target, imm),
+                    source => Instruction::Imul(target, source),
+                });
+                self.truncate(&reg, scalar);
+                return reg;
+            }
+            (operation @ (Operator::Div | Operator::Mod), _) => {
+                self.build_div(&reg, source, operation == Operator::Mod, scalar);
+                return reg;
+            }
+            // Bitwise operations keep values sign or zero extended.
+            (Operator::BitAnd, _) => {
+                self.emit(Instruction::And(target, source));
+                return reg;
+            }
+            (Operator::BitOr, _) => {
+                self.emit(Instruction::Or(target, source));
+                return reg;
+            }
+            (Operator::BitXor, _) => {
+                self.emit(Instruction::Xor(target, source));
+                return reg;
+            }
+            (operation @ (Operator::ShiftLeft | Operator::ShiftRight), _) => {
+                self.build_shift(&reg, source, operation, scalar);
+                return reg;
             }
+            (operation, signed) => comparison_cond(&operation, signed),
         };
 
-        let source = self.get_value(y);
-
-        let mut compare = false;
-        let opcode = match operation {
-            Operator::Add => "add",
-            Operator::Sub => "sub",
-            Operator::Mult => "imul",
-            Operator::Div => todo!(),
-            Operator::DoubleEquals
-            | Operator::Greater
-            | Operator::GreaterEquals
-            | Operator::NotEquals
-            | Operator::Less
-            | Operator::LessEquals => {
-                compare = true;
-                "cmp"
+        let reg = reg.resized(Size::Dword);
+        let low = Arg::Reg(reg.0.resized(Size::Byte));
+        self.emit(Instruction::Cmp(target, source));
+        self.emit(Instruction::Set(cond, low.clone()));
+        self.emit(Instruction::Movzx(Arg::Reg(reg.0), low));
+
+        reg
+    }
+
+    /// Divides `reg` by `divisor`, keeping the quotient or the remainder.
+    /// Division always goes through rdx:rax, so they're saved around it
+    /// unless they hold `reg`.
+    fn build_div(&mut self, reg: &Reg, divisor: Arg, remainder: bool, scalar: Scalar) {
+        let size = reg.0.size();
+        let saved = self
+            .live_registers()
+            .into_iter()
+            .filter(|live| {
+                [Register::RAX, Register::RDX].contains(live) && live.num() != reg.0.num()
+            })
+            .collect::<Vec<_>>();
+        self.push_registers(&saved);
+
+        // The divisor waits on the stack while rax and rdx are overwritten.
+        self.emit(Instruction::Push(match divisor {
+            Arg::Reg(divisor) => Arg::Reg(divisor.resized(Size::Qword)),
+            divisor => divisor,
+        }));
+        let rax = Register::RAX.resized(size);
+        let rdx = Register::RDX.resized(size);
+        self.emit(Instruction::Mov(Arg::Reg(rax), Arg::Reg(reg.0)));
+
+        let divisor = Arg::Mem(Memory::new(Register::RSP, 0).sized(size));
+        self.emit(match (scalar.signed, size) {
+            (true, Size::Qword) => Instruction::Cqo,
+            (true, _) => Instruction::Cdq,
+            (false, _) => {
+                let edx = Arg::Reg(Register::RDX.resized(Size::Dword));
+                Instruction::Xor(edx.clone(), edx)
             }
-            Operator::Mod => todo!(),
+        });
+        self.emit(if scalar.signed {
+            Instruction::Idiv(divisor)
+        } else {
+            Instruction::Div(divisor)
+        });
+
+        let result = if remainder { rdx } else { rax };
+        self.emit(Instruction::Mov(Arg::Reg(reg.0), Arg::Reg(result)));
+        self.emit(Instruction::Add(Arg::Reg(Register::RSP), Arg::Imm(8)));
+        self.restore_registers(saved);
+        self.truncate(reg, scalar);
+    }
+
+    /// Shifts `reg` by `count`. A count that isn't a constant has to be in
+    /// cl, so rcx is saved around the shift unless it holds `reg`.
+    fn build_shift(&mut self, reg: &Reg, count: Arg, operation: Operator, scalar: Scalar) {
+        let size = reg.0.size();
+        let shift = match (operation, scalar.signed) {
+            (Operator::ShiftLeft, _) => Instruction::Shl,
+            (_, true) => Instruction::Sar,
+            (_, false) => Instruction::Shr,
         };
 
-        let out = format!("{opcode} {register}, {source}", register = reg.0);
-        self.buffer.push_str(&self.format(&out));
-
-        if compare {
-            let opcode = match operation {
-                Operator::DoubleEquals => "sete",
-                Operator::Greater => "setg",
-                Operator::GreaterEquals => "setge",
-                Operator::Less => "setl",
-                Operator::LessEquals => "setle",
-                Operator::NotEquals => "setne",
-                _ => panic!(),
-            };
+        // The CPU only looks at the low bits of the count.
+        if let Arg::Imm(count) = count {
+            let mask = size.bytes() as i64 * 8 - 1;
+            self.emit(shift(Arg::Reg(reg.0), Arg::Imm(count & mask)));
+            self.truncate(reg, scalar);
+            return;
+        }
+
+        let saved = self
+            .live_registers()
+            .into_iter()
+            .filter(|live| live.num() == Register::RCX.num() && live.num() != reg.0.num())
+            .collect::<Vec<_>>();
+        self.push_registers(&saved);
+
+        // The value waits on the stack while the count is moved into cl.
+        self.emit(Instruction::Push(Arg::Reg(reg.0.resized(Size::Qword))));
+        self.emit(Instruction::Mov(
+            Arg::Reg(Register::RCX.resized(size)),
+            count,
+        ));
+        let value = Arg::Mem(Memory::new(Register::RSP, 0).sized(size));
+        self.emit(shift(
+            value.clone(),
+            Arg::Reg(Register::RCX.resized(Size::Byte)),
+        ));
+        self.emit(Instruction::Mov(Arg::Reg(reg.0), value));
+        self.emit(Instruction::Add(Arg::Reg(Register::RSP), Arg::Imm(8)));
+        self.restore_registers(saved);
+        self.truncate(reg, scalar);
+    }
+
+    /// Flips every bit of an integer.
+    pub fn build_not(&mut self, value: Operand, scalar: Scalar) -> Reg {
+        let reg = self.operand_reg(value, scalar.reg_size());
+        self.emit(Instruction::Not(Arg::Reg(reg.0)));
+        self.truncate(&reg, scalar);
+        reg
+    }
+
+    /// Strings are compared and concatenated by the runtime.
+    pub fn build_str_op(&mut self, x: Operand, y: Operand, operation: Operator) -> Reg {
+        let (name, size) = match operation {
+            Operator::Add => ("str_concat", Size::Qword),
+            Operator::DoubleEquals | Operator::NotEquals => ("str_eq", Size::Dword),
+            operation => unreachable!("Strings don't support {:?}.", operation),
+        };
+
+        // The operands are consumed by the call, so they don't need saving.
+        let args = [&x, &y].map(|arg| match arg {
+            Operand::Reg(reg) => Some(reg.0.num()),
+            _ => None,
+        });
+        let saved = self
+            .live_registers()
+            .into_iter()
+            .filter(|reg| !args.contains(&Some(reg.num())))
+            .collect::<Vec<_>>();
+        self.push_registers(&saved);
+
+        self.build_push(y);
+        self.build_push(x);
+        self.call_by_name(name);
+        self.drop_args(2);
+
+        let reg = self.call_result(size);
+        self.restore_registers(saved);
+        if operation == Operator::NotEquals {
+            self.emit(Instruction::Xor(Arg::Reg(reg.0), Arg::Imm(1)));
+        }
+        reg
+    }
+
+    /// Loads the length stored right before the string's bytes.
+    pub fn build_len(&mut self, string: Operand) -> Reg {
+        let reg = self.store_to_reg(string, None, Size::Qword);
+        let length = Memory::new(reg.0, -8).sized(Size::Qword);
+        self.emit(Instruction::Mov(Arg::Reg(reg.0), Arg::Mem(length)));
+        reg
+    }
+
+    /// Loads the element of a string or an array at a 64-bit `index`,
+    /// indices past the end jump to the runtime's error handler.
+    pub fn build_index(&mut self, sequence: Operand, index: Operand, scalar: Scalar) -> Reg {
+        let base = self.store_to_reg(sequence, None, Size::Qword);
+        let index = self.store_to_reg(index, None, Size::Qword);
+
+        let length = Memory::new(base.0, -8).sized(Size::Qword);
+        self.emit(Instruction::Cmp(Arg::Reg(index.0), Arg::Mem(length)));
+        self.emit(Instruction::Jcc(
+            Cond::Ae,
+            Arg::label("index_out_of_bounds"),
+        ));
+
+        let element = Memory {
+            index: Some(index.0),
+            scale: scalar.size.bytes() as u8,
+            ..Memory::new(base.0, 0)
+        };
+        self.load(&base, element, scalar);
+        self.free_reg(index);
+        base.resized(scalar.reg_size())
+    }
+
+    /// Moves a float of `size` into an SSE register.
+    fn load_xmm(&mut self, value: Arg, xmm: u8, size: Size) {
+        let value = match value {
+            Arg::Imm(_) => {
+                let reg = self.alloc_reg(size);
+                let out = Arg::Reg(reg.0);
+                self.emit(Instruction::Mov(out.clone(), value));
+                self.free_reg(reg);
+                out
+            }
+            value => value,
+        };
+        self.emit(move_xmm(Arg::Xmm(xmm), value, size));
+    }
+
+    /// `xmm0` and `xmm1` are only used as scratch registers, the result
+    /// ends up back in `reg`. Its size tells an `f32` from an `f64`.
+    fn build_float_op(&mut self, reg: Reg, source: Arg, operation: Operator) -> Reg {
+        let (x, y) = (Arg::Xmm(0), Arg::Xmm(1));
+        let size = reg.0.size();
+        let double = size == Size::Qword;
+        self.load_xmm(Arg::Reg(reg.0), 0, size);
+        self.load_xmm(source, 1, size);
+
+        let cond = match (operation, double) {
+            (Operator::Add, false) => Instruction::Addss(x, y),
+            (Operator::Add, true) => Instruction::Addsd(x, y),
+            (Operator::Sub, false) => Instruction::Subss(x, y),
+            (Operator::Sub, true) => Instruction::Subsd(x, y),
+            (Operator::Mult, false) => Instruction::Mulss(x, y),
+            (Operator::Mult, true) => Instruction::Mulsd(x, y),
+            (Operator::Div, false) => Instruction::Divss(x, y),
+            (Operator::Div, true) => Instruction::Divsd(x, y),
+            (Operator::Mod, _) => unreachable!("Floats have no remainder."),
+            // ucomiss sets the flags like an unsigned comparison.
+            (operation, _) => {
+                let cond = comparison_cond(&operation, false);
+
+                let reg = reg.resized(Size::Dword);
+                let low = Arg::Reg(reg.0.resized(Size::Byte));
+                self.emit(compare_xmm(x, y, size));
+                self.emit(Instruction::Set(cond, low.clone()));
+                self.emit(Instruction::Movzx(Arg::Reg(reg.0), low));
+                return reg;
+            }
+        };
+
+        self.emit(cond);
+        self.emit(move_xmm(Arg::Reg(reg.0), Arg::Xmm(0), size));
+        reg
+    }
+
+    /// Converts between integers of any width and signedness, wrapping
+    /// like a two's complement truncation, and floats.
+    pub fn build_cast(&mut self, value: Operand, from: Scalar, to: Scalar) -> Reg {
+        let reg = match value {
+            Operand::Reg(reg) => reg.resized(from.reg_size()),
+            Operand::Var(var) => self.get_var(&var),
+            value => self.store_to_reg(value, None, from.reg_size()),
+        };
+
+        match (from.float, to.float) {
+            (true, true) => return self.float_to_float(reg, from, to),
+            (false, true) => return self.int_to_float(reg, from, to),
+            (true, false) => return self.float_to_int(reg, from, to),
+            (false, false) => {}
+        }
+
+        if to.size != Size::Qword || from.size == Size::Qword {
+            let reg = reg.resized(to.reg_size());
+            self.truncate(&reg, to);
+            return reg;
+        }
+
+        if from.signed {
+            let target = Arg::Reg(reg.0.resized(Size::Qword));
+            self.emit(Instruction::Movsx(target, Arg::Reg(reg.0)));
+            return reg.resized(Size::Qword);
+        }
 
-            let mut out = String::from(opcode);
-            out.push_str(" al");
-            self.buffer.push_str(&self.format(&out));
+        // Writing a 32-bit register clears the upper half.
+        self.emit(Instruction::Mov(Arg::Reg(reg.0), Arg::Reg(reg.0)));
+        reg.resized(Size::Qword)
+    }
 
-            let out = format!("movzx {}, al", reg.0);
-            self.buffer.push_str(&self.format(&out));
+    fn float_to_float(&mut self, reg: Reg, from: Scalar, to: Scalar) -> Reg {
+        if from.size == to.size {
+            return reg;
         }
 
+        let x = Arg::Xmm(0);
+        self.emit(move_xmm(x.clone(), Arg::Reg(reg.0), from.size));
+        self.emit(match to.size {
+            Size::Qword => Instruction::Cvtss2sd(x.clone(), x),
+            _ => Instruction::Cvtsd2ss(x.clone(), x),
+        });
+        let reg = reg.resized(to.size);
+        self.emit(move_xmm(Arg::Reg(reg.0), Arg::Xmm(0), to.size));
         reg
     }
 
+    fn int_to_float(&mut self, reg: Reg, from: Scalar, to: Scalar) -> Reg {
+        let source = match (from.size, from.signed) {
+            // Zero extend into a new register and convert all 64 bits,
+            // the 32-bit form would treat large values as negative.
+            (Size::Dword, false) => {
+                let out = self.alloc_reg(Size::Dword);
+                let wide = out.0.resized(Size::Qword);
+                self.emit(Instruction::Mov(Arg::Reg(out.0), Arg::Reg(reg.0)));
+                self.free_reg(out);
+                wide
+            }
+            _ => reg.0.resized(from.reg_size()),
+        };
+
+        let reg = reg.resized(to.size);
+        self.emit(match to.size {
+            Size::Qword => Instruction::Cvtsi2sd(Arg::Xmm(0), Arg::Reg(source)),
+            _ => Instruction::Cvtsi2ss(Arg::Xmm(0), Arg::Reg(source)),
+        });
+        self.emit(move_xmm(Arg::Reg(reg.0), Arg::Xmm(0), to.size));
+        reg
+    }
+
+    /// Truncates towards zero, out of range values wrap like an integer cast.
+    fn float_to_int(&mut self, reg: Reg, from: Scalar, to: Scalar) -> Reg {
+        // u32 needs the 64-bit form to cover its whole range.
+        let size = match (to.size, to.signed) {
+            (Size::Dword, false) | (Size::Qword, _) => Size::Qword,
+            _ => Size::Dword,
+        };
+
+        self.emit(move_xmm(
+            Arg::Xmm(0),
+            Arg::Reg(reg.0.resized(from.size)),
+            from.size,
+        ));
+        let reg = reg.resized(size);
+        let target = Arg::Reg(reg.0);
+        self.emit(match from.size {
+            Size::Qword => Instruction::Cvttsd2si(target, Arg::Xmm(0)),
+            _ => Instruction::Cvttss2si(target, Arg::Xmm(0)),
+        });
+        self.truncate(&reg, to);
+        reg.resized(to.reg_size())
+    }
+
+    /// Registers that currently hold a value.
+    fn live_registers(&self) -> Vec<Register> {
+        REGISTERS
+            .iter()
+            .map(|name| Reg::new(name).to_x64())
+            .filter(|reg| !self.registers.iter().any(|free| free.0.num() == reg.num()))
+            .collect()
+    }
+
+    fn push_registers(&mut self, registers: &[Register]) {
+        for reg in registers {
+            self.emit(Instruction::Push(Arg::Reg(*reg)));
+        }
+    }
+
+    /// Pushes every register holding a value, since the called function
+    /// is free to overwrite them. Call this before pushing the arguments.
+    pub fn save_registers(&mut self) -> Vec<Register> {
+        let saved = self.live_registers();
+        self.push_registers(&saved);
+        saved
+    }
+
+    /// Pops the registers saved before a call, after claiming its result.
+    pub fn restore_registers(&mut self, saved: Vec<Register>) {
+        for reg in saved.into_iter().rev() {
+            self.emit(Instruction::Pop(Arg::Reg(reg)));
+        }
+    }
+
+    /// Claims the register holding the return value of the last call.
+    pub fn call_result(&mut self, size: Size) -> Reg {
+        let rax = Register::RAX.num();
+        match self.registers.iter().position(|reg| reg.0.num() == rax) {
+            Some(i) => self.registers.remove(i).resized(size),
+            None => {
+                let reg = self.alloc_reg(size);
+                let result = Arg::Reg(Register::RAX.resized(size));
+                self.emit(Instruction::Mov(Arg::Reg(reg.0), result));
+                reg
+            }
+        }
+    }
+
     pub fn free_reg(&mut self, x: Reg) {
         self.registers.push(x);
     }
 
+    /// Pops `count` pushed call arguments off the stack.
+    pub fn drop_args(&mut self, count: usize) {
+        let rsp = Arg::Reg(Register::RSP);
+        self.emit(Instruction::Add(rsp, Arg::Imm(count as i64 * 8)));
+    }
+
     pub fn get_label(&mut self) -> Label {
         let ret = Label::new(self.label_count);
         self.label_count += 1;
         ret
     }
 
-    pub fn build_jump(&mut self, label: &Label, jmp: Jump) {
-        let out = format!("{} {}", jmp.to_string(), label.to_string());
-        self.buffer.push_str(&self.format(&out));
+    /// Compares `x` with `y` and jumps to `target` when the comparison
+    /// comes out as `when`, the flags feed the jump without making a bool.
+    pub fn build_branch(
+        &mut self,
+        x: Operand,
+        y: Operand,
+        operation: Operator,
+        scalar: Scalar,
+        target: &Label,
+        when: bool,
+    ) {
+        // ucomiss sets the flags like an unsigned comparison.
+        let jump = Jump::comparison(&operation, scalar.signed && !scalar.float)
+            .expect("Only comparisons can branch.");
+        let jump = if when { jump } else { jump.negate() };
+
+        let size = scalar.reg_size();
+        let reg = self.operand_reg(x, size);
+        let source = self.get_value(y, size);
+        if scalar.float {
+            self.load_xmm(Arg::Reg(reg.0), 0, size);
+            self.load_xmm(source, 1, size);
+            self.emit(compare_xmm(Arg::Xmm(0), Arg::Xmm(1), size));
+        } else {
+            self.emit(Instruction::Cmp(Arg::Reg(reg.0), source));
+        }
+        self.free_reg(reg);
+
+        self.build_jump(target, jump);
     }
 
-    pub fn write_raw(&mut self, text: &str) {
-        self.buffer.push_str(text)
+    /// Jumps to `target` when the bool `condition` is `when`. Variables are
+    /// compared in memory without loading them first.
+    pub fn build_test(&mut self, condition: Operand, target: &Label, when: bool) {
+        match condition {
+            Operand::Var(var) => {
+                self.emit(Instruction::Cmp(Arg::Mem(var.memory()), Arg::Imm(1)));
+            }
+            condition => {
+                let reg = match condition {
+                    Operand::Reg(reg) => reg,
+                    condition => self.store_to_reg(condition, None, Size::Dword),
+                };
+                self.emit(Instruction::Cmp(
+                    Arg::Reg(reg.0.resized(Size::Dword)),
+                    Arg::Imm(1),
+                ));
+                self.free_reg(reg);
+            }
+        }
+
+        let jump = if when { Jump::Equal } else { Jump::NotEqual };
+        self.build_jump(target, jump);
     }
 
-    pub fn write_raw_fmt(&mut self, text: &str) {
-        let out = self.format(text);
-        self.buffer.push_str(&out);
+    pub fn build_jump(&mut self, label: &Label, jmp: Jump) {
+        let target = Arg::label(&label.to_string());
+        self.emit(match jmp.cond() {
+            Some(cond) => Instruction::Jcc(cond, target),
+            None => Instruction::Jmp(target),
+        });
     }
 
-    pub fn insert_label(&mut self, label: &Label) {
-        self.buffer.push_str(&label.to_string());
-        self.buffer.push_str(":\n");
+    /// Parses and appends handwritten assembly, e.g. from `__asm__`.
+    pub fn write_asm(&mut self, text: &str) -> Result<(), ParseError> {
+        let instructions = parse::parse(text)?;
+        self.buffer.extend(instructions);
+        Ok(())
     }
 
-    pub fn write(&mut self, text: &str) {
-        self.buffer.push_str(text)
+    pub fn insert_label(&mut self, label: &Label) {
+        self.emit(Instruction::Label(label.to_string()));
     }
 
     pub fn build_syscall(&mut self) {
-        self.buffer.push_str(&self.format("syscall"));
+        self.emit(Instruction::Syscall);
     }
 
     /// This function doesn't check is the called func
     /// exists, so make sure it does.
     pub fn call_by_name(&mut self, name: &str) {
-        let out = format!("call {name}");
-        self.buffer.push_str(&self.format(&out));
+        self.emit(Instruction::Call(Arg::label(name)));
     }
 
     pub fn write_to_fn(&mut self, f: &mut Function, return_label: &Label) {
-        self.buffer.push_str(&return_label.to_string());
-        self.buffer.push_str(":\n");
+        self.insert_label(return_label);
 
         f.write(&self.buffer);
-        f.set_reserved(self.reserved * 4);
+        f.set_reserved(self.reserved);
         self.buffer.clear();
         self.offset = 0;
-    }
-
-    fn format(&self, value: &str) -> String {
-        let mut out = String::new();
-
-        out.push_str("    ");
-        out.push_str(value);
-        out.push('\n');
-
-        out
+        self.reserved = 0;
     }
 }
@@ -13,3 +13,496 @@ puts:
     leave
     ret
 ";
+
+/// Strings are stored as their bytes followed by a NUL, with the length as
+/// a qword right before them. A string value points at the first byte.
+/// Arrays are laid out the same way, `args` turns the command line
+/// arguments `_start` saved into an array of strings. `env` looks a
+/// variable up in the environment and gives an empty string without it.
+pub const STRING_SOURCE: &str = "\
+print:
+    push rbp
+    mov rbp, rsp
+    push rdi
+    push rsi
+    push rdx
+    push rcx
+    push r11
+    mov rsi, [rbp+16]
+    mov rdx, [rsi-8]
+    mov rax, 1
+    mov rdi, 1
+    syscall
+    pop r11
+    pop rcx
+    pop rdx
+    pop rsi
+    pop rdi
+    leave
+    ret
+str_concat:
+    push rbp
+    mov rbp, rsp
+    push rsi
+    push rdx
+    push rcx
+    push r8
+    mov rsi, [rbp+16]
+    mov rdx, [rsi-8]
+    mov rsi, [rbp+24]
+    add rdx, [rsi-8]
+    mov rcx, rdx
+    add rcx, 9
+    push rcx
+    call alloc
+    add rsp, 8
+    mov [rax], rdx
+    add rax, 8
+    mov r8, rax
+    mov rsi, [rbp+16]
+    mov rcx, [rsi-8]
+.concat_first:
+    cmp rcx, 0
+    je .concat_second
+    mov dl, [rsi]
+    mov [r8], dl
+    add rsi, 1
+    add r8, 1
+    sub rcx, 1
+    jmp .concat_first
+.concat_second:
+    mov rsi, [rbp+24]
+    mov rcx, [rsi-8]
+.concat_loop:
+    cmp rcx, 0
+    je .concat_done
+    mov dl, [rsi]
+    mov [r8], dl
+    add rsi, 1
+    add r8, 1
+    sub rcx, 1
+    jmp .concat_loop
+.concat_done:
+    mov byte [r8], 0
+    pop r8
+    pop rcx
+    pop rdx
+    pop rsi
+    leave
+    ret
+str_eq:
+    push rbp
+    mov rbp, rsp
+    push rsi
+    push rdi
+    push rcx
+    push rdx
+    mov rsi, [rbp+16]
+    mov rdi, [rbp+24]
+    xor eax, eax
+    mov rcx, [rsi-8]
+    cmp rcx, [rdi-8]
+    jne .eq_done
+.eq_loop:
+    cmp rcx, 0
+    je .eq_same
+    sub rcx, 1
+    mov dl, [rsi+rcx]
+    cmp dl, [rdi+rcx]
+    jne .eq_done
+    jmp .eq_loop
+.eq_same:
+    mov eax, 1
+.eq_done:
+    pop rdx
+    pop rcx
+    pop rdi
+    pop rsi
+    leave
+    ret
+str_from:
+    push rbp
+    mov rbp, rsp
+    push rsi
+    push rcx
+    push rdx
+    mov rcx, [rbp+24]
+    lea rdx, [rcx+9]
+    push rdx
+    call alloc
+    add rsp, 8
+    mov [rax], rcx
+    add rax, 8
+    mov byte [rax+rcx], 0
+    mov rsi, [rbp+16]
+.from_loop:
+    cmp rcx, 0
+    je .from_done
+    sub rcx, 1
+    mov dl, [rsi+rcx]
+    mov [rax+rcx], dl
+    jmp .from_loop
+.from_done:
+    pop rdx
+    pop rcx
+    pop rsi
+    leave
+    ret
+args:
+    push rbp
+    mov rbp, rsp
+    push rsi
+    push rdi
+    push rcx
+    push rdx
+    push r8
+    mov rcx, [argc]
+    lea rdx, [rcx*8+8]
+    push rdx
+    call alloc
+    add rsp, 8
+    mov [rax], rcx
+    add rax, 8
+    mov r8, rax
+    mov rsi, [argv]
+.args_loop:
+    cmp rcx, 0
+    je .args_done
+    sub rcx, 1
+    mov rdi, [rsi+rcx*8]
+    xor edx, edx
+.args_length:
+    cmp byte [rdi+rdx], 0
+    je .args_copy
+    add rdx, 1
+    jmp .args_length
+.args_copy:
+    push rdx
+    push rdi
+    call str_from
+    add rsp, 16
+    mov [r8+rcx*8], rax
+    jmp .args_loop
+.args_done:
+    mov rax, r8
+    pop r8
+    pop rdx
+    pop rcx
+    pop rdi
+    pop rsi
+    leave
+    ret
+arg_count:
+    mov rax, [argc]
+    ret
+env:
+    push rbp
+    mov rbp, rsp
+    push rsi
+    push rdi
+    push rcx
+    push rdx
+    push r8
+    mov r8, [envp]
+.env_loop:
+    mov rdi, [r8]
+    cmp rdi, 0
+    je .env_missing
+    add r8, 8
+    mov rsi, [rbp+16]
+    mov rcx, [rsi-8]
+.env_name:
+    cmp rcx, 0
+    je .env_equals
+    mov dl, [rsi]
+    cmp dl, [rdi]
+    jne .env_loop
+    add rsi, 1
+    add rdi, 1
+    sub rcx, 1
+    jmp .env_name
+.env_equals:
+    cmp byte [rdi], 61
+    jne .env_loop
+    add rdi, 1
+    xor edx, edx
+.env_length:
+    cmp byte [rdi+rdx], 0
+    je .env_copy
+    add rdx, 1
+    jmp .env_length
+.env_missing:
+    xor edx, edx
+.env_copy:
+    push rdx
+    push rdi
+    call str_from
+    add rsp, 16
+    pop r8
+    pop rdx
+    pop rcx
+    pop rdi
+    pop rsi
+    leave
+    ret
+index_out_of_bounds:
+    push index_error
+    call print
+    mov rax, 60
+    mov rdi, 101
+    syscall
+";
+
+/// Strings used by the runtime, emitted like the module's own strings.
+pub const RUNTIME_STRINGS: [(&str, &str); 2] = [
+    ("index_error", "Index out of bounds.\n"),
+    ("unwrap_error", "Unwrapped an error.\n"),
+];
+
+/// Zeroed qwords the runtime keeps its state in, emitted in `.bss`.
+pub const RUNTIME_QWORDS: [&str; 4] = ["heap_free", "argc", "argv", "envp"];
+
+/// `alloc(size)`, `free(ptr)` and `realloc(ptr, size)`.
+///
+/// Every block starts with a 16 byte header holding its capacity, the
+/// pointer handed out comes right after it. Small blocks are carved out of
+/// the brk heap and go on a first fit free list through `heap_free` when
+/// freed, with the next free block in the second header qword. Big blocks
+/// of 128 KiB and more are mapped with mmap and unmapped again by `free`.
+pub const ALLOC_SOURCE: &str = "\
+alloc:
+    push rbp
+    mov rbp, rsp
+    push rdi
+    push rsi
+    push rdx
+    push rcx
+    push r8
+    push r9
+    push r10
+    push r11
+    mov rdi, [rbp+16]
+    add rdi, 15
+    and rdi, -16
+    cmp rdi, 0
+    jne .alloc_sized
+    mov rdi, 16
+.alloc_sized:
+    cmp rdi, 131072
+    jae .alloc_map
+    lea rsi, [heap_free]
+.alloc_search:
+    mov rax, [rsi]
+    cmp rax, 0
+    je .alloc_grow
+    cmp [rax-16], rdi
+    jae .alloc_reuse
+    lea rsi, [rax-8]
+    jmp .alloc_search
+.alloc_reuse:
+    mov rdx, [rax-8]
+    mov [rsi], rdx
+    jmp .alloc_done
+.alloc_grow:
+    mov rdx, rdi
+    mov rax, 12
+    xor edi, edi
+    syscall
+    add rax, 15
+    and rax, -16
+    mov r8, rax
+    lea rdi, [rax+rdx+16]
+    mov rax, 12
+    syscall
+    cmp rax, rdi
+    jb .alloc_failed
+    mov [r8], rdx
+    lea rax, [r8+16]
+    jmp .alloc_done
+.alloc_map:
+    lea rsi, [rdi+16]
+    xor edi, edi
+    mov rdx, 3
+    mov r10, 34
+    mov r8, -1
+    xor r9d, r9d
+    mov rax, 9
+    syscall
+    cmp rax, -4096
+    ja .alloc_failed
+    sub rsi, 16
+    mov [rax], rsi
+    add rax, 16
+.alloc_done:
+    pop r11
+    pop r10
+    pop r9
+    pop r8
+    pop rcx
+    pop rdx
+    pop rsi
+    pop rdi
+    leave
+    ret
+.alloc_failed:
+    mov rax, 60
+    mov rdi, 12
+    syscall
+free:
+    push rbp
+    mov rbp, rsp
+    push rdi
+    push rsi
+    push rcx
+    push r11
+    mov rdi, [rbp+16]
+    cmp rdi, 0
+    je .free_done
+    mov rsi, [rdi-16]
+    cmp rsi, 131072
+    jae .free_unmap
+    mov rax, [heap_free]
+    mov [rdi-8], rax
+    mov [heap_free], rdi
+    jmp .free_done
+.free_unmap:
+    sub rdi, 16
+    add rsi, 16
+    mov rax, 11
+    syscall
+.free_done:
+    pop r11
+    pop rcx
+    pop rsi
+    pop rdi
+    leave
+    ret
+realloc:
+    push rbp
+    mov rbp, rsp
+    push rsi
+    push rdx
+    push rcx
+    mov rsi, [rbp+16]
+    mov rax, rsi
+    cmp rsi, 0
+    je .realloc_new
+    mov rcx, [rsi-16]
+    cmp rcx, [rbp+24]
+    jae .realloc_done
+.realloc_new:
+    push qword [rbp+24]
+    call alloc
+    add rsp, 8
+    cmp rsi, 0
+    je .realloc_done
+    mov rcx, [rsi-16]
+.realloc_copy:
+    sub rcx, 8
+    mov rdx, [rsi+rcx]
+    mov [rax+rcx], rdx
+    jne .realloc_copy
+    push rax
+    push rsi
+    call free
+    add rsp, 8
+    pop rax
+.realloc_done:
+    pop rcx
+    pop rdx
+    pop rsi
+    leave
+    ret
+";
+
+/// `ok(value)`, `err(code)` and the functions looking into a result.
+///
+/// A result points at two qwords on the heap, a tag that's 0 for ok and 1
+/// for an error followed by the value or the error code. The layout doesn't
+/// depend on the value's type, so `?` can return an error as it is.
+/// Nothing tracks who still holds a result, so like strings its block is
+/// never freed.
+pub const RESULT_SOURCE: &str = "\
+result_ok:
+    push rbp
+    mov rbp, rsp
+    push rcx
+    push 16
+    call alloc
+    add rsp, 8
+    mov qword [rax], 0
+    mov rcx, [rbp+16]
+    mov [rax+8], rcx
+    pop rcx
+    leave
+    ret
+result_err:
+    push rbp
+    mov rbp, rsp
+    push rcx
+    push 16
+    call alloc
+    add rsp, 8
+    mov qword [rax], 1
+    mov rcx, [rbp+16]
+    mov [rax+8], rcx
+    pop rcx
+    leave
+    ret
+result_is_ok:
+    mov rax, [rsp+8]
+    mov rax, [rax]
+    xor eax, 1
+    ret
+result_error:
+    mov rax, [rsp+8]
+    cmp qword [rax], 0
+    je .error_ok
+    mov rax, [rax+8]
+    ret
+.error_ok:
+    xor eax, eax
+    ret
+result_unwrap:
+    mov rax, [rsp+8]
+    cmp qword [rax], 0
+    jne .unwrap_error
+    mov rax, [rax+8]
+    ret
+.unwrap_error:
+    push unwrap_error
+    call print
+    mov rax, 60
+    mov rdi, 101
+    syscall
+";
+
+/// `enum_new(tag, count, fields...)` makes an enum's value, the tag as a
+/// qword followed by the `count` payload qwords. The value is never
+/// freed, the same as a result.
+pub const ENUM_SOURCE: &str = "\
+enum_new:
+    push rbp
+    mov rbp, rsp
+    push rcx
+    push rdx
+    mov rcx, [rbp+24]
+    lea rdx, [rcx*8+8]
+    push rdx
+    call alloc
+    add rsp, 8
+    mov rdx, [rbp+16]
+    mov [rax], rdx
+.enum_copy:
+    cmp rcx, 0
+    je .enum_done
+    mov rdx, [rbp+rcx*8+24]
+    mov [rax+rcx*8], rdx
+    sub rcx, 1
+    jmp .enum_copy
+.enum_done:
+    pop rdx
+    pop rcx
+    leave
+    ret
+";
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use crate::encoder::{RelocKind, Relocation};
+
+const EHDR_SIZE: u64 = 64;
+const SHDR_SIZE: u64 = 64;
+const SYM_SIZE: u64 = 24;
+const RELA_SIZE: u64 = 24;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+// Section header indices, in the order they're written.
+const TEXT_INDEX: u16 = 1;
+const DATA_INDEX: u16 = 2;
+const BSS_INDEX: u16 = 3;
+const SYMTAB_INDEX: u32 = 5;
+const STRTAB_INDEX: u32 = 6;
+const SHSTRTAB_INDEX: u16 = 7;
+const SECTION_COUNT: u16 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    Undefined,
+    Text,
+    Data,
+    Bss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    NoType,
+    Object,
+    Func,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub section: Section,
+    pub value: u64,
+    pub kind: SymbolKind,
+    pub global: bool,
+}
+
+/// A relocatable ELF64 object for x86-64 with a single
+/// `.text`, `.data` and `.bss` section.
+#[derive(Debug, Default)]
+pub struct ObjectFile {
+    pub text: Vec<u8>,
+    pub data: Vec<u8>,
+    pub bss_size: u64,
+    pub symbols: Vec<Symbol>,
+    /// Relocations applied to `.text`.
+    pub relocations: Vec<Relocation>,
+}
+
+impl ObjectFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let symbols = self.ordered_symbols();
+        let first_global = symbols
+            .iter()
+            .position(|sym| sym.global)
+            .unwrap_or(symbols.len());
+
+        let mut strtab = StringTable::new();
+        let mut symtab = vec![0; SYM_SIZE as usize];
+        let mut indices = HashMap::new();
+        for (i, sym) in symbols.iter().enumerate() {
+            indices.insert(sym.name.as_str(), i as u64 + 1);
+
+            let bind = if sym.global { STB_GLOBAL } else { STB_LOCAL };
+            let kind = match sym.kind {
+                SymbolKind::NoType => STT_NOTYPE,
+                SymbolKind::Object => STT_OBJECT,
+                SymbolKind::Func => STT_FUNC,
+            };
+            let shndx = match sym.section {
+                Section::Undefined => 0,
+                Section::Text => TEXT_INDEX,
+                Section::Data => DATA_INDEX,
+                Section::Bss => BSS_INDEX,
+            };
+
+            symtab.extend(strtab.add(&sym.name).to_le_bytes());
+            symtab.push((bind << 4) | kind);
+            symtab.push(0);
+            symtab.extend(shndx.to_le_bytes());
+            symtab.extend(sym.value.to_le_bytes());
+            symtab.extend(0u64.to_le_bytes());
+        }
+
+        let mut rela = vec![];
+        for reloc in &self.relocations {
+            let kind: u64 = match reloc.kind {
+                RelocKind::Abs64 => 1,
+                RelocKind::Pc32 => 2,
+                RelocKind::Plt32 => 4,
+                RelocKind::Abs32 => 10,
+                RelocKind::Abs32S => 11,
+            };
+            let sym = indices[reloc.symbol.as_str()];
+
+            rela.extend(reloc.offset.to_le_bytes());
+            rela.extend(((sym << 32) | kind).to_le_bytes());
+            rela.extend(reloc.addend.to_le_bytes());
+        }
+
+        let mut shstrtab = StringTable::new();
+        let names = [
+            ".text",
+            ".data",
+            ".bss",
+            ".rela.text",
+            ".symtab",
+            ".strtab",
+            ".shstrtab",
+        ]
+        .map(|name| shstrtab.add(name));
+
+        // Lay out the section contents right after the ELF header.
+        let mut out = vec![0; EHDR_SIZE as usize];
+        let mut place = |bytes: &[u8], align: u64| {
+            while !(out.len() as u64).is_multiple_of(align) {
+                out.push(0);
+            }
+            let offset = out.len() as u64;
+            out.extend_from_slice(bytes);
+            offset
+        };
+        let text_offset = place(&self.text, 16);
+        let data_offset = place(&self.data, 8);
+        let rela_offset = place(&rela, 8);
+        let symtab_offset = place(&symtab, 8);
+        let strtab_offset = place(&strtab.bytes, 1);
+        let shstrtab_offset = place(&shstrtab.bytes, 1);
+        let shoff = place(&[], 8);
+
+        let headers = [
+            SectionHeader::default(),
+            SectionHeader {
+                name: names[0],
+                kind: SHT_PROGBITS,
+                flags: SHF_ALLOC | SHF_EXECINSTR,
+                offset: text_offset,
+                size: self.text.len() as u64,
+                align: 16,
+                ..Default::default()
+            },
+            SectionHeader {
+                name: names[1],
+                kind: SHT_PROGBITS,
+                flags: SHF_ALLOC | SHF_WRITE,
+                offset: data_offset,
+                size: self.data.len() as u64,
+                align: 8,
+                ..Default::default()
+            },
+            SectionHeader {
+                name: names[2],
+                kind: SHT_NOBITS,
+                flags: SHF_ALLOC | SHF_WRITE,
+                offset: shoff,
+                size: self.bss_size,
+                align: 8,
+                ..Default::default()
+            },
+            SectionHeader {
+                name: names[3],
+                kind: SHT_RELA,
+                flags: SHF_INFO_LINK,
+                offset: rela_offset,
+                size: rela.len() as u64,
+                link: SYMTAB_INDEX,
+                info: TEXT_INDEX as u32,
+                align: 8,
+                entsize: RELA_SIZE,
+            },
+            SectionHeader {
+                name: names[4],
+                kind: SHT_SYMTAB,
+                offset: symtab_offset,
+                size: symtab.len() as u64,
+                link: STRTAB_INDEX,
+                // Index of the first global symbol, the null symbol counts too.
+                info: first_global as u32 + 1,
+                align: 8,
+                entsize: SYM_SIZE,
+                ..Default::default()
+            },
+            SectionHeader {
+                name: names[5],
+                kind: SHT_STRTAB,
+                offset: strtab_offset,
+                size: strtab.bytes.len() as u64,
+                align: 1,
+                ..Default::default()
+            },
+            SectionHeader {
+                name: names[6],
+                kind: SHT_STRTAB,
+                offset: shstrtab_offset,
+                size: shstrtab.bytes.len() as u64,
+                align: 1,
+                ..Default::default()
+            },
+        ];
+        debug_assert_eq!(headers.len(), SECTION_COUNT as usize);
+
+        for header in &headers {
+            header.write(&mut out);
+        }
+
+        write_elf_header(&mut out[..EHDR_SIZE as usize], shoff);
+        out
+    }
+
+    /// ELF requires local symbols to come before global ones. Symbols
+    /// that are only referenced by relocations become undefined globals.
+    fn ordered_symbols(&self) -> Vec<Symbol> {
+        let (mut symbols, globals): (Vec<_>, Vec<_>) =
+            self.symbols.iter().cloned().partition(|sym| !sym.global);
+        symbols.extend(globals);
+
+        for reloc in &self.relocations {
+            if !symbols.iter().any(|sym| sym.name == reloc.symbol) {
+                symbols.push(Symbol {
+                    name: reloc.symbol.clone(),
+                    section: Section::Undefined,
+                    value: 0,
+                    kind: SymbolKind::NoType,
+                    global: true,
+                });
+            }
+        }
+
+        symbols
+    }
+}
+
+fn write_elf_header(out: &mut [u8], shoff: u64) {
+    let mut header = vec![0x7F, b'E', b'L', b'F'];
+    // 64-bit, little endian, version 1, System V ABI
+    header.extend([2, 1, 1, 0]);
+    header.extend([0; 8]);
+    // ET_REL, EM_X86_64, EV_CURRENT
+    header.extend(1u16.to_le_bytes());
+    header.extend(62u16.to_le_bytes());
+    header.extend(1u32.to_le_bytes());
+    // entry, phoff, shoff
+    header.extend(0u64.to_le_bytes());
+    header.extend(0u64.to_le_bytes());
+    header.extend(shoff.to_le_bytes());
+    // flags, ehsize, phentsize, phnum
+    header.extend(0u32.to_le_bytes());
+    header.extend((EHDR_SIZE as u16).to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    // shentsize, shnum, shstrndx
+    header.extend((SHDR_SIZE as u16).to_le_bytes());
+    header.extend(SECTION_COUNT.to_le_bytes());
+    header.extend(SHSTRTAB_INDEX.to_le_bytes());
+
+    out.copy_from_slice(&header);
+}
+
+#[derive(Default)]
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl SectionHeader {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.name.to_le_bytes());
+        out.extend(self.kind.to_le_bytes());
+        out.extend(self.flags.to_le_bytes());
+        // sh_addr is always 0 in relocatable files.
+        out.extend(0u64.to_le_bytes());
+        out.extend(self.offset.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+        out.extend(self.link.to_le_bytes());
+        out.extend(self.info.to_le_bytes());
+        out.extend(self.align.to_le_bytes());
+        out.extend(self.entsize.to_le_bytes());
+    }
+}
+
+struct StringTable {
+    bytes: Vec<u8>,
+}
+
+impl StringTable {
+    fn new() -> Self {
+        Self { bytes: vec![0] }
+    }
+
+    fn add(&mut self, string: &str) -> u32 {
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(string.as_bytes());
+        self.bytes.push(0);
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let object = ObjectFile::new();
+        let bytes = object.to_bytes();
+
+        assert_eq!(&bytes[..4], b"\x7FELF");
+        // e_shnum
+        assert_eq!(u16::from_le_bytes([bytes[60], bytes[61]]), SECTION_COUNT);
+    }
+
+    #[test]
+    fn test_symbol_order() {
+        let mut object = ObjectFile::new();
+        object.symbols = vec![
+            Symbol {
+                name: "_start".to_owned(),
+                section: Section::Text,
+                value: 0,
+                kind: SymbolKind::Func,
+                global: true,
+            },
+            Symbol {
+                name: "main".to_owned(),
+                section: Section::Text,
+                value: 4,
+                kind: SymbolKind::Func,
+                global: false,
+            },
+        ];
+
+        let names: Vec<_> = object
+            .ordered_symbols()
+            .into_iter()
+            .map(|sym| sym.name)
+            .collect();
+        assert_eq!(names, ["main", "_start"]);
+    }
+}
@@ -0,0 +1,984 @@
+use std::{collections::HashMap, fmt};
+
+use crate::instr::{Instruction, Memory, Operand, Register, Size};
+
+/// An error produced while encoding an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeError {
+    pub text: String,
+    pub message: String,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (`{}`)", self.message, self.text.trim())
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+type EResult<T> = Result<T, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelocKind {
+    /// R_X86_64_64
+    Abs64,
+    /// R_X86_64_32
+    Abs32,
+    /// R_X86_64_32S
+    Abs32S,
+    /// R_X86_64_PC32
+    Pc32,
+    /// R_X86_64_PLT32
+    Plt32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub offset: u64,
+    pub symbol: String,
+    pub kind: RelocKind,
+    pub addend: i64,
+}
+
+/// Machine code together with the labels it defines and the
+/// relocations it still needs from the linker.
+#[derive(Debug, Default)]
+pub struct Assembled {
+    pub code: Vec<u8>,
+    pub labels: Vec<(String, u64)>,
+    pub relocations: Vec<Relocation>,
+}
+
+/// An immediate, optionally relative to a symbol.
+#[derive(Debug, Clone, PartialEq)]
+struct Imm {
+    value: i64,
+    symbol: Option<String>,
+}
+
+impl Imm {
+    fn fits_i8(&self) -> bool {
+        self.symbol.is_none() && i8::try_from(self.value).is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Rm<'a> {
+    Reg(Register),
+    Mem(&'a Memory),
+}
+
+impl<'a> Rm<'a> {
+    fn size(&self) -> Option<Size> {
+        match self {
+            Rm::Reg(reg) => Some(reg.size()),
+            Rm::Mem(mem) => mem.size,
+        }
+    }
+}
+
+struct Fixup {
+    offset: usize,
+    label: String,
+    kind: RelocKind,
+    text: String,
+}
+
+#[derive(Default)]
+struct Encoder {
+    code: Vec<u8>,
+    labels: HashMap<String, u64>,
+    label_order: Vec<String>,
+    fixups: Vec<Fixup>,
+    relocations: Vec<Relocation>,
+    scope: String,
+}
+
+/// Encodes instructions into x86-64 machine code.
+///
+/// All jumps and calls use 32-bit displacements, labels starting
+/// with a dot belong to the last non-local label, like in NASM.
+pub fn assemble(instructions: &[Instruction]) -> Result<Assembled, EncodeError> {
+    let mut encoder = Encoder::default();
+
+    for instr in instructions {
+        encoder.instruction(instr).map_err(|message| EncodeError {
+            text: instr.to_string(),
+            message,
+        })?;
+    }
+
+    encoder.finish()
+}
+
+impl Encoder {
+    fn finish(mut self) -> Result<Assembled, EncodeError> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            let end = fixup.offset as i64 + 4;
+
+            match self.labels.get(&fixup.label) {
+                Some(target) => {
+                    let rel = *target as i64 - end;
+                    self.code[fixup.offset..fixup.offset + 4]
+                        .copy_from_slice(&(rel as i32).to_le_bytes());
+                }
+                None if fixup.label.contains('.') => {
+                    return Err(EncodeError {
+                        text: fixup.text,
+                        message: format!("Undefined local label '{}'", fixup.label),
+                    })
+                }
+                None => self.relocations.push(Relocation {
+                    offset: fixup.offset as u64,
+                    symbol: fixup.label,
+                    kind: fixup.kind,
+                    addend: -4,
+                }),
+            }
+        }
+
+        let labels = self
+            .label_order
+            .iter()
+            .map(|name| (name.clone(), self.labels[name]))
+            .collect();
+
+        Ok(Assembled {
+            code: self.code,
+            labels,
+            relocations: self.relocations,
+        })
+    }
+
+    fn define_label(&mut self, label: &str) -> EResult<()> {
+        if !label.starts_with('.') {
+            self.scope = label.to_owned();
+        }
+        let name = self.scoped(label);
+
+        if self.labels.contains_key(&name) {
+            return Err(format!("Label '{}' is defined twice", name));
+        }
+        self.labels.insert(name.clone(), self.code.len() as u64);
+        self.label_order.push(name);
+        Ok(())
+    }
+
+    fn scoped(&self, label: &str) -> String {
+        if label.starts_with('.') {
+            format!("{}{}", self.scope, label)
+        } else {
+            label.to_owned()
+        }
+    }
+
+    fn imm_of(&self, op: &Operand) -> Option<Imm> {
+        match op {
+            Operand::Imm(value) => Some(Imm {
+                value: *value,
+                symbol: None,
+            }),
+            Operand::Sym(name, addend) => Some(Imm {
+                value: *addend,
+                symbol: Some(self.scoped(name)),
+            }),
+            _ => None,
+        }
+    }
+
+    fn scoped_mem(&self, mem: &Memory) -> EResult<Memory> {
+        for reg in mem.base.iter().chain(mem.index.iter()) {
+            if reg.size() != Size::Qword {
+                return Err("Memory operands need 64-bit registers".to_owned());
+            }
+        }
+        if mem.index.map(|reg| reg.num()) == Some(4) {
+            return Err("rsp can't be used as an index".to_owned());
+        }
+        if i32::try_from(mem.disp).is_err() {
+            return Err("Displacement doesn't fit in 32 bits".to_owned());
+        }
+
+        let mut mem = mem.clone();
+        mem.symbol = mem.symbol.map(|symbol| self.scoped(&symbol));
+        Ok(mem)
+    }
+
+    fn instruction(&mut self, instr: &Instruction) -> EResult<()> {
+        // Resolve symbols and validate memory operands up front.
+        let operands = instr
+            .operands()
+            .iter()
+            .map(|op| match op {
+                Operand::Mem(mem) => self.scoped_mem(mem).map(Operand::Mem),
+                op => Ok(op.clone()),
+            })
+            .collect::<EResult<Vec<_>>>()?;
+
+        match (instr, operands.as_slice()) {
+            (Instruction::Label(label), _) => self.define_label(label),
+            (Instruction::Comment(_), _) => Ok(()),
+            (Instruction::Mov(..), [dst, src]) => self.mov(dst, src),
+            (Instruction::Movzx(..), [Operand::Reg(dst), src]) => self.extend(0xB6, *dst, src),
+            (Instruction::Movsx(..), [Operand::Reg(dst), src]) => self.extend(0xBE, *dst, src),
+            (Instruction::Lea(..), [Operand::Reg(dst), Operand::Mem(mem)]) => {
+                if dst.size() == Size::Byte {
+                    return Err("lea can't use byte registers".to_owned());
+                }
+                self.emit_rm(Some(dst.size()), &[0x8D], dst.num(), Rm::Mem(mem), false)
+            }
+            (Instruction::Add(..), [dst, src]) => self.alu(0, dst, src),
+            (Instruction::Or(..), [dst, src]) => self.alu(1, dst, src),
+            (Instruction::And(..), [dst, src]) => self.alu(4, dst, src),
+            (Instruction::Sub(..), [dst, src]) => self.alu(5, dst, src),
+            (Instruction::Xor(..), [dst, src]) => self.alu(6, dst, src),
+            (Instruction::Cmp(..), [dst, src]) => self.alu(7, dst, src),
+            (Instruction::Imul(..), [Operand::Reg(dst), src]) => {
+                let rm = rm_of(src)?;
+                check_size(dst.size(), rm.size())?;
+                if dst.size() == Size::Byte {
+                    return Err("imul can't use byte registers".to_owned());
+                }
+                self.emit_rm(Some(dst.size()), &[0x0F, 0xAF], dst.num(), rm, false)
+            }
+            (Instruction::Imul3(_, _, imm), [Operand::Reg(dst), src, _]) => {
+                let rm = rm_of(src)?;
+                check_size(dst.size(), rm.size())?;
+                self.imul3(*dst, rm, *imm)
+            }
+            (Instruction::Shl(..), [dst, count]) => self.shift(4, dst, count),
+            (Instruction::Shr(..), [dst, count]) => self.shift(5, dst, count),
+            (Instruction::Sar(..), [dst, count]) => self.shift(7, dst, count),
+            (Instruction::Not(_), [arg]) => self.unary(2, arg),
+            (Instruction::Div(_), [arg]) => self.unary(6, arg),
+            (Instruction::Idiv(_), [arg]) => self.unary(7, arg),
+            (Instruction::Cdq, []) => {
+                self.code.push(0x99);
+                Ok(())
+            }
+            (Instruction::Cqo, []) => {
+                self.code.extend([0x48, 0x99]);
+                Ok(())
+            }
+            (Instruction::Set(cond, _), [dst]) => {
+                let rm = rm_of(dst)?;
+                if rm.size().unwrap_or(Size::Byte) != Size::Byte {
+                    return Err("setcc needs a byte operand".to_owned());
+                }
+                self.emit_rm(None, &[0x0F, 0x90 + cond.code()], 0, rm, false)
+            }
+            (Instruction::Movd(..), [Operand::Xmm(dst), src]) => {
+                let rm = rm_of(src)?;
+                check_size(Size::Dword, rm.size())?;
+                self.sse(Some(0x66), 0x6E, *dst, rm, false)
+            }
+            (Instruction::Movd(..), [dst, Operand::Xmm(src)]) => {
+                let rm = rm_of(dst)?;
+                check_size(Size::Dword, rm.size())?;
+                self.sse(Some(0x66), 0x7E, *src, rm, false)
+            }
+            (Instruction::Movq(..), [Operand::Xmm(dst), src]) => {
+                let rm = rm_of(src)?;
+                check_size(Size::Qword, rm.size())?;
+                self.sse(Some(0x66), 0x6E, *dst, rm, true)
+            }
+            (Instruction::Movq(..), [dst, Operand::Xmm(src)]) => {
+                let rm = rm_of(dst)?;
+                check_size(Size::Qword, rm.size())?;
+                self.sse(Some(0x66), 0x7E, *src, rm, true)
+            }
+            (Instruction::Cvtsi2ss(..) | Instruction::Cvtsi2sd(..), [Operand::Xmm(dst), src]) => {
+                let rm = rm_of(src)?;
+                let wide = match rm.size() {
+                    Some(Size::Dword) => false,
+                    Some(Size::Qword) => true,
+                    Some(_) => {
+                        return Err(format!(
+                            "Source of {} must be 32 or 64 bits",
+                            instr.mnemonic()
+                        ))
+                    }
+                    None => return Err("Operation size not specified".to_owned()),
+                };
+                self.sse(Some(scalar_prefix(instr)), 0x2A, *dst, rm, wide)
+            }
+            (Instruction::Cvttss2si(..) | Instruction::Cvttsd2si(..), [Operand::Reg(dst), src]) => {
+                let wide = match dst.size() {
+                    Size::Dword => false,
+                    Size::Qword => true,
+                    _ => {
+                        return Err(format!(
+                            "Destination of {} must be 32 or 64 bits",
+                            instr.mnemonic()
+                        ))
+                    }
+                };
+                let prefix = scalar_prefix(instr);
+                self.sse(
+                    Some(prefix),
+                    0x2C,
+                    dst.num(),
+                    xmm_rm_of(src, float_size(instr))?,
+                    wide,
+                )
+            }
+            (Instruction::Cvtss2sd(..), [Operand::Xmm(dst), src]) => self.sse(
+                Some(0xF3),
+                0x5A,
+                *dst,
+                xmm_rm_of(src, float_size(instr))?,
+                false,
+            ),
+            (Instruction::Cvtsd2ss(..), [Operand::Xmm(dst), src]) => self.sse(
+                Some(0xF2),
+                0x5A,
+                *dst,
+                xmm_rm_of(src, float_size(instr))?,
+                false,
+            ),
+            (Instruction::Addss(..) | Instruction::Addsd(..), [Operand::Xmm(dst), src]) => self
+                .sse(
+                    Some(scalar_prefix(instr)),
+                    0x58,
+                    *dst,
+                    xmm_rm_of(src, float_size(instr))?,
+                    false,
+                ),
+            (Instruction::Subss(..) | Instruction::Subsd(..), [Operand::Xmm(dst), src]) => self
+                .sse(
+                    Some(scalar_prefix(instr)),
+                    0x5C,
+                    *dst,
+                    xmm_rm_of(src, float_size(instr))?,
+                    false,
+                ),
+            (Instruction::Mulss(..) | Instruction::Mulsd(..), [Operand::Xmm(dst), src]) => self
+                .sse(
+                    Some(scalar_prefix(instr)),
+                    0x59,
+                    *dst,
+                    xmm_rm_of(src, float_size(instr))?,
+                    false,
+                ),
+            (Instruction::Divss(..) | Instruction::Divsd(..), [Operand::Xmm(dst), src]) => self
+                .sse(
+                    Some(scalar_prefix(instr)),
+                    0x5E,
+                    *dst,
+                    xmm_rm_of(src, float_size(instr))?,
+                    false,
+                ),
+            (Instruction::Ucomiss(..), [Operand::Xmm(dst), src]) => {
+                self.sse(None, 0x2E, *dst, xmm_rm_of(src, float_size(instr))?, false)
+            }
+            (Instruction::Ucomisd(..), [Operand::Xmm(dst), src]) => self.sse(
+                Some(0x66),
+                0x2E,
+                *dst,
+                xmm_rm_of(src, float_size(instr))?,
+                false,
+            ),
+            (Instruction::Push(_), [arg]) => self.push(arg),
+            (Instruction::Pop(_), [Operand::Reg(reg)]) => {
+                check_stack_reg(reg)?;
+                self.emit_plus_reg(reg.size(), 0x58, *reg);
+                Ok(())
+            }
+            (Instruction::Pop(_), [Operand::Mem(mem)]) => {
+                self.emit_rm(None, &[0x8F], 0, Rm::Mem(mem), false)
+            }
+            (Instruction::Call(_), [target @ Operand::Sym(..)]) => {
+                self.code.push(0xE8);
+                self.rel32(target, RelocKind::Plt32, instr)
+            }
+            (Instruction::Call(_), [arg]) => self.emit_rm(None, &[0xFF], 2, rm_of(arg)?, false),
+            (Instruction::Jmp(_), [target @ Operand::Sym(..)]) => {
+                self.code.push(0xE9);
+                self.rel32(target, RelocKind::Pc32, instr)
+            }
+            (Instruction::Jmp(_), [arg]) => self.emit_rm(None, &[0xFF], 4, rm_of(arg)?, false),
+            (Instruction::Jcc(cond, _), [target @ Operand::Sym(..)]) => {
+                self.code.extend([0x0F, 0x80 + cond.code()]);
+                self.rel32(target, RelocKind::Pc32, instr)
+            }
+            (Instruction::Leave, []) => {
+                self.code.push(0xC9);
+                Ok(())
+            }
+            (Instruction::Ret, []) => {
+                self.code.push(0xC3);
+                Ok(())
+            }
+            (Instruction::Syscall, []) => {
+                self.code.extend([0x0F, 0x05]);
+                Ok(())
+            }
+            (Instruction::Nop, []) => {
+                self.code.push(0x90);
+                Ok(())
+            }
+            _ => Err(format!("Invalid operands for '{}'", instr.mnemonic())),
+        }
+    }
+
+    /// `div` and `idiv` only differ in the opcode extension `n`.
+    /// `not`, `div` and `idiv` share an opcode and take a single operand.
+    fn unary(&mut self, n: u8, arg: &Operand) -> EResult<()> {
+        let rm = rm_of(arg)?;
+        let size = rm
+            .size()
+            .ok_or_else(|| "Operation size not specified".to_owned())?;
+        let opcode = if size == Size::Byte { 0xF6 } else { 0xF7 };
+        self.emit_rm(Some(size), &[opcode], n, rm, false)
+    }
+
+    /// Shifts by an immediate or by `cl`, the only register x86 accepts.
+    fn shift(&mut self, n: u8, dst: &Operand, count: &Operand) -> EResult<()> {
+        let rm = rm_of(dst)?;
+        let size = rm
+            .size()
+            .ok_or_else(|| "Operation size not specified".to_owned())?;
+        let byte = size == Size::Byte;
+
+        match count {
+            Operand::Imm(1) => {
+                self.emit_rm(Some(size), &[if byte { 0xD0 } else { 0xD1 }], n, rm, false)
+            }
+            Operand::Imm(count) => {
+                let count = u8::try_from(*count)
+                    .map_err(|_| "Shift count must be between 0 and 255".to_owned())?;
+                self.emit_rm(Some(size), &[if byte { 0xC0 } else { 0xC1 }], n, rm, false)?;
+                self.code.push(count);
+                Ok(())
+            }
+            Operand::Reg(reg) if *reg == Register::RCX.resized(Size::Byte) => {
+                self.emit_rm(Some(size), &[if byte { 0xD2 } else { 0xD3 }], n, rm, false)
+            }
+            _ => Err("Shift count must be a number or cl".to_owned()),
+        }
+    }
+
+    fn mov(&mut self, dst: &Operand, src: &Operand) -> EResult<()> {
+        if let Some(imm) = self.imm_of(src) {
+            return match dst {
+                Operand::Reg(dst) => self.mov_reg_imm(*dst, &imm),
+                Operand::Mem(mem) => {
+                    let size = mem
+                        .size
+                        .ok_or_else(|| "Operation size not specified".to_owned())?;
+                    let opcode = if size == Size::Byte { 0xC6 } else { 0xC7 };
+                    self.emit_rm(Some(size), &[opcode], 0, Rm::Mem(mem), false)?;
+                    match size {
+                        Size::Qword => self.imm(&imm, Size::Dword, RelocKind::Abs32S),
+                        size => self.imm(&imm, size, RelocKind::Abs32),
+                    }
+                }
+                _ => Err("Invalid operands for 'mov'".to_owned()),
+            };
+        }
+
+        match (dst, src) {
+            (dst, Operand::Reg(src)) => {
+                let rm = rm_of(dst)?;
+                check_size(src.size(), rm.size())?;
+                let opcode = if src.size() == Size::Byte { 0x88 } else { 0x89 };
+                self.emit_rm(Some(src.size()), &[opcode], src.num(), rm, needs_rex(src))
+            }
+            (Operand::Reg(dst), Operand::Mem(mem)) => {
+                check_size(dst.size(), mem.size)?;
+                let opcode = if dst.size() == Size::Byte { 0x8A } else { 0x8B };
+                self.emit_rm(
+                    Some(dst.size()),
+                    &[opcode],
+                    dst.num(),
+                    Rm::Mem(mem),
+                    needs_rex(dst),
+                )
+            }
+            _ => Err("Invalid operands for 'mov'".to_owned()),
+        }
+    }
+
+    fn mov_reg_imm(&mut self, dst: Register, imm: &Imm) -> EResult<()> {
+        match dst.size() {
+            Size::Qword if imm.symbol.is_some() || u32::try_from(imm.value).is_err() => {
+                if imm.symbol.is_none() && i32::try_from(imm.value).is_ok() {
+                    self.emit_rm(Some(Size::Qword), &[0xC7], 0, Rm::Reg(dst), false)?;
+                    self.imm(imm, Size::Dword, RelocKind::Abs32S)
+                } else {
+                    self.emit_plus_reg(Size::Qword, 0xB8, dst);
+                    self.imm(imm, Size::Qword, RelocKind::Abs64)
+                }
+            }
+            // Like NASM, small 64-bit values use the zero extending 32-bit form.
+            Size::Qword => {
+                self.emit_plus_reg(Size::Dword, 0xB8, dst);
+                self.imm(imm, Size::Dword, RelocKind::Abs32)
+            }
+            Size::Byte => {
+                self.emit_plus_reg(Size::Byte, 0xB0, dst);
+                self.imm(imm, Size::Byte, RelocKind::Abs32)
+            }
+            size => {
+                self.emit_plus_reg(size, 0xB8, dst);
+                self.imm(imm, size, RelocKind::Abs32)
+            }
+        }
+    }
+
+    fn alu(&mut self, n: u8, dst: &Operand, src: &Operand) -> EResult<()> {
+        if let Some(imm) = self.imm_of(src) {
+            let rm = rm_of(dst)?;
+            let size = rm
+                .size()
+                .ok_or_else(|| "Operation size not specified".to_owned())?;
+
+            return if size == Size::Byte {
+                self.emit_rm(Some(size), &[0x80], n, rm, false)?;
+                self.imm(&imm, Size::Byte, RelocKind::Abs32)
+            } else if imm.fits_i8() {
+                self.emit_rm(Some(size), &[0x83], n, rm, false)?;
+                self.imm(&imm, Size::Byte, RelocKind::Abs32)
+            } else {
+                self.emit_rm(Some(size), &[0x81], n, rm, false)?;
+                match size {
+                    Size::Word => self.imm(&imm, Size::Word, RelocKind::Abs32),
+                    Size::Qword => self.imm(&imm, Size::Dword, RelocKind::Abs32S),
+                    _ => self.imm(&imm, Size::Dword, RelocKind::Abs32),
+                }
+            };
+        }
+
+        match (dst, src) {
+            (dst, Operand::Reg(src)) => {
+                let rm = rm_of(dst)?;
+                check_size(src.size(), rm.size())?;
+                let opcode = n * 8 + if src.size() == Size::Byte { 0 } else { 1 };
+                self.emit_rm(Some(src.size()), &[opcode], src.num(), rm, needs_rex(src))
+            }
+            (Operand::Reg(dst), Operand::Mem(mem)) => {
+                check_size(dst.size(), mem.size)?;
+                let opcode = n * 8 + if dst.size() == Size::Byte { 2 } else { 3 };
+                self.emit_rm(
+                    Some(dst.size()),
+                    &[opcode],
+                    dst.num(),
+                    Rm::Mem(mem),
+                    needs_rex(dst),
+                )
+            }
+            _ => Err("Invalid operands".to_owned()),
+        }
+    }
+
+    fn imul3(&mut self, dst: Register, src: Rm, value: i64) -> EResult<()> {
+        if dst.size() == Size::Byte {
+            return Err("imul can't use byte registers".to_owned());
+        }
+
+        let imm = Imm {
+            value,
+            symbol: None,
+        };
+        if imm.fits_i8() {
+            self.emit_rm(Some(dst.size()), &[0x6B], dst.num(), src, false)?;
+            self.imm(&imm, Size::Byte, RelocKind::Abs32)
+        } else {
+            self.emit_rm(Some(dst.size()), &[0x69], dst.num(), src, false)?;
+            match dst.size() {
+                Size::Word => self.imm(&imm, Size::Word, RelocKind::Abs32),
+                _ => self.imm(&imm, Size::Dword, RelocKind::Abs32S),
+            }
+        }
+    }
+
+    /// movzx and movsx, `opcode` is the byte-source variant.
+    fn extend(&mut self, opcode: u8, dst: Register, src: &Operand) -> EResult<()> {
+        let rm = rm_of(src)?;
+        let opcode = match rm.size() {
+            Some(Size::Byte) => opcode,
+            Some(Size::Word) => opcode + 1,
+            // movsxd, 32-bit sources only sign extend into 64-bit registers.
+            Some(Size::Dword) if opcode == 0xBE && dst.size() == Size::Qword => {
+                return self.emit_rm(Some(Size::Qword), &[0x63], dst.num(), rm, false);
+            }
+            Some(_) => return Err("Source of an extension must be a byte or word".to_owned()),
+            None => return Err("Operation size not specified".to_owned()),
+        };
+        if dst.size() == Size::Byte {
+            return Err("Destination of an extension can't be a byte".to_owned());
+        }
+
+        self.emit_rm(Some(dst.size()), &[0x0F, opcode], dst.num(), rm, false)
+    }
+
+    /// Encodes an SSE instruction, the mandatory prefix has to come before REX.
+    fn sse(&mut self, prefix: Option<u8>, opcode: u8, reg: u8, rm: Rm, wide: bool) -> EResult<()> {
+        self.code.extend(prefix);
+        let size = wide.then_some(Size::Qword);
+        self.emit_rm(size, &[0x0F, opcode], reg, rm, false)
+    }
+
+    fn push(&mut self, arg: &Operand) -> EResult<()> {
+        if let Some(imm) = self.imm_of(arg) {
+            return if imm.fits_i8() {
+                self.code.push(0x6A);
+                self.imm(&imm, Size::Byte, RelocKind::Abs32)
+            } else {
+                self.code.push(0x68);
+                self.imm(&imm, Size::Dword, RelocKind::Abs32S)
+            };
+        }
+
+        match arg {
+            Operand::Reg(reg) => {
+                check_stack_reg(reg)?;
+                self.emit_plus_reg(reg.size(), 0x50, *reg);
+                Ok(())
+            }
+            Operand::Mem(mem) => self.emit_rm(None, &[0xFF], 6, Rm::Mem(mem), false),
+            _ => Err("Invalid operand for 'push'".to_owned()),
+        }
+    }
+
+    fn rel32(&mut self, target: &Operand, kind: RelocKind, instr: &Instruction) -> EResult<()> {
+        let label = match target {
+            Operand::Sym(label, 0) => self.scoped(label),
+            _ => return Err("Expected a label".to_owned()),
+        };
+
+        self.fixups.push(Fixup {
+            offset: self.code.len(),
+            label,
+            kind,
+            text: instr.to_string(),
+        });
+        self.code.extend([0; 4]);
+        Ok(())
+    }
+
+    fn imm(&mut self, imm: &Imm, size: Size, kind: RelocKind) -> EResult<()> {
+        let bytes = size.bytes() as usize;
+
+        if let Some(symbol) = &imm.symbol {
+            if bytes < 4 {
+                return Err("Symbols need at least a 32-bit immediate".to_owned());
+            }
+            self.relocations.push(Relocation {
+                offset: self.code.len() as u64,
+                symbol: symbol.clone(),
+                kind,
+                addend: imm.value,
+            });
+            self.code.extend(std::iter::repeat_n(0, bytes));
+            return Ok(());
+        }
+
+        let fits = match size {
+            Size::Byte => i8::try_from(imm.value).is_ok() || u8::try_from(imm.value).is_ok(),
+            Size::Word => i16::try_from(imm.value).is_ok() || u16::try_from(imm.value).is_ok(),
+            Size::Dword => i32::try_from(imm.value).is_ok() || u32::try_from(imm.value).is_ok(),
+            Size::Qword => true,
+        };
+        if !fits {
+            return Err(format!("Immediate {} is out of range", imm.value));
+        }
+
+        self.code
+            .extend_from_slice(&imm.value.to_le_bytes()[..bytes]);
+        Ok(())
+    }
+
+    fn emit_prefixes(&mut self, size: Option<Size>, r: u8, x: u8, b: u8, force_rex: bool) {
+        if size == Some(Size::Word) {
+            self.code.push(0x66);
+        }
+
+        let w = (size == Some(Size::Qword)) as u8;
+        let rex = (w << 3) | ((r >> 3) << 2) | ((x >> 3) << 1) | (b >> 3);
+        if rex != 0 || force_rex {
+            self.code.push(0x40 | rex);
+        }
+    }
+
+    /// Encodes instructions with the register in the low bits of the opcode.
+    fn emit_plus_reg(&mut self, size: Size, opcode: u8, reg: Register) {
+        // push and pop are 64-bit by default and don't need REX.W.
+        let size = match opcode {
+            0x50 | 0x58 if size == Size::Qword => None,
+            _ => Some(size),
+        };
+        self.emit_prefixes(size, 0, 0, reg.num(), needs_rex(&reg));
+        self.code.push(opcode + (reg.num() & 7));
+    }
+
+    /// Encodes an instruction with a ModRM byte. `reg` is either a
+    /// register number or an opcode extension.
+    fn emit_rm(
+        &mut self,
+        size: Option<Size>,
+        opcode: &[u8],
+        reg: u8,
+        rm: Rm,
+        force_rex: bool,
+    ) -> EResult<()> {
+        match rm {
+            Rm::Reg(rm) => {
+                self.emit_prefixes(size, reg, 0, rm.num(), force_rex || needs_rex(&rm));
+                self.code.extend_from_slice(opcode);
+                self.code.push(0xC0 | ((reg & 7) << 3) | (rm.num() & 7));
+            }
+            Rm::Mem(mem) => {
+                let index = mem.index.map_or(0, |reg| reg.num());
+                let base = mem.base.map_or(0, |reg| reg.num());
+                self.emit_prefixes(size, reg, index, base, force_rex);
+                self.code.extend_from_slice(opcode);
+                self.modrm_mem(reg & 7, mem);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn modrm_mem(&mut self, reg: u8, mem: &Memory) {
+        let scale = match mem.scale {
+            1 => 0,
+            2 => 1,
+            4 => 2,
+            _ => 3,
+        };
+
+        let base = match mem.base {
+            Some(base) => base.num(),
+            None => {
+                // Absolute or index-only addressing always uses disp32.
+                let index = mem.index.map_or(4, |reg| reg.num() & 7);
+                self.code.push((reg << 3) | 4);
+                self.code.push((scale << 6) | (index << 3) | 5);
+                self.disp32(mem);
+                return;
+            }
+        };
+
+        let modbits = if mem.symbol.is_none() && mem.disp == 0 && base & 7 != 5 {
+            0
+        } else if mem.symbol.is_none() && i8::try_from(mem.disp).is_ok() {
+            1
+        } else {
+            2
+        };
+
+        if mem.index.is_some() || base & 7 == 4 {
+            let index = mem.index.map_or(4, |reg| reg.num() & 7);
+            self.code.push((modbits << 6) | (reg << 3) | 4);
+            self.code.push((scale << 6) | (index << 3) | (base & 7));
+        } else {
+            self.code.push((modbits << 6) | (reg << 3) | (base & 7));
+        }
+
+        match modbits {
+            1 => self.code.push(mem.disp as i8 as u8),
+            2 => self.disp32(mem),
+            _ => {}
+        }
+    }
+
+    fn disp32(&mut self, mem: &Memory) {
+        if let Some(symbol) = &mem.symbol {
+            self.relocations.push(Relocation {
+                offset: self.code.len() as u64,
+                symbol: symbol.clone(),
+                kind: RelocKind::Abs32S,
+                addend: mem.disp,
+            });
+            self.code.extend([0; 4]);
+        } else {
+            self.code.extend((mem.disp as i32).to_le_bytes());
+        }
+    }
+}
+
+/// spl, bpl, sil and dil can only be addressed with a REX prefix.
+fn needs_rex(reg: &Register) -> bool {
+    reg.size() == Size::Byte && (4..8).contains(&reg.num())
+}
+
+fn rm_of(op: &Operand) -> EResult<Rm<'_>> {
+    match op {
+        Operand::Reg(reg) => Ok(Rm::Reg(*reg)),
+        Operand::Mem(mem) => Ok(Rm::Mem(mem)),
+        _ => Err("Expected a register or memory operand".to_owned()),
+    }
+}
+
+/// An SSE register or a memory operand holding a float of `size`.
+fn xmm_rm_of(op: &Operand, size: Size) -> EResult<Rm<'_>> {
+    match op {
+        Operand::Xmm(num) => Ok(Rm::Reg(Register::new(*num, Size::Dword))),
+        Operand::Mem(mem) => {
+            check_size(size, mem.size)?;
+            Ok(Rm::Mem(mem))
+        }
+        _ => Err("Expected an SSE register or memory operand".to_owned()),
+    }
+}
+
+/// Whether the instruction reads a double, `sd` instructions take an F2
+/// prefix where their `ss` forms take F3.
+fn is_double(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Cvtsi2sd(..)
+            | Instruction::Cvttsd2si(..)
+            | Instruction::Cvtsd2ss(..)
+            | Instruction::Addsd(..)
+            | Instruction::Subsd(..)
+            | Instruction::Mulsd(..)
+            | Instruction::Divsd(..)
+            | Instruction::Ucomisd(..)
+    )
+}
+
+fn scalar_prefix(instr: &Instruction) -> u8 {
+    if is_double(instr) {
+        0xF2
+    } else {
+        0xF3
+    }
+}
+
+fn float_size(instr: &Instruction) -> Size {
+    if is_double(instr) {
+        Size::Qword
+    } else {
+        Size::Dword
+    }
+}
+
+fn check_size(size: Size, other: Option<Size>) -> EResult<()> {
+    match other {
+        Some(other) if other != size => Err("Mismatch in operand sizes".to_owned()),
+        _ => Ok(()),
+    }
+}
+
+fn check_stack_reg(reg: &Register) -> EResult<()> {
+    match reg.size() {
+        Size::Qword | Size::Word => Ok(()),
+        _ => Err("Only 64-bit and 16-bit registers can be pushed or popped".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    fn encode(source: &str) -> Result<Assembled, EncodeError> {
+        assemble(&parse(source).unwrap())
+    }
+
+    #[test]
+    fn test_encodings() {
+        let cases: [(&str, &[u8]); 50] = [
+            ("push rbp", &[0x55]),
+            ("push r15", &[0x41, 0x57]),
+            ("mov rbp, rsp", &[0x48, 0x89, 0xE5]),
+            ("sub rsp, 16", &[0x48, 0x83, 0xEC, 0x10]),
+            ("mov eax, [rbp+16]", &[0x8B, 0x45, 0x10]),
+            ("mov dword [rbp-4], 5", &[0xC7, 0x45, 0xFC, 5, 0, 0, 0]),
+            ("mov rsi, [rsp+16]", &[0x48, 0x8B, 0x74, 0x24, 0x10]),
+            ("mov rax, 60", &[0xB8, 0x3C, 0, 0, 0]),
+            ("xor rdi, rdi", &[0x48, 0x31, 0xFF]),
+            ("cmp r15d, 5", &[0x41, 0x83, 0xFF, 0x05]),
+            ("sete al", &[0x0F, 0x94, 0xC0]),
+            ("movzx r15d, al", &[0x44, 0x0F, 0xB6, 0xF8]),
+            ("imul r15d, eax", &[0x44, 0x0F, 0xAF, 0xF8]),
+            ("movsxd rax, ecx", &[0x48, 0x63, 0xC1]),
+            ("movsx ecx, byte [rbp-1]", &[0x0F, 0xBE, 0x4D, 0xFF]),
+            ("movzx eax, word [rbp-2]", &[0x0F, 0xB7, 0x45, 0xFE]),
+            ("movsx r8, word [rbp-2]", &[0x4C, 0x0F, 0xBF, 0x45, 0xFE]),
+            ("mov byte [rbp-1], al", &[0x88, 0x45, 0xFF]),
+            ("movd xmm0, eax", &[0x66, 0x0F, 0x6E, 0xC0]),
+            ("movd r9d, xmm1", &[0x66, 0x41, 0x0F, 0x7E, 0xC9]),
+            ("cvtsi2ss xmm0, r15d", &[0xF3, 0x41, 0x0F, 0x2A, 0xC7]),
+            ("cvtsi2ss xmm1, rax", &[0xF3, 0x48, 0x0F, 0x2A, 0xC8]),
+            ("cvttss2si rcx, xmm0", &[0xF3, 0x48, 0x0F, 0x2C, 0xC8]),
+            ("addss xmm0, xmm1", &[0xF3, 0x0F, 0x58, 0xC1]),
+            ("divss xmm0, dword [rbp-4]", &[0xF3, 0x0F, 0x5E, 0x45, 0xFC]),
+            ("ucomiss xmm0, xmm1", &[0x0F, 0x2E, 0xC1]),
+            ("movq xmm0, rax", &[0x66, 0x48, 0x0F, 0x6E, 0xC0]),
+            ("movq r9, xmm1", &[0x66, 0x49, 0x0F, 0x7E, 0xC9]),
+            ("cvtsi2sd xmm1, rax", &[0xF2, 0x48, 0x0F, 0x2A, 0xC8]),
+            ("cvttsd2si ecx, xmm0", &[0xF2, 0x0F, 0x2C, 0xC8]),
+            ("cvtss2sd xmm0, xmm0", &[0xF3, 0x0F, 0x5A, 0xC0]),
+            ("cvtsd2ss xmm0, xmm0", &[0xF2, 0x0F, 0x5A, 0xC0]),
+            ("addsd xmm0, xmm1", &[0xF2, 0x0F, 0x58, 0xC1]),
+            ("mulsd xmm0, qword [rbp-8]", &[0xF2, 0x0F, 0x59, 0x45, 0xF8]),
+            ("ucomisd xmm0, xmm1", &[0x66, 0x0F, 0x2E, 0xC1]),
+            ("lea rax, [rbp-8]", &[0x48, 0x8D, 0x45, 0xF8]),
+            ("lea r9, [rsp+rcx*8]", &[0x4C, 0x8D, 0x0C, 0xCC]),
+            ("cqo", &[0x48, 0x99]),
+            ("idiv dword [rsp]", &[0xF7, 0x3C, 0x24]),
+            ("div qword [rsp]", &[0x48, 0xF7, 0x34, 0x24]),
+            ("idiv r9d", &[0x41, 0xF7, 0xF9]),
+            ("shl eax, 1", &[0xD1, 0xE0]),
+            ("shl r9, 3", &[0x49, 0xC1, 0xE1, 0x03]),
+            ("sar dword [rbp-4], cl", &[0xD3, 0x7D, 0xFC]),
+            ("shr sil, cl", &[0x40, 0xD2, 0xEE]),
+            ("not rcx", &[0x48, 0xF7, 0xD1]),
+            ("not byte [rsp]", &[0xF6, 0x14, 0x24]),
+            ("leave", &[0xC9]),
+            ("ret", &[0xC3]),
+            ("syscall", &[0x0F, 0x05]),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(encode(line).unwrap().code, expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_jumps() {
+        let source = "\
+main:
+.L0:
+    jmp .L0
+    jne .L1
+.L1:
+    call puts
+";
+        let out = encode(source).unwrap();
+
+        assert_eq!(
+            out.code,
+            [0xE9, 0xFB, 0xFF, 0xFF, 0xFF, 0x0F, 0x85, 0, 0, 0, 0, 0xE8, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            out.relocations,
+            vec![Relocation {
+                offset: 12,
+                symbol: "puts".to_owned(),
+                kind: RelocKind::Plt32,
+                addend: -4,
+            }]
+        );
+        assert_eq!(out.labels[1], ("main.L0".to_owned(), 0));
+    }
+
+    #[test]
+    fn test_symbol_immediate() {
+        let out = encode("push str_0").unwrap();
+
+        assert_eq!(out.code, [0x68, 0, 0, 0, 0]);
+        assert_eq!(out.relocations[0].kind, RelocKind::Abs32S);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(encode("mov [rbp-4], 5").is_err());
+        assert!(encode("mov eax, rbx").is_err());
+        assert!(encode("main:\n    jmp .L9").is_err());
+    }
+}
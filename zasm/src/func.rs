@@ -1,6 +1,11 @@
+use std::fmt;
+
+use crate::instr::{Instruction, Operand, Register};
+
+#[derive(Debug)]
 pub struct Function {
     name: String,
-    text: String,
+    body: Vec<Instruction>,
     reserved: u32,
 }
 
@@ -8,13 +13,13 @@ impl Function {
     pub fn new(name: &str) -> Self {
         Function {
             name: name.to_owned(),
-            text: "".to_owned(),
+            body: vec![],
             reserved: 0,
         }
     }
 
-    pub fn write(&mut self, text: &str) {
-        self.text.push_str(text)
+    pub fn write(&mut self, instructions: &[Instruction]) {
+        self.body.extend_from_slice(instructions)
     }
 
     pub fn name(&self) -> &str {
@@ -24,24 +29,42 @@ impl Function {
     pub fn set_reserved(&mut self, x: u32) {
         self.reserved = x;
     }
-}
 
-impl ToString for Function {
-    fn to_string(&self) -> String {
-        let mut out = self.name.to_owned();
-        out.push_str(":\n");
-        out.push_str("    push rbp\n");
-        out.push_str("    mov rbp, rsp\n");
+    pub fn body(&self) -> &[Instruction] {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Vec<Instruction> {
+        &mut self.body
+    }
+
+    /// The whole function, including its label, prologue and epilogue.
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let rbp = Operand::Reg(Register::RBP);
+        let rsp = Operand::Reg(Register::RSP);
+
+        let mut out = vec![
+            Instruction::Label(self.name.clone()),
+            Instruction::Push(rbp.clone()),
+            Instruction::Mov(rbp, rsp.clone()),
+        ];
 
         if self.reserved > 0 {
-            out.push_str("    sub rsp, ");
-            out.push_str(&self.reserved.to_string());
-            out.push('\n');
+            out.push(Instruction::Sub(rsp, Operand::Imm(self.reserved as i64)));
         }
 
-        out.push_str(&self.text);
-        out.push_str("    leave\n");
-        out.push_str("    ret\n");
+        out.extend_from_slice(&self.body);
+        out.push(Instruction::Leave);
+        out.push(Instruction::Ret);
         out
     }
 }
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instr in self.instructions() {
+            writeln!(f, "{}", instr)?;
+        }
+        Ok(())
+    }
+}
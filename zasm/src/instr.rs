@@ -0,0 +1,457 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Byte,
+    Word,
+    Dword,
+    Qword,
+}
+
+impl Size {
+    pub fn bytes(self) -> u32 {
+        match self {
+            Size::Byte => 1,
+            Size::Word => 2,
+            Size::Dword => 4,
+            Size::Qword => 8,
+        }
+    }
+
+    pub fn from_keyword(word: &str) -> Option<Self> {
+        match word {
+            "byte" => Some(Size::Byte),
+            "word" => Some(Size::Word),
+            "dword" => Some(Size::Dword),
+            "qword" => Some(Size::Qword),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Size::Byte => "byte",
+            Size::Word => "word",
+            Size::Dword => "dword",
+            Size::Qword => "qword",
+        }
+    }
+}
+
+const REGS_64: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+const REGS_32: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d",
+    "r13d", "r14d", "r15d",
+];
+const REGS_16: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w",
+    "r14w", "r15w",
+];
+const REGS_8: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+    "r13b", "r14b", "r15b",
+];
+
+/// A general purpose register, `num` is its x86-64 encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register {
+    num: u8,
+    size: Size,
+}
+
+impl Register {
+    pub const RAX: Register = Register::new(0, Size::Qword);
+    pub const RCX: Register = Register::new(1, Size::Qword);
+    pub const RDX: Register = Register::new(2, Size::Qword);
+    pub const RSP: Register = Register::new(4, Size::Qword);
+    pub const RBP: Register = Register::new(5, Size::Qword);
+    pub const RSI: Register = Register::new(6, Size::Qword);
+    pub const RDI: Register = Register::new(7, Size::Qword);
+    pub const EAX: Register = Register::new(0, Size::Dword);
+    pub const AL: Register = Register::new(0, Size::Byte);
+
+    pub const fn new(num: u8, size: Size) -> Self {
+        Self { num, size }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+
+        [
+            (REGS_64, Size::Qword),
+            (REGS_32, Size::Dword),
+            (REGS_16, Size::Word),
+            (REGS_8, Size::Byte),
+        ]
+        .iter()
+        .find_map(|(names, size)| {
+            names
+                .iter()
+                .position(|reg| *reg == name)
+                .map(|num| Self::new(num as u8, *size))
+        })
+    }
+
+    pub fn num(&self) -> u8 {
+        self.num
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// The same register accessed with a different width, e.g. `eax` -> `rax`.
+    pub fn resized(&self, size: Size) -> Self {
+        Self::new(self.num, size)
+    }
+
+    pub fn name(&self) -> &'static str {
+        let names = match self.size {
+            Size::Byte => &REGS_8,
+            Size::Word => &REGS_16,
+            Size::Dword => &REGS_32,
+            Size::Qword => &REGS_64,
+        };
+        names[self.num as usize]
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A memory operand: `size [base + index*scale + symbol + disp]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Memory {
+    pub size: Option<Size>,
+    pub base: Option<Register>,
+    pub index: Option<Register>,
+    pub scale: u8,
+    pub disp: i64,
+    pub symbol: Option<String>,
+}
+
+impl Memory {
+    pub fn new(base: Register, disp: i64) -> Self {
+        Self {
+            size: None,
+            base: Some(base),
+            index: None,
+            scale: 1,
+            disp,
+            symbol: None,
+        }
+    }
+
+    /// The memory at a label, `[name]`.
+    pub fn label(name: &str) -> Self {
+        Self {
+            size: None,
+            base: None,
+            index: None,
+            scale: 1,
+            disp: 0,
+            symbol: Some(name.to_owned()),
+        }
+    }
+
+    pub fn sized(mut self, size: Size) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Reg(Register),
+    Imm(i64),
+    /// Address of a label or symbol plus an addend.
+    Sym(String, i64),
+    Mem(Memory),
+    /// An SSE register, `xmm0` to `xmm15`.
+    Xmm(u8),
+}
+
+impl Operand {
+    pub fn label(name: &str) -> Self {
+        Operand::Sym(name.to_owned(), 0)
+    }
+
+    /// The operand size, if the operand has one.
+    pub fn size(&self) -> Option<Size> {
+        match self {
+            Operand::Reg(reg) => Some(reg.size()),
+            Operand::Mem(mem) => mem.size,
+            _ => None,
+        }
+    }
+}
+
+/// Condition codes used by `jcc` and `setcc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    O,
+    No,
+    B,
+    Ae,
+    E,
+    Ne,
+    Be,
+    A,
+    S,
+    Ns,
+    P,
+    Np,
+    L,
+    Ge,
+    Le,
+    G,
+}
+
+const CONDITIONS: [(Cond, &str); 16] = [
+    (Cond::O, "o"),
+    (Cond::No, "no"),
+    (Cond::B, "b"),
+    (Cond::Ae, "ae"),
+    (Cond::E, "e"),
+    (Cond::Ne, "ne"),
+    (Cond::Be, "be"),
+    (Cond::A, "a"),
+    (Cond::S, "s"),
+    (Cond::Ns, "ns"),
+    (Cond::P, "p"),
+    (Cond::Np, "np"),
+    (Cond::L, "l"),
+    (Cond::Ge, "ge"),
+    (Cond::Le, "le"),
+    (Cond::G, "g"),
+];
+
+impl Cond {
+    /// The 4-bit condition code used in the opcode.
+    pub fn code(self) -> u8 {
+        CONDITIONS
+            .iter()
+            .position(|(cond, _)| *cond == self)
+            .expect("Every condition is in the table.") as u8
+    }
+
+    pub fn suffix(self) -> &'static str {
+        CONDITIONS[self.code() as usize].1
+    }
+
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        let canonical = match suffix {
+            "c" | "nae" => "b",
+            "nb" | "nc" => "ae",
+            "z" => "e",
+            "nz" => "ne",
+            "na" => "be",
+            "nbe" => "a",
+            "pe" => "p",
+            "po" => "np",
+            "nge" => "l",
+            "nl" => "ge",
+            "ng" => "le",
+            "nle" => "g",
+            other => other,
+        };
+
+        CONDITIONS
+            .iter()
+            .find(|(_, name)| *name == canonical)
+            .map(|(cond, _)| *cond)
+    }
+
+    /// The condition that holds exactly when this one doesn't.
+    pub fn negate(self) -> Self {
+        CONDITIONS[(self.code() ^ 1) as usize].0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Label(String),
+    Comment(String),
+
+    Mov(Operand, Operand),
+    Movzx(Operand, Operand),
+    Movsx(Operand, Operand),
+    /// Loads the address of a memory operand.
+    Lea(Operand, Operand),
+    Add(Operand, Operand),
+    Sub(Operand, Operand),
+    And(Operand, Operand),
+    Or(Operand, Operand),
+    Xor(Operand, Operand),
+    /// Flips every bit.
+    Not(Operand),
+    /// Shifts left by an immediate or `cl`.
+    Shl(Operand, Operand),
+    /// Shifts right, filling in zeros.
+    Shr(Operand, Operand),
+    /// Shifts right, filling in copies of the sign bit.
+    Sar(Operand, Operand),
+    Cmp(Operand, Operand),
+    Imul(Operand, Operand),
+    Imul3(Operand, Operand, i64),
+    /// Unsigned division of edx:eax or rdx:rax, the quotient goes to eax
+    /// and the remainder to edx.
+    Div(Operand),
+    /// Signed division, see `Div`.
+    Idiv(Operand),
+    /// Sign extends eax into edx.
+    Cdq,
+    /// Sign extends rax into rdx.
+    Cqo,
+    Set(Cond, Operand),
+
+    /// Moves 32 bits between a general purpose and an SSE register.
+    Movd(Operand, Operand),
+    Cvtsi2ss(Operand, Operand),
+    Cvttss2si(Operand, Operand),
+    Addss(Operand, Operand),
+    Subss(Operand, Operand),
+    Mulss(Operand, Operand),
+    Divss(Operand, Operand),
+    Ucomiss(Operand, Operand),
+    /// Moves 64 bits between a general purpose and an SSE register.
+    Movq(Operand, Operand),
+    Cvtsi2sd(Operand, Operand),
+    Cvttsd2si(Operand, Operand),
+    Cvtss2sd(Operand, Operand),
+    Cvtsd2ss(Operand, Operand),
+    Addsd(Operand, Operand),
+    Subsd(Operand, Operand),
+    Mulsd(Operand, Operand),
+    Divsd(Operand, Operand),
+    Ucomisd(Operand, Operand),
+
+    Push(Operand),
+    Pop(Operand),
+    Call(Operand),
+    Jmp(Operand),
+    Jcc(Cond, Operand),
+    Leave,
+    Ret,
+    Syscall,
+    Nop,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> String {
+        let name = match self {
+            Instruction::Label(_) | Instruction::Comment(_) => "",
+            Instruction::Mov(..) => "mov",
+            Instruction::Movzx(..) => "movzx",
+            Instruction::Lea(..) => "lea",
+            Instruction::Movsx(_, src) if src.size() == Some(Size::Dword) => "movsxd",
+            Instruction::Movsx(..) => "movsx",
+            Instruction::Add(..) => "add",
+            Instruction::Sub(..) => "sub",
+            Instruction::And(..) => "and",
+            Instruction::Or(..) => "or",
+            Instruction::Xor(..) => "xor",
+            Instruction::Not(_) => "not",
+            Instruction::Shl(..) => "shl",
+            Instruction::Shr(..) => "shr",
+            Instruction::Sar(..) => "sar",
+            Instruction::Cmp(..) => "cmp",
+            Instruction::Imul(..) | Instruction::Imul3(..) => "imul",
+            Instruction::Div(_) => "div",
+            Instruction::Idiv(_) => "idiv",
+            Instruction::Cdq => "cdq",
+            Instruction::Cqo => "cqo",
+            Instruction::Set(cond, _) => return format!("set{}", cond.suffix()),
+            Instruction::Movd(..) => "movd",
+            Instruction::Cvtsi2ss(..) => "cvtsi2ss",
+            Instruction::Cvttss2si(..) => "cvttss2si",
+            Instruction::Addss(..) => "addss",
+            Instruction::Subss(..) => "subss",
+            Instruction::Mulss(..) => "mulss",
+            Instruction::Divss(..) => "divss",
+            Instruction::Ucomiss(..) => "ucomiss",
+            Instruction::Movq(..) => "movq",
+            Instruction::Cvtsi2sd(..) => "cvtsi2sd",
+            Instruction::Cvttsd2si(..) => "cvttsd2si",
+            Instruction::Cvtss2sd(..) => "cvtss2sd",
+            Instruction::Cvtsd2ss(..) => "cvtsd2ss",
+            Instruction::Addsd(..) => "addsd",
+            Instruction::Subsd(..) => "subsd",
+            Instruction::Mulsd(..) => "mulsd",
+            Instruction::Divsd(..) => "divsd",
+            Instruction::Ucomisd(..) => "ucomisd",
+            Instruction::Push(_) => "push",
+            Instruction::Pop(_) => "pop",
+            Instruction::Call(_) => "call",
+            Instruction::Jmp(_) => "jmp",
+            Instruction::Jcc(cond, _) => return format!("j{}", cond.suffix()),
+            Instruction::Leave => "leave",
+            Instruction::Ret => "ret",
+            Instruction::Syscall => "syscall",
+            Instruction::Nop => "nop",
+        };
+        name.to_owned()
+    }
+
+    /// Operands in Intel order (destination first).
+    pub fn operands(&self) -> Vec<Operand> {
+        match self {
+            Instruction::Mov(a, b)
+            | Instruction::Movzx(a, b)
+            | Instruction::Lea(a, b)
+            | Instruction::Movsx(a, b)
+            | Instruction::Add(a, b)
+            | Instruction::Sub(a, b)
+            | Instruction::And(a, b)
+            | Instruction::Or(a, b)
+            | Instruction::Xor(a, b)
+            | Instruction::Shl(a, b)
+            | Instruction::Shr(a, b)
+            | Instruction::Sar(a, b)
+            | Instruction::Cmp(a, b)
+            | Instruction::Imul(a, b)
+            | Instruction::Movd(a, b)
+            | Instruction::Cvtsi2ss(a, b)
+            | Instruction::Cvttss2si(a, b)
+            | Instruction::Addss(a, b)
+            | Instruction::Subss(a, b)
+            | Instruction::Mulss(a, b)
+            | Instruction::Divss(a, b)
+            | Instruction::Ucomiss(a, b)
+            | Instruction::Movq(a, b)
+            | Instruction::Cvtsi2sd(a, b)
+            | Instruction::Cvttsd2si(a, b)
+            | Instruction::Cvtss2sd(a, b)
+            | Instruction::Cvtsd2ss(a, b)
+            | Instruction::Addsd(a, b)
+            | Instruction::Subsd(a, b)
+            | Instruction::Mulsd(a, b)
+            | Instruction::Divsd(a, b)
+            | Instruction::Ucomisd(a, b) => vec![a.clone(), b.clone()],
+            Instruction::Imul3(a, b, imm) => vec![a.clone(), b.clone(), Operand::Imm(*imm)],
+            Instruction::Set(_, a)
+            | Instruction::Not(a)
+            | Instruction::Div(a)
+            | Instruction::Idiv(a)
+            | Instruction::Push(a)
+            | Instruction::Pop(a)
+            | Instruction::Call(a)
+            | Instruction::Jmp(a)
+            | Instruction::Jcc(_, a) => vec![a.clone()],
+            Instruction::Label(_)
+            | Instruction::Comment(_)
+            | Instruction::Cdq
+            | Instruction::Cqo
+            | Instruction::Leave
+            | Instruction::Ret
+            | Instruction::Syscall
+            | Instruction::Nop => vec![],
+        }
+    }
+}
@@ -1,21 +1,31 @@
 use builder::Reg;
 use func::Function;
+use instr::Instruction;
 
 pub mod builder;
 pub mod constants;
+pub mod elf;
+pub mod encoder;
 pub mod func;
+pub mod instr;
+pub mod parse;
+pub mod peephole;
+pub mod printer;
 pub mod types;
 
 pub struct Builder {
-    buffer: String,
+    buffer: Vec<Instruction>,
     registers: Vec<Reg>,
     offset: i32,
     reserved: u32,
     label_count: u32,
 }
 
+#[derive(Debug)]
 pub struct Module<'guard> {
     globals: Vec<&'guard str>,
-    strings: Vec<String>,
+    strings: Vec<Vec<u8>>,
     functions: Vec<Function>,
+    main_args: bool,
+    main_returns: bool,
 }
@@ -0,0 +1,361 @@
+use std::fmt;
+
+use crate::instr::{Cond, Instruction, Memory, Operand, Register, Size};
+
+/// An error produced while parsing a line of assembly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub text: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} (`{}`)", self.line, self.message, self.text)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type PResult<T> = Result<T, String>;
+
+/// Parses NASM-style assembly text into instructions. Comments
+/// and empty lines are dropped.
+pub fn parse(source: &str) -> Result<Vec<Instruction>, ParseError> {
+    let mut out = vec![];
+
+    for (i, line) in source.lines().enumerate() {
+        let instr = parse_line(line).map_err(|message| ParseError {
+            line: i + 1,
+            text: line.trim().to_owned(),
+            message,
+        })?;
+        out.extend(instr);
+    }
+
+    Ok(out)
+}
+
+fn parse_line(line: &str) -> PResult<Option<Instruction>> {
+    let line = match line.find(';') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+    .trim();
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(label) = line.strip_suffix(':') {
+        let label = label.trim();
+        if !is_symbol(label) {
+            return Err(format!("Invalid label '{}'", label));
+        }
+        return Ok(Some(Instruction::Label(label.to_owned())));
+    }
+
+    let (mnemonic, rest) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+    let mnemonic = mnemonic.to_lowercase();
+
+    let mut args = if rest.is_empty() {
+        vec![]
+    } else {
+        rest.split(',')
+            .map(|arg| parse_operand(arg.trim()))
+            .collect::<PResult<Vec<_>>>()?
+    };
+
+    if let Some(cond) = mnemonic.strip_prefix("set").and_then(Cond::from_suffix) {
+        let [arg] = take::<1>(&mut args, &mnemonic)?;
+        return Ok(Some(Instruction::Set(cond, arg)));
+    }
+
+    if mnemonic != "jmp" {
+        if let Some(cond) = mnemonic.strip_prefix('j').and_then(Cond::from_suffix) {
+            let [arg] = take::<1>(&mut args, &mnemonic)?;
+            return Ok(Some(Instruction::Jcc(cond, arg)));
+        }
+    }
+
+    let instr = match mnemonic.as_str() {
+        "mov" | "movzx" | "movsx" | "movsxd" | "lea" | "add" | "sub" | "and" | "or" | "xor"
+        | "shl" | "sal" | "shr" | "sar" | "cmp" | "movd" | "cvtsi2ss" | "cvttss2si" | "addss"
+        | "subss" | "mulss" | "divss" | "ucomiss" | "movq" | "cvtsi2sd" | "cvttsd2si"
+        | "cvtss2sd" | "cvtsd2ss" | "addsd" | "subsd" | "mulsd" | "divsd" | "ucomisd" => {
+            let [a, b] = take::<2>(&mut args, &mnemonic)?;
+            match mnemonic.as_str() {
+                "mov" => Instruction::Mov(a, b),
+                "movd" => Instruction::Movd(a, b),
+                "cvtsi2ss" => Instruction::Cvtsi2ss(a, b),
+                "cvttss2si" => Instruction::Cvttss2si(a, b),
+                "addss" => Instruction::Addss(a, b),
+                "subss" => Instruction::Subss(a, b),
+                "mulss" => Instruction::Mulss(a, b),
+                "divss" => Instruction::Divss(a, b),
+                "ucomiss" => Instruction::Ucomiss(a, b),
+                "movq" => Instruction::Movq(a, b),
+                "cvtsi2sd" => Instruction::Cvtsi2sd(a, b),
+                "cvttsd2si" => Instruction::Cvttsd2si(a, b),
+                "cvtss2sd" => Instruction::Cvtss2sd(a, b),
+                "cvtsd2ss" => Instruction::Cvtsd2ss(a, b),
+                "addsd" => Instruction::Addsd(a, b),
+                "subsd" => Instruction::Subsd(a, b),
+                "mulsd" => Instruction::Mulsd(a, b),
+                "divsd" => Instruction::Divsd(a, b),
+                "ucomisd" => Instruction::Ucomisd(a, b),
+                "movzx" => Instruction::Movzx(a, b),
+                "lea" => Instruction::Lea(a, b),
+                "movsx" | "movsxd" => Instruction::Movsx(a, b),
+                "add" => Instruction::Add(a, b),
+                "sub" => Instruction::Sub(a, b),
+                "and" => Instruction::And(a, b),
+                "or" => Instruction::Or(a, b),
+                "xor" => Instruction::Xor(a, b),
+                "shl" | "sal" => Instruction::Shl(a, b),
+                "shr" => Instruction::Shr(a, b),
+                "sar" => Instruction::Sar(a, b),
+                _ => Instruction::Cmp(a, b),
+            }
+        }
+        "imul" => match args.len() {
+            3 => {
+                let [a, b, c] = take::<3>(&mut args, &mnemonic)?;
+                match c {
+                    Operand::Imm(imm) => Instruction::Imul3(a, b, imm),
+                    _ => return Err("Third operand of imul must be a number".to_owned()),
+                }
+            }
+            _ => match take::<2>(&mut args, &mnemonic)? {
+                [a, Operand::Imm(imm)] => Instruction::Imul3(a.clone(), a, imm),
+                [a, b] => Instruction::Imul(a, b),
+            },
+        },
+        "push" | "pop" | "call" | "jmp" | "not" | "div" | "idiv" => {
+            let [a] = take::<1>(&mut args, &mnemonic)?;
+            match mnemonic.as_str() {
+                "push" => Instruction::Push(a),
+                "pop" => Instruction::Pop(a),
+                "call" => Instruction::Call(a),
+                "not" => Instruction::Not(a),
+                "div" => Instruction::Div(a),
+                "idiv" => Instruction::Idiv(a),
+                _ => Instruction::Jmp(a),
+            }
+        }
+        "leave" | "ret" | "syscall" | "nop" | "cdq" | "cqo" => {
+            take::<0>(&mut args, &mnemonic)?;
+            match mnemonic.as_str() {
+                "leave" => Instruction::Leave,
+                "cdq" => Instruction::Cdq,
+                "cqo" => Instruction::Cqo,
+                "ret" => Instruction::Ret,
+                "syscall" => Instruction::Syscall,
+                _ => Instruction::Nop,
+            }
+        }
+        _ => return Err(format!("Unsupported instruction '{}'", mnemonic)),
+    };
+
+    Ok(Some(instr))
+}
+
+fn take<const N: usize>(args: &mut Vec<Operand>, mnemonic: &str) -> PResult<[Operand; N]> {
+    std::mem::take(args)
+        .try_into()
+        .map_err(|_| format!("'{}' takes {} operand(s)", mnemonic, N))
+}
+
+pub fn parse_operand(text: &str) -> PResult<Operand> {
+    let (size, text) = match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => match Size::from_keyword(&word.to_lowercase()) {
+            Some(size) => (Some(size), rest.trim()),
+            None => (None, text),
+        },
+        None => (None, text),
+    };
+
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or_else(|| "Missing ']'".to_owned())?;
+        return Ok(Operand::Mem(parse_mem(inner, size)?));
+    }
+
+    if let Some(num) = xmm_number(text) {
+        return Ok(Operand::Xmm(num));
+    }
+
+    if let Some(reg) = Register::from_name(text) {
+        if size.is_some() {
+            return Err("Size specifiers aren't allowed on registers".to_owned());
+        }
+        return Ok(Operand::Reg(reg));
+    }
+
+    let mut value = 0;
+    let mut symbol = None;
+    for (sign, term) in split_terms(text)? {
+        match parse_number(term) {
+            Some(number) => value += sign * number,
+            None if sign > 0 && symbol.is_none() && is_symbol(term) => {
+                symbol = Some(term.to_owned())
+            }
+            None => return Err(format!("Invalid operand '{}'", text)),
+        }
+    }
+
+    Ok(match symbol {
+        Some(symbol) => Operand::Sym(symbol, value),
+        None => Operand::Imm(value),
+    })
+}
+
+fn xmm_number(text: &str) -> Option<u8> {
+    let num = text.to_lowercase().strip_prefix("xmm")?.parse().ok()?;
+    (num < 16).then_some(num)
+}
+
+fn parse_mem(text: &str, size: Option<Size>) -> PResult<Memory> {
+    let mut mem = Memory {
+        size,
+        base: None,
+        index: None,
+        scale: 1,
+        disp: 0,
+        symbol: None,
+    };
+
+    for (sign, term) in split_terms(text)? {
+        if let Some((left, right)) = term.split_once('*') {
+            let (left, right) = (left.trim(), right.trim());
+            let (reg, scale) = match (Register::from_name(left), Register::from_name(right)) {
+                (Some(reg), None) => (reg, right),
+                (None, Some(reg)) => (reg, left),
+                _ => return Err(format!("Invalid index '{}'", term)),
+            };
+            let scale = match parse_number(scale) {
+                Some(scale @ (1 | 2 | 4 | 8)) => scale as u8,
+                _ => return Err(format!("Invalid scale '{}'", scale)),
+            };
+            if sign < 0 || mem.index.is_some() {
+                return Err(format!("Invalid index '{}'", term));
+            }
+            mem.index = Some(reg);
+            mem.scale = scale;
+        } else if let Some(reg) = Register::from_name(term) {
+            if sign < 0 {
+                return Err(format!("Can't subtract register '{}'", term));
+            }
+            if mem.base.is_none() {
+                mem.base = Some(reg);
+            } else if mem.index.is_none() {
+                mem.index = Some(reg);
+            } else {
+                return Err("Too many registers in memory operand".to_owned());
+            }
+        } else if let Some(value) = parse_number(term) {
+            mem.disp += sign * value;
+        } else if sign > 0 && mem.symbol.is_none() && is_symbol(term) {
+            mem.symbol = Some(term.to_owned());
+        } else {
+            return Err(format!("Invalid memory operand '{}'", text));
+        }
+    }
+
+    Ok(mem)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text = text.replace('_', "");
+    if let Some(hex) = text.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16).ok().map(|x| x as i64);
+    }
+    if let Some(bin) = text.strip_prefix("0b") {
+        return u64::from_str_radix(bin, 2).ok().map(|x| x as i64);
+    }
+    text.parse().ok()
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' || ch == '.' => {}
+        _ => return false,
+    }
+    chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.')
+}
+
+/// Splits `a+b-c` into signed terms.
+fn split_terms(text: &str) -> PResult<Vec<(i64, &str)>> {
+    let invalid = || format!("Invalid expression '{}'", text);
+    let mut terms = vec![];
+    let mut sign = 1;
+    let mut start = 0;
+
+    for (i, ch) in text.char_indices() {
+        if ch != '+' && ch != '-' {
+            continue;
+        }
+
+        let term = text[start..i].trim();
+        if !term.is_empty() {
+            terms.push((sign, term));
+            sign = 1;
+        } else if !terms.is_empty() || sign < 0 {
+            return Err(invalid());
+        }
+
+        if ch == '-' {
+            sign = -1;
+        }
+        start = i + 1;
+    }
+
+    let term = text[start..].trim();
+    if term.is_empty() {
+        return Err(invalid());
+    }
+    terms.push((sign, term));
+
+    Ok(terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let source = "\
+main:
+    mov dword [rbp-4], 5
+    mov rsi, [rsp+16]
+    imul r15d, eax
+    sete al
+    jne .L1
+    push str_0
+    mov eax, [rbx+rcx*4+8]
+    cvtsi2ss xmm1, rax
+    movd eax, xmm0
+";
+        let printed = parse(source)
+            .unwrap()
+            .iter()
+            .map(|instr| format!("{}\n", instr))
+            .collect::<String>();
+
+        assert_eq!(printed, source);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("mov eax").is_err());
+        assert!(parse("frobnicate eax").is_err());
+        assert!(parse("mov eax, [rbp-rbx]").is_err());
+    }
+}
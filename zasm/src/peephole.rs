@@ -0,0 +1,312 @@
+use crate::instr::{Instruction, Memory, Operand, Register, Size};
+
+/// The highest optimization level that enables anything new.
+pub const MAX_LEVEL: u8 = 2;
+
+/// Rewrites a function body in place until no pass changes it anymore.
+///
+/// Level 0 leaves the code untouched, level 1 removes instructions that
+/// have no effect and level 2 also forwards stores to the loads after them
+/// and pushes loaded qwords straight from memory.
+pub fn optimize(body: &mut Vec<Instruction>, level: u8) {
+    if level == 0 {
+        return;
+    }
+
+    loop {
+        let mut changed = remove_noops(body);
+        changed |= remove_jumps_to_next(body);
+        if level >= 2 {
+            changed |= forward_stores(body);
+            changed |= push_loads(body);
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// Only 64-bit moves to the same register do nothing, `mov eax, eax`
+/// clears the upper half of `rax` and is kept.
+fn is_noop(instr: &Instruction) -> bool {
+    match instr {
+        Instruction::Mov(Operand::Reg(a), Operand::Reg(b)) => a == b && a.size() == Size::Qword,
+        Instruction::Add(Operand::Reg(reg), Operand::Imm(0))
+        | Instruction::Sub(Operand::Reg(reg), Operand::Imm(0)) => *reg == Register::RSP,
+        _ => false,
+    }
+}
+
+fn remove_noops(body: &mut Vec<Instruction>) -> bool {
+    let len = body.len();
+    body.retain(|instr| !is_noop(instr));
+    body.len() != len
+}
+
+/// Removes `jmp .L1` and `jcc .L1` when `.L1:` is among the labels
+/// directly following the jump.
+fn remove_jumps_to_next(body: &mut Vec<Instruction>) -> bool {
+    let mut i = 0;
+    let mut changed = false;
+
+    while i < body.len() {
+        let target = match &body[i] {
+            Instruction::Jmp(Operand::Sym(label, 0))
+            | Instruction::Jcc(_, Operand::Sym(label, 0)) => label,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let falls_through = body[i + 1..]
+            .iter()
+            .take_while(|instr| matches!(instr, Instruction::Label(_) | Instruction::Comment(_)))
+            .any(|instr| matches!(instr, Instruction::Label(name) if name == target));
+
+        if falls_through {
+            body.remove(i);
+            changed = true;
+        } else {
+            i += 1;
+        }
+    }
+
+    changed
+}
+
+/// Both operands address the same memory, regardless of the access size.
+fn same_slot(a: &Memory, b: &Memory) -> bool {
+    a.base == b.base
+        && a.index == b.index
+        && a.scale == b.scale
+        && a.disp == b.disp
+        && a.symbol == b.symbol
+}
+
+fn uses_register(mem: &Memory, reg: Register) -> bool {
+    [mem.base, mem.index]
+        .iter()
+        .flatten()
+        .any(|used| used.num() == reg.num())
+}
+
+/// Replaces a load from a slot that was just stored to with the stored
+/// value, and drops a store of a value that was just loaded from the slot.
+fn forward_stores(body: &mut Vec<Instruction>) -> bool {
+    let mut i = 0;
+    let mut changed = false;
+
+    while i + 1 < body.len() {
+        match (&body[i], &body[i + 1]) {
+            (
+                Instruction::Mov(Operand::Mem(stored), value),
+                Instruction::Mov(Operand::Reg(reg), Operand::Mem(loaded)),
+            ) if same_slot(stored, loaded) => {
+                let size = match value {
+                    Operand::Reg(value) => Some(value.size()),
+                    _ => stored.size,
+                };
+
+                if size == Some(reg.size()) && loaded.size.is_none_or(|size| size == reg.size()) {
+                    body[i + 1] = Instruction::Mov(Operand::Reg(*reg), value.clone());
+                    changed = true;
+                }
+            }
+            (
+                Instruction::Mov(Operand::Reg(reg), Operand::Mem(loaded)),
+                Instruction::Mov(Operand::Mem(stored), Operand::Reg(value)),
+            ) if same_slot(stored, loaded) && reg == value && !uses_register(loaded, *reg) => {
+                body.remove(i + 1);
+                changed = true;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    changed
+}
+
+fn reads_register(operand: &Operand, reg: Register) -> bool {
+    match operand {
+        Operand::Reg(used) => used.num() == reg.num(),
+        Operand::Mem(mem) => uses_register(mem, reg),
+        _ => false,
+    }
+}
+
+/// The value in `reg` is replaced before anything in `rest` reads it. Only
+/// straight line code is followed, anything unclear counts as a read.
+fn is_overwritten(rest: &[Instruction], reg: Register) -> bool {
+    for instr in rest {
+        match instr {
+            Instruction::Comment(_) => {}
+            Instruction::Mov(Operand::Reg(dst), src)
+                if dst.num() == reg.num() && matches!(dst.size(), Size::Dword | Size::Qword) =>
+            {
+                return !reads_register(src, reg);
+            }
+            Instruction::Pop(Operand::Reg(dst)) if dst.num() == reg.num() => return true,
+            // Calls return their value in `rax`.
+            Instruction::Call(target) if reg.num() == Register::RAX.num() => {
+                return !reads_register(target, reg);
+            }
+            Instruction::Label(_)
+            | Instruction::Jmp(_)
+            | Instruction::Jcc(..)
+            | Instruction::Call(_)
+            | Instruction::Ret
+            | Instruction::Leave
+            | Instruction::Syscall
+            | Instruction::Div(_)
+            | Instruction::Idiv(_)
+            | Instruction::Cdq
+            | Instruction::Cqo => return false,
+            instr
+                if instr
+                    .operands()
+                    .iter()
+                    .any(|operand| reads_register(operand, reg)) =>
+            {
+                return false
+            }
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Turns `mov rax, [rbp-8]` followed by `push rax` into `push qword [rbp-8]`
+/// when `rax` isn't needed afterwards.
+///
+/// Smaller loads are kept, so the `mov r15d, [rbp-4]` and `push r15` the
+/// builder emits for every 32-bit argument stay as they are. `push` has no
+/// dword form, and `push qword [rbp-4]` would push the 4 bytes above the
+/// slot where the load zero extends.
+fn push_loads(body: &mut Vec<Instruction>) -> bool {
+    let mut i = 0;
+    let mut changed = false;
+
+    while i + 1 < body.len() {
+        if let (
+            Instruction::Mov(Operand::Reg(reg), Operand::Mem(loaded)),
+            Instruction::Push(Operand::Reg(pushed)),
+        ) = (&body[i], &body[i + 1])
+        {
+            if reg == pushed
+                && reg.size() == Size::Qword
+                && loaded.size.is_none_or(|size| size == Size::Qword)
+                && is_overwritten(&body[i + 2..], *reg)
+            {
+                body[i] = Instruction::Push(Operand::Mem(loaded.clone().sized(Size::Qword)));
+                body.remove(i + 1);
+                changed = true;
+            }
+        }
+        i += 1;
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    fn optimized(source: &str, level: u8) -> String {
+        let mut body = parse(source).unwrap();
+        optimize(&mut body, level);
+        body.iter().map(|instr| format!("{}\n", instr)).collect()
+    }
+
+    #[test]
+    fn test_noops() {
+        let source = "    mov rax, rax
+    mov eax, eax
+    call foo
+    add rsp, 0
+    jmp .L0
+.L1:
+.L0:
+    leave
+";
+        assert_eq!(optimized(source, 0), source);
+        assert_eq!(
+            optimized(source, 1),
+            "    mov eax, eax\n    call foo\n.L1:\n.L0:\n    leave\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_jumps() {
+        let source = "    jne .L1
+    mov eax, 1
+.L1:
+    add rsp, 8
+";
+        assert_eq!(optimized(source, 2), source);
+    }
+
+    #[test]
+    fn test_forward_stores() {
+        let source = "    mov [rbp-4], eax
+    mov ecx, [rbp-4]
+    mov dword [rbp-8], 5
+    mov edx, [rbp-8]
+    mov esi, [rbp-12]
+    mov [rbp-12], esi
+    mov word [rbp-16], 1
+    mov edi, [rbp-16]
+";
+        let expected = "    mov [rbp-4], eax
+    mov ecx, eax
+    mov dword [rbp-8], 5
+    mov edx, 5
+    mov esi, [rbp-12]
+    mov word [rbp-16], 1
+    mov edi, [rbp-16]
+";
+        assert_eq!(optimized(source, 1), source);
+        assert_eq!(optimized(source, 2), expected);
+    }
+
+    #[test]
+    fn test_push_loads() {
+        let source = "    mov rax, qword [rbp-16]
+    push rax
+    mov eax, dword [rbp-4]
+    push rax
+    call f
+    mov rcx, [rbp-8]
+    push rcx
+    add eax, ecx
+    mov rdx, [rbp-24]
+    push rdx
+    mov rdx, [rdx]
+    mov r15d, [rbp-4]
+    push r15
+    call g
+";
+        let expected = "    push qword [rbp-16]
+    mov eax, dword [rbp-4]
+    push rax
+    call f
+    mov rcx, [rbp-8]
+    push rcx
+    add eax, ecx
+    mov rdx, [rbp-24]
+    push rdx
+    mov rdx, [rdx]
+    mov r15d, [rbp-4]
+    push r15
+    call g
+";
+        assert_eq!(optimized(source, 1), source);
+        assert_eq!(optimized(source, 2), expected);
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+
+use crate::instr::{Instruction, Memory, Operand, Size};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// NASM flavoured Intel syntax.
+    #[default]
+    Nasm,
+    /// GNU as flavoured AT&T syntax.
+    Gas,
+}
+
+/// Prints a single instruction, indented like the rest of the output.
+pub fn print(instr: &Instruction, syntax: Syntax) -> String {
+    match instr {
+        Instruction::Label(name) => format!("{}:", name),
+        Instruction::Comment(text) => match syntax {
+            Syntax::Nasm => format!("    ; {}", text),
+            Syntax::Gas => format!("    # {}", text),
+        },
+        _ => match syntax {
+            Syntax::Nasm => print_nasm(instr),
+            Syntax::Gas => print_gas(instr),
+        },
+    }
+}
+
+/// Prints a directive defining the bytes. Printable ASCII is kept readable
+/// in NASM, everything else (quotes, control characters, UTF-8) is written
+/// as numbers since NASM strings have no escapes.
+pub fn print_bytes(bytes: &[u8], syntax: Syntax) -> String {
+    if syntax == Syntax::Gas {
+        let bytes = bytes
+            .iter()
+            .map(|byte| byte.to_string())
+            .collect::<Vec<_>>();
+        return format!(".byte {}", bytes.join(","));
+    }
+
+    let mut parts = vec![];
+    let mut run = String::new();
+    for &byte in bytes {
+        if (b' '..=b'~').contains(&byte) && byte != b'"' {
+            run.push(byte as char);
+            continue;
+        }
+
+        if !run.is_empty() {
+            parts.push(format!("\"{}\"", run));
+            run.clear();
+        }
+        parts.push(byte.to_string());
+    }
+    if !run.is_empty() {
+        parts.push(format!("\"{}\"", run));
+    }
+
+    format!("db {}", parts.join(", "))
+}
+
+fn print_nasm(instr: &Instruction) -> String {
+    let operands = instr
+        .operands()
+        .iter()
+        .map(|op| op.to_string())
+        .collect::<Vec<_>>();
+
+    if operands.is_empty() {
+        format!("    {}", instr.mnemonic())
+    } else {
+        format!("    {} {}", instr.mnemonic(), operands.join(", "))
+    }
+}
+
+impl fmt::Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(size) = self.size {
+            write!(f, "{} ", size.keyword())?;
+        }
+
+        let mut terms = vec![];
+        if let Some(base) = self.base {
+            terms.push(base.to_string());
+        }
+        if let Some(index) = self.index {
+            if self.scale == 1 {
+                terms.push(index.to_string());
+            } else {
+                terms.push(format!("{}*{}", index, self.scale));
+            }
+        }
+        if let Some(symbol) = &self.symbol {
+            terms.push(symbol.clone());
+        }
+
+        let mut out = terms.join("+");
+        if self.disp < 0 {
+            out.push_str(&self.disp.to_string());
+        } else if self.disp > 0 || out.is_empty() {
+            if !out.is_empty() {
+                out.push('+');
+            }
+            out.push_str(&self.disp.to_string());
+        }
+
+        write!(f, "[{}]", out)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => write!(f, "{}", reg),
+            Operand::Imm(value) => write!(f, "{}", value),
+            Operand::Sym(name, addend) => write!(f, "{}", symbol(name, *addend)),
+            Operand::Mem(mem) => write!(f, "{}", mem),
+            Operand::Xmm(num) => write!(f, "xmm{}", num),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", print(self, Syntax::Nasm))
+    }
+}
+
+fn symbol(name: &str, addend: i64) -> String {
+    match addend {
+        0 => name.to_owned(),
+        x if x < 0 => format!("{}{}", name, x),
+        x => format!("{}+{}", name, x),
+    }
+}
+
+fn print_gas(instr: &Instruction) -> String {
+    let operands = instr.operands();
+
+    let mnemonic = match instr {
+        Instruction::Movzx(dst, src) | Instruction::Movsx(dst, src) => {
+            let kind = if matches!(instr, Instruction::Movzx(..)) {
+                "movz"
+            } else {
+                "movs"
+            };
+            format!(
+                "{}{}{}",
+                kind,
+                suffix(src.size().unwrap_or(Size::Byte)),
+                suffix(dst.size().unwrap_or(Size::Dword))
+            )
+        }
+        Instruction::Cdq => "cltd".to_owned(),
+        Instruction::Cqo => "cqto".to_owned(),
+        // Only integer sources in memory need a size.
+        Instruction::Cvtsi2ss(_, Operand::Mem(mem))
+        | Instruction::Cvtsi2sd(_, Operand::Mem(mem)) => {
+            format!(
+                "{}{}",
+                instr.mnemonic(),
+                suffix(mem.size.unwrap_or(Size::Dword))
+            )
+        }
+        // Branches, setcc and SSE instructions don't take a size suffix.
+        Instruction::Set(..)
+        | Instruction::Movd(..)
+        | Instruction::Cvtsi2ss(..)
+        | Instruction::Cvttss2si(..)
+        | Instruction::Addss(..)
+        | Instruction::Subss(..)
+        | Instruction::Mulss(..)
+        | Instruction::Divss(..)
+        | Instruction::Ucomiss(..)
+        | Instruction::Movq(..)
+        | Instruction::Cvtsi2sd(..)
+        | Instruction::Cvttsd2si(..)
+        | Instruction::Cvtss2sd(..)
+        | Instruction::Cvtsd2ss(..)
+        | Instruction::Addsd(..)
+        | Instruction::Subsd(..)
+        | Instruction::Mulsd(..)
+        | Instruction::Divsd(..)
+        | Instruction::Ucomisd(..)
+        | Instruction::Call(_)
+        | Instruction::Jmp(_)
+        | Instruction::Jcc(..)
+        | Instruction::Leave
+        | Instruction::Ret
+        | Instruction::Syscall
+        | Instruction::Nop => instr.mnemonic(),
+        _ => {
+            let size = operands.iter().find_map(|op| op.size());
+            match (instr, size) {
+                (Instruction::Push(_) | Instruction::Pop(_), None) => {
+                    format!("{}q", instr.mnemonic())
+                }
+                (_, Some(size)) => format!("{}{}", instr.mnemonic(), suffix(size)),
+                (_, None) => instr.mnemonic(),
+            }
+        }
+    };
+
+    let branch = matches!(
+        instr,
+        Instruction::Call(_) | Instruction::Jmp(_) | Instruction::Jcc(..)
+    );
+    let operands = operands
+        .iter()
+        .rev()
+        .map(|op| gas_operand(op, branch))
+        .collect::<Vec<_>>();
+
+    if operands.is_empty() {
+        format!("    {}", mnemonic)
+    } else {
+        format!("    {} {}", mnemonic, operands.join(", "))
+    }
+}
+
+fn suffix(size: Size) -> char {
+    match size {
+        Size::Byte => 'b',
+        Size::Word => 'w',
+        Size::Dword => 'l',
+        Size::Qword => 'q',
+    }
+}
+
+fn gas_operand(op: &Operand, branch: bool) -> String {
+    let indirect = if branch { "*" } else { "" };
+
+    match op {
+        Operand::Reg(reg) => format!("{}%{}", indirect, reg),
+        Operand::Xmm(num) => format!("%xmm{}", num),
+        Operand::Imm(value) => format!("${}", value),
+        Operand::Sym(name, addend) if branch => symbol(name, *addend),
+        Operand::Sym(name, addend) => format!("${}", symbol(name, *addend)),
+        Operand::Mem(mem) => {
+            let mut out = String::from(indirect);
+            match &mem.symbol {
+                Some(name) => out.push_str(&symbol(name, mem.disp)),
+                None if mem.disp != 0 || (mem.base.is_none() && mem.index.is_none()) => {
+                    out.push_str(&mem.disp.to_string())
+                }
+                None => {}
+            }
+
+            if mem.base.is_some() || mem.index.is_some() {
+                out.push('(');
+                if let Some(base) = mem.base {
+                    out.push_str(&format!("%{}", base));
+                }
+                if let Some(index) = mem.index {
+                    out.push_str(&format!(",%{},{}", index, mem.scale));
+                }
+                out.push(')');
+            }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instr::{Cond, Register};
+
+    #[test]
+    fn test_nasm() {
+        let load = Instruction::Mov(
+            Operand::Reg(Register::EAX),
+            Operand::Mem(Memory::new(Register::RBP, -4).sized(Size::Dword)),
+        );
+        let jump = Instruction::Jcc(Cond::Ne, Operand::label(".L1"));
+
+        assert_eq!(load.to_string(), "    mov eax, dword [rbp-4]");
+        assert_eq!(jump.to_string(), "    jne .L1");
+    }
+
+    #[test]
+    fn test_gas() {
+        let store = Instruction::Mov(
+            Operand::Mem(Memory::new(Register::RBP, -4).sized(Size::Dword)),
+            Operand::Imm(5),
+        );
+        let push = Instruction::Push(Operand::Sym("str_0".to_owned(), 0));
+        let zx = Instruction::Movzx(Operand::Reg(Register::EAX), Operand::Reg(Register::AL));
+
+        assert_eq!(print(&store, Syntax::Gas), "    movl $5, -4(%rbp)");
+        assert_eq!(print(&push, Syntax::Gas), "    pushq $str_0");
+        assert_eq!(print(&zx, Syntax::Gas), "    movzbl %al, %eax");
+
+        let divisor = Memory::new(Register::RSP, 0).sized(Size::Qword);
+        let div = Instruction::Idiv(Operand::Mem(divisor));
+        assert_eq!(print(&div, Syntax::Gas), "    idivq (%rsp)");
+        assert_eq!(print(&Instruction::Cqo, Syntax::Gas), "    cqto");
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = "say \"hi\"\t\u{e9}\0".as_bytes();
+
+        assert_eq!(
+            print_bytes(bytes, Syntax::Nasm),
+            "db \"say \", 34, \"hi\", 34, 9, 195, 169, 0"
+        );
+        assert_eq!(print_bytes(b"a\n", Syntax::Gas), ".byte 97,10");
+        assert_eq!(print_bytes(&[], Syntax::Nasm), "db ");
+    }
+}
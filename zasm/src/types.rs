@@ -1,9 +1,13 @@
+use std::fmt;
+
+use crate::instr::{Cond, Size};
+
 #[derive(Debug)]
 pub struct StrPtr(usize);
 
-impl ToString for StrPtr {
-    fn to_string(&self) -> String {
-        format!("str_{}", self.0)
+impl fmt::Display for StrPtr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "str_{}", self.0)
     }
 }
 impl StrPtr {
@@ -12,6 +16,44 @@ impl StrPtr {
     }
 }
 
+/// How a value is stored: its width and whether it's sign extended
+/// when loaded into a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scalar {
+    pub size: Size,
+    pub signed: bool,
+    /// The bits are an IEEE 754 float, kept in general purpose registers
+    /// and only moved to SSE registers to operate on them.
+    pub float: bool,
+}
+
+impl Scalar {
+    pub const fn new(size: Size, signed: bool) -> Self {
+        Self {
+            size,
+            signed,
+            float: false,
+        }
+    }
+
+    pub const fn float(size: Size) -> Self {
+        Self {
+            size,
+            signed: true,
+            float: true,
+        }
+    }
+
+    /// Registers hold at least 32 bits, smaller values are extended
+    /// to a full 32-bit register when loaded.
+    pub fn reg_size(self) -> Size {
+        match self.size {
+            Size::Qword => Size::Qword,
+            _ => Size::Dword,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Operator {
     Add,
@@ -25,6 +67,12 @@ pub enum Operator {
     Less,
     LessEquals,
     Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    /// Arithmetic for signed integers, logical for unsigned ones.
+    ShiftRight,
 }
 
 pub struct Label(String);
@@ -35,29 +83,108 @@ impl Label {
     }
 }
 
-impl ToString for Label {
-    fn to_string(&self) -> String {
-        self.0.clone()
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
+/// Every way a jump can go, the conditional ones test the flags left by
+/// the last `cmp`. Signed orderings are less/greater, unsigned ones (and
+/// floats after `ucomiss`) are below/above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Jump {
-    NotEqual,
     Always,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Below,
+    BelowEqual,
+    Above,
+    AboveEqual,
+    Overflow,
+    NoOverflow,
+    Sign,
+    NoSign,
+    Parity,
+    NoParity,
+}
+
+const JUMPS: [(Jump, Cond); 16] = [
+    (Jump::Equal, Cond::E),
+    (Jump::NotEqual, Cond::Ne),
+    (Jump::Less, Cond::L),
+    (Jump::LessEqual, Cond::Le),
+    (Jump::Greater, Cond::G),
+    (Jump::GreaterEqual, Cond::Ge),
+    (Jump::Below, Cond::B),
+    (Jump::BelowEqual, Cond::Be),
+    (Jump::Above, Cond::A),
+    (Jump::AboveEqual, Cond::Ae),
+    (Jump::Overflow, Cond::O),
+    (Jump::NoOverflow, Cond::No),
+    (Jump::Sign, Cond::S),
+    (Jump::NoSign, Cond::Ns),
+    (Jump::Parity, Cond::P),
+    (Jump::NoParity, Cond::Np),
+];
+
+impl Jump {
+    /// The jump taken when comparing two values with `operation` holds,
+    /// `None` if it isn't a comparison.
+    pub fn comparison(operation: &Operator, signed: bool) -> Option<Self> {
+        Some(match (operation, signed) {
+            (Operator::DoubleEquals, _) => Jump::Equal,
+            (Operator::NotEquals, _) => Jump::NotEqual,
+            (Operator::Less, true) => Jump::Less,
+            (Operator::LessEquals, true) => Jump::LessEqual,
+            (Operator::Greater, true) => Jump::Greater,
+            (Operator::GreaterEquals, true) => Jump::GreaterEqual,
+            (Operator::Less, false) => Jump::Below,
+            (Operator::LessEquals, false) => Jump::BelowEqual,
+            (Operator::Greater, false) => Jump::Above,
+            (Operator::GreaterEquals, false) => Jump::AboveEqual,
+            _ => return None,
+        })
+    }
+
+    /// The condition code tested, `None` for `Always`.
+    pub fn cond(self) -> Option<Cond> {
+        JUMPS
+            .iter()
+            .find(|(jump, _)| *jump == self)
+            .map(|(_, cond)| *cond)
+    }
+
+    /// The jump taken exactly when this one isn't.
+    pub fn negate(self) -> Self {
+        let cond = self
+            .cond()
+            .expect("An unconditional jump can't be negated.")
+            .negate();
+        JUMPS
+            .iter()
+            .find(|(_, other)| *other == cond)
+            .map(|(jump, _)| *jump)
+            .expect("Every condition has a jump.")
+    }
 }
 
-impl ToString for Jump {
-    fn to_string(&self) -> String {
-        match self {
-            Jump::NotEqual => "jne".to_owned(),
-            Jump::Always => "jmp".to_owned(),
+impl fmt::Display for Jump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cond() {
+            Some(cond) => write!(f, "j{}", cond.suffix()),
+            None => write!(f, "jmp"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Label;
+    use super::{Jump, Label, Operator};
 
     #[test]
     fn test_labels() {
@@ -66,4 +193,16 @@ mod tests {
 
         assert_eq!(l.to_string(), format!(".L{}", index))
     }
+
+    #[test]
+    fn test_jumps() {
+        assert_eq!(Jump::Always.to_string(), "jmp");
+        assert_eq!(Jump::GreaterEqual.to_string(), "jge");
+        assert_eq!(Jump::BelowEqual.to_string(), "jbe");
+        assert_eq!(Jump::Less.negate(), Jump::GreaterEqual);
+        assert_eq!(Jump::Above.negate(), Jump::BelowEqual);
+        assert_eq!(Jump::Parity.negate(), Jump::NoParity);
+        assert_eq!(Jump::comparison(&Operator::Less, false), Some(Jump::Below));
+        assert_eq!(Jump::comparison(&Operator::Add, true), None);
+    }
 }